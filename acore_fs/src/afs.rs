@@ -4,6 +4,7 @@ use crate::layout::{
     DataBlock, DiskInode, DiskInodeType, SuperBlock, DISK_INODE_PER_BLOCK, DISK_INODE_SIZE,
 };
 use crate::{BlockDevice, Inode, BLOCK_SIZE};
+use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
 use spin::Mutex;
 const BITS_PER_BLOCK: usize = BLOCK_SIZE * 8;
@@ -15,6 +16,21 @@ pub struct AcoreFileSystem {
     pub data_bitmap: BitmapManager,
     pub inode_start_block: u32,
     pub data_start_block: u32,
+    /// The inodes held open, by inode id
+    pub open_inodes: BTreeMap<u32, OpenInode>,
+}
+
+/// What is kept in memory for an inode held open
+#[derive(Default)]
+pub struct OpenInode {
+    /// Number of open references
+    pub refs: usize,
+    /// Its dir entry is removed, reclaim it when the last reference is closed
+    pub unlinked: bool,
+    /// Number of the references which write the inode
+    pub writers: usize,
+    /// Number of the references which map the inode as a running program, and deny writes
+    pub exec_maps: usize,
 }
 
 impl AcoreFileSystem {
@@ -49,6 +65,7 @@ impl AcoreFileSystem {
             data_bitmap,
             inode_start_block,
             data_start_block,
+            open_inodes: BTreeMap::new(),
         };
 
         // clear all blocks
@@ -134,11 +151,25 @@ impl AcoreFileSystem {
             data_bitmap,
             inode_start_block,
             data_start_block,
+            open_inodes: BTreeMap::new(),
         }))
     }
     pub fn alloc_inode_block(&mut self) -> u32 {
         self.inode_bitmap.alloc(&self.block_device).unwrap() as u32
     }
+    /// Zero the disk inode and give its id back to the inode bitmap
+    pub fn dealloc_inode(&mut self, inode_id: u32) {
+        let (block_id, block_offset) = self.get_disk_inode_pos(inode_id);
+        let cache = get_block_cache(block_id as usize, Arc::clone(&self.block_device));
+        let mut disk_inode_lock = cache.lock();
+        disk_inode_lock
+            .as_mut_ref::<[u8; DISK_INODE_SIZE]>(block_offset)
+            .fill(0);
+        drop(disk_inode_lock);
+        drop(cache);
+        self.inode_bitmap
+            .dealloc(&self.block_device, inode_id as usize);
+    }
     pub fn alloc_data_block(&mut self) -> u32 {
         self.data_bitmap.alloc(&self.block_device).unwrap() as u32 + self.data_start_block
     }
@@ -13,6 +13,10 @@ mod vfs;
 
 
 pub const BLOCK_SIZE: usize = 512;
+/// The blocks of the file system, at the start of the disk image
+pub const FS_BLOCKS: usize = 16 * 2048;
+/// The blocks after the file system, where the kernel swaps user pages out to. 32 MiB.
+pub const SWAP_BLOCKS: usize = 8192 * 8;
 pub use vfs::Inode;
 pub use afs::AcoreFileSystem;
 pub use layout::DiskInodeType;
@@ -2,7 +2,6 @@ use crate::afs::AcoreFileSystem;
 use crate::block_dev::BlockDevice;
 use crate::block_manager::{get_block_cache, sync_all};
 use crate::layout::{DirEntry, DiskInode, DiskInodeType, DIR_ENTRY_SIZE};
-use alloc::format;
 use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
@@ -232,7 +231,7 @@ impl Inode {
         sync_all();
     }
 
-    /// Only remove the dir entry, the inode's resource is not deallocated
+    /// Only remove the dir entry, the inode's resource is not deallocated. See `reclaim`
     pub fn remove_dir_entry(&self, name: &str) -> Option<u32> {
         let cache = get_block_cache(self.block_id, Arc::clone(&self.block_device));
         let mut disk_inode_lock = cache.lock();
@@ -268,6 +267,48 @@ impl Inode {
         None
     }
 
+    /// Find the first used dir entry at or after byte `offset`, skipping holes.
+    /// Return (name, inode_id, type, offset of the next entry)
+    pub fn read_dir(&self, offset: usize) -> Option<(String, u32, DiskInodeType, usize)> {
+        let fs = self.fs.lock();
+
+        let cache = get_block_cache(self.block_id, Arc::clone(&self.block_device));
+        let disk_inode_lock = cache.lock();
+        let disk_inode = disk_inode_lock.as_ref::<DiskInode>(self.block_offset);
+        if !disk_inode.is_dir() {
+            return None;
+        }
+
+        let file_count = (disk_inode.size as usize) / DIR_ENTRY_SIZE;
+        let mut dentry = DirEntry::empty();
+        for i in offset.div_ceil(DIR_ENTRY_SIZE)..file_count {
+            assert_eq!(
+                disk_inode.read_at(
+                    i * DIR_ENTRY_SIZE,
+                    dentry.as_bytes_mut(),
+                    &self.block_device
+                ),
+                DIR_ENTRY_SIZE,
+            );
+            if dentry.is_empty() {
+                continue;
+            }
+            // the inode may live in the same block as self
+            drop(disk_inode_lock);
+            drop(cache);
+            let (block_id, block_offset) = fs.get_disk_inode_pos(dentry.inode_id());
+            let cache = get_block_cache(block_id as usize, Arc::clone(&self.block_device));
+            let type_ = cache.lock().as_ref::<DiskInode>(block_offset).type_;
+            return Some((
+                String::from(dentry.name()),
+                dentry.inode_id(),
+                type_,
+                (i + 1) * DIR_ENTRY_SIZE,
+            ));
+        }
+        None
+    }
+
     pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
         let _fs = self.fs.lock();
 
@@ -313,6 +354,94 @@ impl Inode {
 
         sync_all()
     }
+    /// Free the data blocks and the inode itself, or when the last open reference is closed.
+    /// The dir entry pointing to this inode should have been removed already.
+    pub fn reclaim(&self) {
+        let mut fs = self.fs.lock();
+        if let Some(open) = fs.open_inodes.get_mut(&(self.inode_id as u32)) {
+            open.unlinked = true;
+            return;
+        }
+        drop(fs);
+        self.free();
+    }
+    /// Take an open reference, which keeps the inode from being reclaimed until closed
+    pub fn open(&self) {
+        let mut fs = self.fs.lock();
+        fs.open_inodes.entry(self.inode_id as u32).or_default().refs += 1;
+    }
+    /// Drop an open reference taken by `open`, the last one reclaims an unlinked inode
+    pub fn close(&self) {
+        let mut fs = self.fs.lock();
+        let id = self.inode_id as u32;
+        let open = fs.open_inodes.get_mut(&id).unwrap();
+        open.refs -= 1;
+        if open.refs > 0 {
+            return;
+        }
+        let unlinked = fs.open_inodes.remove(&id).unwrap().unlinked;
+        drop(fs);
+        if unlinked {
+            self.free();
+        }
+    }
+    /// Take an open reference for writing, fails if the inode is a running program
+    pub fn open_write(&self) -> bool {
+        let mut fs = self.fs.lock();
+        let open = fs.open_inodes.entry(self.inode_id as u32).or_default();
+        if open.exec_maps > 0 {
+            return false;
+        }
+        open.refs += 1;
+        open.writers += 1;
+        true
+    }
+    /// Drop an open reference taken by `open_write`
+    pub fn close_write(&self) {
+        let mut fs = self.fs.lock();
+        fs.open_inodes
+            .get_mut(&(self.inode_id as u32))
+            .unwrap()
+            .writers -= 1;
+        drop(fs);
+        self.close();
+    }
+    /// Take an open reference for mapping the inode as a running program, which denies
+    /// writes until closed. Fails if the inode is opened for writing.
+    pub fn open_exec(&self) -> bool {
+        let mut fs = self.fs.lock();
+        let open = fs.open_inodes.entry(self.inode_id as u32).or_default();
+        if open.writers > 0 {
+            return false;
+        }
+        open.refs += 1;
+        open.exec_maps += 1;
+        true
+    }
+    /// Drop an open reference taken by `open_exec`
+    pub fn close_exec(&self) {
+        let mut fs = self.fs.lock();
+        fs.open_inodes
+            .get_mut(&(self.inode_id as u32))
+            .unwrap()
+            .exec_maps -= 1;
+        drop(fs);
+        self.close();
+    }
+    fn free(&self) {
+        self.clear();
+        let mut fs = self.fs.lock();
+        fs.dealloc_inode(self.inode_id as u32);
+        drop(fs);
+        sync_all()
+    }
+    pub fn is_dir(&self) -> bool {
+        let cache = get_block_cache(self.block_id, Arc::clone(&self.block_device));
+        let disk_inode_lock = cache.lock();
+        disk_inode_lock
+            .as_ref::<DiskInode>(self.block_offset)
+            .is_dir()
+    }
     pub fn can_clear(&self) -> bool {
         let cache = get_block_cache(self.block_id, Arc::clone(&self.block_device));
         let mut disk_inode_lock = cache.lock();
@@ -330,22 +459,21 @@ impl Inode {
             }
         }
     }
-    pub fn fstat(&self) -> String {
+    pub fn inode_id(&self) -> usize {
+        self.inode_id
+    }
+    /// Return (type, size in bytes, blocks used including the indirect ones)
+    pub fn stat(&self) -> (DiskInodeType, u32, u32) {
         let _fs = self.fs.lock();
 
         let cache = get_block_cache(self.block_id, Arc::clone(&self.block_device));
         let disk_inode_lock = cache.lock();
         let disk_inode = disk_inode_lock.as_ref::<DiskInode>(self.block_offset);
 
-        format!(
-            "Type: {:<15} Size: {:<15} Inode: {:<15} Blocks: {:<15}",
-            match disk_inode.type_ {
-                DiskInodeType::File => "File",
-                DiskInodeType::Directory => "Directory",
-            },
+        (
+            disk_inode.type_,
             disk_inode.size,
-            self.inode_id,
-            DiskInode::total_blocks(disk_inode.size)
+            DiskInode::total_blocks(disk_inode.size),
         )
     }
 }
@@ -1,4 +1,4 @@
-use acore_fs::{AcoreFileSystem, BlockDevice, BLOCK_SIZE};
+use acore_fs::{AcoreFileSystem, BlockDevice, BLOCK_SIZE, FS_BLOCKS, SWAP_BLOCKS};
 use clap::{App, Arg};
 use std::collections::HashSet;
 use std::fs::{read_dir, File, OpenOptions};
@@ -74,13 +74,13 @@ fn acore_fs_pack() -> std::io::Result<()> {
             .write(true)
             .create(true)
             .open(format!("{}{}", target_path, "fs.img"))?;
-        f.set_len(16 * 2048 * BLOCK_SIZE as u64)?;
+        f.set_len(((FS_BLOCKS + SWAP_BLOCKS) * BLOCK_SIZE) as u64)?;
         f
     })));
 
     println!(
         "Successfully created fs.img with size: {} bytes",
-        16 * 2048 * BLOCK_SIZE
+        (FS_BLOCKS + SWAP_BLOCKS) * BLOCK_SIZE
     );
 
     let apps: Vec<_> = read_dir(src_path)?
@@ -95,7 +95,7 @@ fn acore_fs_pack() -> std::io::Result<()> {
     println!("Found {} apps to pack", apps.len());
 
     // 16MiB, at most 4096 inodes
-    let afs = AcoreFileSystem::new(block_file, 16 * 2048, 4096);
+    let afs = AcoreFileSystem::new(block_file, FS_BLOCKS as u32, 4096);
 
     println!("Successfully created AcoreFileSystem");
 
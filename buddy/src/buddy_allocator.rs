@@ -1,9 +1,12 @@
 use super::buddy_list::BuddyList;
 use core::alloc::{GlobalAlloc, Layout};
-use core::cell::RefCell;
+use core::cell::UnsafeCell;
 use core::cmp::{max, min};
+use core::hint::spin_loop;
 use core::mem::size_of;
-use core::ops::Deref;
+use core::ops::{Deref, DerefMut};
+use core::ptr::null_mut;
+use core::sync::atomic::{AtomicBool, Ordering};
 // use crate::config::*;
 
 pub struct HeapInner {
@@ -17,11 +20,26 @@ impl HeapInner {
         }
     }
 
+    /// Set up the free lists and give the memory [start, start + size) to the heap
+    ///
+    /// # Safety
+    ///
+    /// The range must be valid memory which nothing else uses. The heap owns it from then on,
+    /// and hands it out through `alloc`.
     pub unsafe fn init(&mut self, start: usize, size: usize) {
         for i in 0..32 {
             self.free_lists[i].init(i);
         }
+        self.add_to_heap(start, size);
+    }
 
+    /// Add the memory [start, start + size) to the heap, it needn't be adjacent to the existing one
+    ///
+    /// # Safety
+    ///
+    /// The range must be valid memory which nothing else uses, and not already in the heap.
+    /// The heap owns it from then on.
+    pub unsafe fn add_to_heap(&mut self, start: usize, size: usize) {
         // start shifted to the right and end shifted to the left, aligned to the size of usize
         let aligned_start = (start + size_of::<usize>() - 1) & (!size_of::<usize>() + 1);
         let aligned_end = (start + size) & (!size_of::<usize>() + 1);
@@ -55,7 +73,8 @@ impl HeapInner {
                 return Some(ptr as *mut u8);
             }
         }
-        panic!("[alloc] Out of memory");
+        // out of memory
+        None
     }
 
     pub fn dealloc(&mut self, mut ptr: *mut u8, layout: Layout) {
@@ -82,19 +101,65 @@ fn prev_power_of_two(num: usize) -> usize {
     1 << (8 * (size_of::<usize>()) - num.leading_zeros() as usize - 1)
 }
 
-pub struct Heap(RefCell<HeapInner>);
+/// A heap shared by the harts, or the threads of a process, behind a spin lock
+pub struct Heap {
+    locked: AtomicBool,
+    inner: UnsafeCell<HeapInner>,
+    /// Called to add memory to the heap when it runs out, then the allocation is retried once
+    rescue: Option<fn(&mut HeapInner, &Layout)>,
+}
 
 impl Heap {
     pub const fn new() -> Self {
-        Self(RefCell::new(HeapInner::new()))
+        Self {
+            locked: AtomicBool::new(false),
+            inner: UnsafeCell::new(HeapInner::new()),
+            rescue: None,
+        }
+    }
+
+    pub const fn with_rescue(rescue: fn(&mut HeapInner, &Layout)) -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            inner: UnsafeCell::new(HeapInner::new()),
+            rescue: Some(rescue),
+        }
     }
+
+    /// Spin until the heap is free, then hold it until the guard is dropped
+    pub fn lock(&self) -> HeapGuard<'_> {
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            spin_loop();
+        }
+        HeapGuard { heap: self }
+    }
+}
+
+pub struct HeapGuard<'a> {
+    heap: &'a Heap,
 }
 
-impl Deref for Heap {
-    type Target = RefCell<HeapInner>;
+impl Deref for HeapGuard<'_> {
+    type Target = HeapInner;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        unsafe { &*self.heap.inner.get() }
+    }
+}
+
+impl DerefMut for HeapGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.heap.inner.get() }
+    }
+}
+
+impl Drop for HeapGuard<'_> {
+    fn drop(&mut self) {
+        self.heap.locked.store(false, Ordering::Release);
     }
 }
 
@@ -102,10 +167,20 @@ unsafe impl Sync for Heap {}
 
 unsafe impl GlobalAlloc for Heap {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        self.0.borrow_mut().alloc(layout).unwrap_or(0 as *mut u8)
+        let mut inner = self.lock();
+        if let Some(ptr) = inner.alloc(layout) {
+            return ptr;
+        }
+        match self.rescue {
+            Some(rescue) => {
+                rescue(&mut inner, &layout);
+                inner.alloc(layout).unwrap_or(null_mut())
+            }
+            None => null_mut(),
+        }
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
-        self.0.borrow_mut().dealloc(ptr, layout);
+        self.lock().dealloc(ptr, layout);
     }
 }
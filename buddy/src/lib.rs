@@ -4,7 +4,7 @@ extern crate alloc;
 mod buddy_allocator;
 mod buddy_list;
 
-pub use buddy_allocator::Heap;
+pub use buddy_allocator::{Heap, HeapInner};
 
 // use crate::println;
 // use alloc::format;
@@ -0,0 +1,16 @@
+//! Embed the text symbols of the kernel for symbolizing backtraces.
+//! `make kernel` writes them to target/kernel.sym after a build, and builds again if they changed.
+//! The code doesn't move when they change, since they are placed after it in .rodata.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+fn main() {
+    let syms = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("target/kernel.sym");
+    println!("cargo:rerun-if-changed={}", syms.display());
+    // no symbols on the first build
+    let data = fs::read(&syms).unwrap_or_default();
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("kernel.sym");
+    fs::write(out, data).unwrap();
+}
@@ -2,7 +2,10 @@ pub const VIRT_TEST: usize = 0x10_0000;
 
 pub const FINISHER_PASS: usize = 0x5555;
 
-pub const KERNEL_HEAP_SIZE: usize = 0x30_0000;
+/// The heap also keeps track of every user page, in a frame or swapped out. That's about 250
+/// bytes per page after the buddy allocator rounds them up, or 10 MiB for the 32768 frames and
+/// `SWAP_PAGES` swapped out, so 3 MiB runs out long before the memory does.
+pub const KERNEL_HEAP_SIZE: usize = 0x100_0000;
 
 pub const PAGE_SIZE: usize = 0x1000;
 pub const PAGE_SIZE_BITS: usize = 0xc;
@@ -11,6 +14,17 @@ pub const VA_WIDTH_SV39: usize = 39;
 pub const PPN_WIDTH_SV39: usize = PA_WIDTH_SV39 - PAGE_SIZE_BITS;
 pub const VPN_WIDTH_SV39: usize = VA_WIDTH_SV39 - PAGE_SIZE_BITS;
 
+/// User pointers must be below it, the upper half of the address space is the kernel's
+pub const USER_SPACE_END: usize = 1 << (VA_WIDTH_SV39 - 1);
+/// The max length of a string passed to syscalls, like a path or an arg of exec
+pub const MAX_USER_STR_LEN: usize = PAGE_SIZE;
+/// The heap of a user process starts here and grows up by brk, far away from the user stacks
+pub const USER_HEAP_BASE: usize = 0x10_0000_0000;
+/// The max size of the heap of a user process
+pub const USER_HEAP_LIMIT: usize = 0x1000_0000;
+/// mmap places the mappings from here up to `USER_SPACE_END`
+pub const USER_MMAP_BASE: usize = 0x20_0000_0000;
+
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1; // 0xffff_ffff_ffff_f000
 /// The base address of the trap ctx of thread 0 in each process.
 pub const TRAP_CONTEXT_BASE: usize = TRAMPOLINE - PAGE_SIZE; // 0xffff_ffff_ffff_e000
@@ -23,9 +37,28 @@ pub const VIRT_UART0_SIZE: usize = 0x100;
 pub const VIRT_UART_VIRTIO: usize = 0x1000_1000;
 pub const VIRT_UART_VIRTIO_SIZE: usize = 0x1000;
 
+/// The machine software interrupt pending bits of the harts, 32 bits each
+pub const MSIP: usize = 0x0200_0000;
 pub const MTIME: usize = 0x0200_bff8;
 pub const MTIMECMP: usize = 0x0200_4000;
 
 pub const MEMORY_END: usize = 0x8800_0000;
-pub const USER_STACK_SIZE: usize = 4096 * 4;
+/// User pages are swapped out to the blocks after the file system, which `acore_fs_fuse` leaves
+pub const SWAP_START_BLOCK: usize = acore_fs::FS_BLOCKS;
+/// The max number of pages swapped out
+pub const SWAP_PAGES: usize = acore_fs::SWAP_BLOCKS / (PAGE_SIZE / acore_fs::BLOCK_SIZE);
+/// The space reserved for each user stack, which grows down into it on demand.
+/// It's also the hard limit of the stack size.
+pub const USER_STACK_MAX: usize = 0x80_0000;
+/// The unmapped gap below each user stack, an overflow faults in it
+pub const USER_STACK_GUARD: usize = PAGE_SIZE;
 pub const KERNEL_STACK_SIZE: usize = 4096 * 4;
+/// The max number of harts, the others are parked at boot. Keep it the same as in entry.asm.
+pub const MAX_HARTS: usize = 8;
+
+/// The time slice of the round robin scheduler (feature sched-rr), in timer ticks
+pub const RR_TIME_SLICE: usize = 5;
+
+/// Whether a process making an unknown syscall gets SIGSYS, which kills it unless handled.
+/// The syscall returns -ENOSYS either way.
+pub const SIGSYS_ON_UNKNOWN_SYSCALL: bool = false;
@@ -4,6 +4,11 @@ use core::{arch::asm, panic::PanicInfo};
 use crate::config::*;
 use log::*;
 use crate::println;
+use crate::trap::print_backtrace;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Set by the first panic, so a panic in printing the backtrace doesn't print it again
+static PANICKED: AtomicBool = AtomicBool::new(false);
 
 #[panic_handler]
 /// panic handler
@@ -18,6 +23,9 @@ fn panic(info: &PanicInfo) -> ! {
     } else {
         error!("[kernel] Panicked: {}", info.message().unwrap());
     }
+    if !PANICKED.swap(true, Ordering::Relaxed) {
+        print_backtrace();
+    }
     shutdown()
 }
 
@@ -1,14 +1,112 @@
 /// For simplicity, only implement a poll-based stdin.
+/// There's no external interrupt from the UART, so the readers are woken up at each tick.
+///
+/// Like a terminal, Ctrl-C and Ctrl-Z typed while a process group is in the foreground send it
+/// SIGINT and SIGTSTP instead of being read.
 
 
 use super::mmio::UART;
-use crate::proc::switch_thread;
-pub fn getchar() -> u8 {
-    loop {
-        if let Some(c) = UART.recv() {
-            return c;
+use crate::println;
+use crate::proc::{
+    block_thread, check_interrupt, get_cur_proc, get_cur_thread, pgid2pcbs, send_signal,
+    wakeup_thread, SignalFlags, ThreadControlBlock,
+};
+use crate::sync::SpinLock;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use lazy_static::lazy_static;
+
+const CTRL_C: u8 = 0x03;
+const CTRL_Z: u8 = 0x1a;
+
+/// No process group is in the foreground, the reader gets Ctrl-C and Ctrl-Z as they are
+pub const NO_FOREGROUND: usize = usize::MAX;
+
+/// The process group the console keys signal
+static FOREGROUND: AtomicUsize = AtomicUsize::new(NO_FOREGROUND);
+
+lazy_static! {
+    /// The bytes received and not read yet
+    static ref INPUT: SpinLock<VecDeque<u8>> = SpinLock::new(VecDeque::new());
+    /// The threads waiting for a byte from the console
+    static ref READERS: SpinLock<VecDeque<Arc<ThreadControlBlock>>> =
+        SpinLock::new(VecDeque::new());
+}
+
+pub fn set_foreground(pgid: usize) {
+    FOREGROUND.store(pgid, Ordering::Relaxed);
+}
+
+/// Move the bytes received by the UART to the input, and signal the foreground group for
+/// the keys it should get
+fn poll_uart() {
+    let foreground = FOREGROUND.load(Ordering::Relaxed);
+    let mut signals = Vec::new();
+    let mut input = INPUT.exclusive_access();
+    while let Some(c) = UART.recv() {
+        match c {
+            CTRL_C if foreground != NO_FOREGROUND => signals.push(SignalFlags::SIGINT),
+            CTRL_Z if foreground != NO_FOREGROUND => signals.push(SignalFlags::SIGTSTP),
+            _ => input.push_back(c),
+        }
+    }
+    drop(input);
+    for signal in signals {
+        if signal == SignalFlags::SIGINT {
+            println!("^C");
         } else {
-            switch_thread();
+            println!("^Z");
+        }
+        for proc in pgid2pcbs(foreground) {
+            send_signal(&proc, signal);
         }
     }
 }
+
+/// Wait for a byte from the console. None if a signal interrupts the wait.
+pub fn getchar() -> Option<u8> {
+    let cur_thread = get_cur_thread().unwrap();
+    let ret = loop {
+        // wait in the queue before looking at the input and the signals, so none is missed
+        READERS.exclusive_access().push_back(cur_thread.clone());
+        if check_interrupt() {
+            break None;
+        }
+        // a stopped process leaves the input to the others
+        if !get_cur_proc().exclusive_access().frozen {
+            poll_uart();
+            if let Some(c) = INPUT.exclusive_access().pop_front() {
+                break Some(c);
+            }
+        }
+        block_thread();
+    };
+    READERS
+        .exclusive_access()
+        .retain(|reader| !Arc::ptr_eq(reader, &cur_thread));
+    ret
+}
+
+/// Wake up the readers, e.g. to look at a signal sent to them
+pub fn wakeup_readers() {
+    let readers = core::mem::take(&mut *READERS.exclusive_access());
+    for reader in readers {
+        wakeup_thread(reader);
+    }
+}
+
+/// Wake up the readers if a byte has arrived, the first one takes it and the others wait again
+pub fn check_console() {
+    poll_uart();
+    if !INPUT.exclusive_access().is_empty() {
+        wakeup_readers();
+    }
+}
+
+/// Number of bytes that can be read without blocking
+pub fn available() -> usize {
+    poll_uart();
+    INPUT.exclusive_access().len()
+}
@@ -1,5 +1,15 @@
-use core::fmt::{self, Write};
 use super::mmio::UART;
+use crate::proc::hart_id;
+use core::fmt::{self, Write};
+use core::hint::spin_loop;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// No hart is printing
+const NO_HART: usize = usize::MAX;
+
+/// The hart printing, so the lines of different harts don't mix.
+/// It's not a SpinLock, since a hart panicking in the middle of printing has to print again.
+static PRINTING: AtomicUsize = AtomicUsize::new(NO_HART);
 
 struct Stdout;
 
@@ -13,7 +23,20 @@ impl Write for Stdout {
 }
 
 pub fn print(args: fmt::Arguments) {
+    let hart = hart_id();
+    let reentered = PRINTING.load(Ordering::Relaxed) == hart;
+    if !reentered {
+        while PRINTING
+            .compare_exchange(NO_HART, hart, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            spin_loop();
+        }
+    }
     Stdout.write_fmt(args).unwrap();
+    if !reentered {
+        PRINTING.store(NO_HART, Ordering::Release);
+    }
 }
 
 /// print string macro
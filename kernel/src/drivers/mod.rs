@@ -1,14 +1,14 @@
 use alloc::sync::Arc;
 use crate::mm::{
-    frame_alloc, frame_dealloc, FrameGuard, PhysAddr, PhysPageNum, VirtAddr,
-    KERNEL_MM,
+    frame_alloc, frame_dealloc, FrameGuard, PageTable, PhysAddr, PhysPageNum, VirtAddr,
 };
-use crate::sync::UPSafeCell;
+use crate::sync::SpinLock;
 use acore_fs::BlockDevice;
 use alloc::vec::Vec;
 use lazy_static::lazy_static;
+use riscv::register::satp;
 use virtio_drivers::{Hal, VirtIOBlk, VirtIOHeader};
-pub struct VirtIOBlock(UPSafeCell<VirtIOBlk<'static, VirtIOHal>>);
+pub struct VirtIOBlock(SpinLock<VirtIOBlk<'static, VirtIOHal>>);
 
 impl BlockDevice for VirtIOBlock {
     fn read_block(&self, block_id: usize, buf: &mut [u8]) {
@@ -30,7 +30,7 @@ impl VirtIOBlock {
     #[allow(unused)]
     pub fn new() -> Self {
         unsafe {
-            Self(UPSafeCell::new(
+            Self(SpinLock::new(
                 VirtIOBlk::<VirtIOHal>::new(&mut *(VIRTIO0 as *mut VirtIOHeader)).unwrap(),
             ))
         }
@@ -40,7 +40,7 @@ impl VirtIOBlock {
 pub struct VirtIOHal;
 
 lazy_static! {
-    static ref DMA_FRAMES: UPSafeCell<Vec<FrameGuard>> = unsafe { UPSafeCell::new(Vec::new()) };
+    static ref DMA_FRAMES: SpinLock<Vec<FrameGuard>> = SpinLock::new(Vec::new());
 }
 
 impl Hal for VirtIOHal {
@@ -72,9 +72,9 @@ impl Hal for VirtIOHal {
     }
 
     fn virt_to_phys(va: usize) -> usize {
-        KERNEL_MM
-            .exclusive_access()
-            .page_table
+        // walk the current page table, since the I/O for swapping may be in the middle of
+        // changing the kernel space, e.g. allocating a kernel stack
+        PageTable::from_token(satp::read().bits())
             .find_pa(VirtAddr::from(va))
             .unwrap()
             .0
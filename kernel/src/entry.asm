@@ -1,12 +1,23 @@
     .section .text.entry
     .globl _start
 _start:
-    la sp, boot_stack_top
+    # every hart starts here, each with its own boot stack of 64 KiB.
+    # The harts beyond MAX_HARTS (8) in config.rs are parked.
+    csrr t0, mhartid
+    li t1, 8
+    bgeu t0, t1, park
+    addi t0, t0, 1
+    slli t0, t0, 16
+    la sp, boot_stack_lower_bound
+    add sp, sp, t0
     call from_m_to_s
+park:
+    wfi
+    j park
 
     .section .bss.stack
     .globl boot_stack_lower_bound
 boot_stack_lower_bound:
-    .space 4096 * 16
+    .space 4096 * 16 * 8
     .globl boot_stack_top
-boot_stack_top:
\ No newline at end of file
+boot_stack_top:
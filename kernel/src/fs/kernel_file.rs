@@ -1,9 +1,10 @@
 use crate::drivers::BLOCK_DEVICE;
-use crate::fs::File;
-use crate::sync::UPSafeCell;
+use crate::errno::Errno;
+use crate::fs::path::Path;
+use crate::fs::{Dirent, File, Stat, StatMode, DT_DIR, DT_REG, SEEK_CUR, SEEK_END, SEEK_SET};
+use crate::sync::SpinLock;
 use acore_fs::AcoreFileSystem;
 use acore_fs::{DiskInodeType, Inode, BLOCK_SIZE};
-use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use bitflags::bitflags;
@@ -20,8 +21,14 @@ bitflags! {
         const RDWR = 1 << 1;
         ///Allow create
         const CREATE = 1 << 9;
+        ///Fail if CREATE is set and the file exists
+        const EXCL = 1 << 7;
         ///Clear file and return an empty one
         const TRUNC = 1 << 10;
+        ///Every write goes to the end of the file
+        const APPEND = 1 << 11;
+        ///Fail if the file is not a directory
+        const DIRECTORY = 1 << 16;
     }
 }
 
@@ -29,12 +36,12 @@ impl OpenFlags {
     /// Do not check validity for simplicity
     /// Return (readable, writable)
     pub fn read_write(&self) -> (bool, bool) {
-        if self.is_empty() {
-            (true, false)
-        } else if self.contains(Self::WRONLY) {
+        if self.contains(Self::WRONLY) {
             (false, true)
-        } else {
+        } else if self.contains(Self::RDWR) {
             (true, true)
+        } else {
+            (true, false)
         }
     }
 }
@@ -42,19 +49,32 @@ impl OpenFlags {
 pub struct KernelFile {
     readable: bool,
     writable: bool,
-    inner: UPSafeCell<KernelFileInner>,
+    append: bool,
+    inner: SpinLock<KernelFileInner>,
 }
 pub struct KernelFileInner {
     offset: usize,
     inode: Arc<Inode>,
 }
 impl KernelFile {
-    pub fn new(readable: bool, writable: bool, inode: Arc<Inode>) -> Self {
-        Self {
+    /// A running program can't be opened for writing
+    pub fn new(readable: bool, writable: bool, inode: Arc<Inode>) -> Result<Self, Errno> {
+        if !writable {
+            inode.open();
+        } else if !inode.open_write() {
+            return Err(Errno::ETXTBSY);
+        }
+        Ok(Self {
             readable,
             writable,
-            inner: unsafe { UPSafeCell::new(KernelFileInner { offset: 0, inode }) },
-        }
+            append: false,
+            inner: SpinLock::new(KernelFileInner { offset: 0, inode }),
+        })
+    }
+
+    /// The inode of the file, e.g. for loading an ELF lazily
+    pub fn inode(&self) -> Arc<Inode> {
+        self.inner.exclusive_access().inode.clone()
     }
 
     /// Read all data from the kernel file into a vector
@@ -72,51 +92,61 @@ impl KernelFile {
         }
         v
     }
-    pub fn from_path(path: &str, flags: OpenFlags) -> Option<Arc<Self>> {
+    /// Open the file at `path`. A relative path is resolved against `cwd`.
+    /// With CREATE, a path ending with '/' or DIRECTORY creates a directory.
+    pub fn from_path(cwd: &str, path: &str, flags: OpenFlags) -> Result<Arc<Self>, Errno> {
         // println!("from_path {:?}", path);
-        let is_dir = path.ends_with('/');
-        let mut path = path.split('/').skip(1).collect::<Vec<_>>();
-
-        let file_name = path.pop().unwrap();
-        let mut inode = ROOT.clone();
-        if path.len() > 0 {
-            for dir_entry in path[..path.len() - 1].iter() {
-                inode = inode.access_dir_entry(*dir_entry, DiskInodeType::Directory, false)?;
-            }
-        }
+        let path = Path::new(cwd, path).ok_or(Errno::ENOENT)?;
         let (readable, writable) = flags.read_write();
-        let create = if flags.contains(OpenFlags::CREATE) {
-            true
-        } else {
-            false
-        };
-        if is_dir && create {
-            assert!(file_name.is_empty());
-            assert!(path.len() > 0);
-            inode = inode.access_dir_entry(path[path.len() - 1], DiskInodeType::Directory, true)?;
-        } else {
-            if path.len() > 0 {
-                inode = inode.access_dir_entry(
-                    path[path.len() - 1],
-                    DiskInodeType::Directory,
-                    false,
-                )?;
-            }
-            let type_ = if is_dir {
-                assert!(file_name.is_empty());
-                DiskInodeType::Directory
-            } else {
-                DiskInodeType::File
-            };
-            inode = inode.access_dir_entry(file_name, type_, create)?;
-            if flags.contains(OpenFlags::TRUNC) {
-                inode.clear();
+        let create = flags.contains(OpenFlags::CREATE);
+        let is_dir = path.is_dir || flags.contains(OpenFlags::DIRECTORY);
+        let inode = match path.name() {
+            Some(name) => {
+                let parent = path.parent_inode().ok_or(Errno::ENOENT)?;
+                match parent.access_dir_entry(name, DiskInodeType::File, false) {
+                    Some(_) if create && flags.contains(OpenFlags::EXCL) => {
+                        return Err(Errno::EEXIST)
+                    }
+                    Some(inode) => inode,
+                    None if create => {
+                        let type_ = if is_dir {
+                            DiskInodeType::Directory
+                        } else {
+                            DiskInodeType::File
+                        };
+                        parent
+                            .access_dir_entry(name, type_, true)
+                            .ok_or(Errno::ENOSPC)?
+                    }
+                    None => return Err(Errno::ENOENT),
+                }
             }
+            None if create && flags.contains(OpenFlags::EXCL) => return Err(Errno::EEXIST),
+            None => ROOT.clone(),
+        };
+        if is_dir && !inode.is_dir() {
+            return Err(Errno::ENOTDIR);
+        }
+        let mut file = Self::new(readable, writable, inode.clone())?;
+        // truncating is writing, which is checked for a running program above
+        if writable && !inode.is_dir() && flags.contains(OpenFlags::TRUNC) {
+            inode.clear();
         }
-        Some(Arc::new(Self::new(readable, writable, inode)))
+        file.append = flags.contains(OpenFlags::APPEND);
+        Ok(Arc::new(file))
     }
+}
 
-
+impl Drop for KernelFile {
+    /// A removed file is reclaimed when the last file opening it is closed
+    fn drop(&mut self) {
+        let inode = &self.inner.exclusive_access().inode;
+        if self.writable {
+            inode.close_write();
+        } else {
+            inode.close();
+        }
+    }
 }
 
 impl File for KernelFile {
@@ -137,20 +167,79 @@ impl File for KernelFile {
 
     fn write(&self, buf: &[u8]) -> usize {
         let mut inner = self.inner.exclusive_access();
+        if self.append {
+            inner.offset = inner.inode.stat().1 as usize;
+        }
         let write_size = inner.inode.write_at(inner.offset, buf);
         assert_eq!(write_size, buf.len());
         inner.offset += write_size;
         write_size
     }
 
-    fn seek(&self, offset: usize) -> usize {
+    fn seek(&self, offset: isize, whence: usize) -> Result<usize, Errno> {
         let mut inner = self.inner.exclusive_access();
-        inner.offset = offset;
-        inner.offset
+        let base = match whence {
+            SEEK_SET => 0,
+            SEEK_CUR => inner.offset as isize,
+            SEEK_END => inner.inode.stat().1 as isize,
+            _ => return Err(Errno::EINVAL),
+        };
+        match base.checked_add(offset) {
+            Some(new_offset) if new_offset >= 0 => {
+                inner.offset = new_offset as usize;
+                Ok(new_offset as usize)
+            }
+            _ => Err(Errno::EINVAL),
+        }
     }
 
-    fn stat(&self) -> String {
-        self.inner.exclusive_access().inode.fstat()
+    fn getdents(&self, buf: &mut [u8]) -> Result<usize, Errno> {
+        let mut inner = self.inner.exclusive_access();
+        if !inner.inode.is_dir() {
+            return Err(Errno::ENOTDIR);
+        }
+        let mut written = 0;
+        while let Some((name, ino, type_, next)) = inner.inode.read_dir(inner.offset) {
+            let reclen = Dirent::reclen(name.len());
+            if written + reclen > buf.len() {
+                if written == 0 {
+                    // buffer cannot hold even one entry
+                    return Err(Errno::EINVAL);
+                }
+                break;
+            }
+            let dirent = Dirent {
+                ino: ino as u64,
+                off: next as u64,
+                reclen: reclen as u16,
+                type_: match type_ {
+                    DiskInodeType::File => DT_REG,
+                    DiskInodeType::Directory => DT_DIR,
+                },
+            };
+            dirent.write_to(&name, &mut buf[written..written + reclen]);
+            written += reclen;
+            inner.offset = next;
+        }
+        Ok(written)
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, Errno> {
+        let inner = self.inner.exclusive_access();
+        if inner.inode.is_dir() {
+            return Err(Errno::ENODEV);
+        }
+        Ok(inner.inode.read_at(offset, buf))
+    }
+
+    fn stat(&self) -> Stat {
+        let inner = self.inner.exclusive_access();
+        let (type_, size, blocks) = inner.inode.stat();
+        let mode = match type_ {
+            DiskInodeType::File => StatMode::FILE,
+            DiskInodeType::Directory => StatMode::DIR,
+        };
+        Stat::new(inner.inode.inode_id() as u64, mode, size as u64, blocks as u64)
     }
 }
 
@@ -159,6 +248,5 @@ lazy_static! {
         let afs = AcoreFileSystem::open(BLOCK_DEVICE.clone());
         AcoreFileSystem::root_inode(afs)
     };
-    pub static ref CWD: UPSafeCell<String> = unsafe { UPSafeCell::new(String::from("/")) };
 }
 
@@ -1,6 +1,8 @@
-use alloc::string::String;
+use crate::errno::Errno;
+use bitflags::bitflags;
 
 pub mod kernel_file;
+pub mod path;
 pub mod stdio;
 pub mod pipe;
 
@@ -10,8 +12,92 @@ pub trait File: Send + Sync {
     fn writable(&self) -> bool;
     fn read(&self, buf: &mut [u8]) -> usize;
     fn write(&self, buf: &[u8]) -> usize;
-    #[allow(unused)]
-    fn seek(&self, offset: usize) -> usize;
-    #[allow(unused)]
-    fn stat(&self) -> String;
+    /// Move the offset according to `whence`, return the new offset
+    fn seek(&self, offset: isize, whence: usize) -> Result<usize, Errno>;
+    fn stat(&self) -> Stat;
+    /// Fill `buf` with packed `Dirent`s, return the bytes written, 0 at the end
+    /// of the directory
+    fn getdents(&self, _buf: &mut [u8]) -> Result<usize, Errno> {
+        Err(Errno::ENOTDIR)
+    }
+    /// Read at `offset` without moving the offset of the file, for mmap
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> Result<usize, Errno> {
+        Err(Errno::ENODEV)
+    }
+}
+
+/// whence of sys_lseek
+pub const SEEK_SET: usize = 0;
+pub const SEEK_CUR: usize = 1;
+pub const SEEK_END: usize = 2;
+
+/// d_type of a `Dirent`, the same values as Linux
+pub const DT_DIR: u8 = 4;
+pub const DT_REG: u8 = 8;
+
+/// Header of an entry returned by sys_getdents, laid out as Linux's dirent64.
+/// The NUL-terminated name follows at `DIRENT_NAME_OFFSET`,
+/// and the whole record is padded to `reclen`, a multiple of 8.
+pub struct Dirent {
+    pub ino: u64,
+    /// offset of the next entry in the directory
+    pub off: u64,
+    pub reclen: u16,
+    pub type_: u8,
+}
+pub const DIRENT_NAME_OFFSET: usize = 19;
+
+impl Dirent {
+    /// Record length needed for a name of `name_len` bytes
+    pub fn reclen(name_len: usize) -> usize {
+        (DIRENT_NAME_OFFSET + name_len + 1).next_multiple_of(8)
+    }
+    /// Write the record into `buf`, which must be `reclen` long
+    pub fn write_to(&self, name: &str, buf: &mut [u8]) {
+        buf.fill(0);
+        buf[0..8].copy_from_slice(&self.ino.to_le_bytes());
+        buf[8..16].copy_from_slice(&self.off.to_le_bytes());
+        buf[16..18].copy_from_slice(&self.reclen.to_le_bytes());
+        buf[18] = self.type_;
+        buf[DIRENT_NAME_OFFSET..DIRENT_NAME_OFFSET + name.len()].copy_from_slice(name.as_bytes());
+    }
+}
+
+bitflags! {
+    /// File type in `Stat`, the same values as st_mode in Linux
+    pub struct StatMode: u32 {
+        const NULL = 0;
+        /// pipe
+        const FIFO = 0o010000;
+        /// character device, i.e. stdin and stdout
+        const CHR = 0o020000;
+        const DIR = 0o040000;
+        const FILE = 0o100000;
+    }
+}
+
+/// File status, written back to the user by sys_fstat
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Stat {
+    /// inode number, 0 if the file is not on disk
+    pub ino: u64,
+    pub mode: StatMode,
+    pad: u32,
+    /// size in bytes
+    pub size: u64,
+    /// number of 512B blocks allocated
+    pub blocks: u64,
+}
+
+impl Stat {
+    pub fn new(ino: u64, mode: StatMode, size: u64, blocks: u64) -> Self {
+        Self {
+            ino,
+            mode,
+            pad: 0,
+            size,
+            blocks,
+        }
+    }
 }
@@ -0,0 +1,81 @@
+//! Path resolution shared by all path-taking syscalls
+
+use crate::fs::kernel_file::ROOT;
+use acore_fs::{DiskInodeType, Inode};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// A path resolved against a cwd. `.`, `..` and redundant slashes are folded away.
+#[derive(PartialEq)]
+pub struct Path {
+    /// components from the root
+    components: Vec<String>,
+    /// the path ends with '/', so it must name a directory
+    pub is_dir: bool,
+}
+
+impl Path {
+    /// `cwd` is an absolute path ending with '/'. Return None for an empty path.
+    pub fn new(cwd: &str, path: &str) -> Option<Self> {
+        if path.is_empty() {
+            return None;
+        }
+        let base = if path.starts_with('/') { "" } else { cwd };
+        let mut components: Vec<String> = Vec::new();
+        for part in base.split('/').chain(path.split('/')) {
+            match part {
+                "" | "." => {}
+                // `..` of the root is the root itself
+                ".." => {
+                    components.pop();
+                }
+                _ => components.push(String::from(part)),
+            }
+        }
+        let last = path.rsplit('/').next().unwrap();
+        Some(Self {
+            components,
+            is_dir: matches!(last, "" | "." | ".."),
+        })
+    }
+    /// The last component, None for the root
+    pub fn name(&self) -> Option<&str> {
+        self.components.last().map(|s| s.as_str())
+    }
+    /// Absolute form of the path, directories end with '/'
+    pub fn to_string(&self, as_dir: bool) -> String {
+        let mut s = String::from("/");
+        for part in self.components.iter() {
+            s.push_str(part);
+            s.push('/');
+        }
+        if !as_dir && !self.components.is_empty() {
+            s.pop();
+        }
+        s
+    }
+    /// Walk down to the directory containing the last component
+    pub fn parent_inode(&self) -> Option<Arc<Inode>> {
+        let mut inode = ROOT.clone();
+        if let Some((_, dirs)) = self.components.split_last() {
+            for dir in dirs {
+                inode = inode.access_dir_entry(dir, DiskInodeType::Directory, false)?;
+            }
+        }
+        Some(inode)
+    }
+    /// Walk down to the inode the path names
+    pub fn inode(&self) -> Option<Arc<Inode>> {
+        let inode = match self.name() {
+            Some(name) => self
+                .parent_inode()?
+                .access_dir_entry(name, DiskInodeType::File, false)?,
+            None => ROOT.clone(),
+        };
+        if self.is_dir && !inode.is_dir() {
+            return None;
+        }
+        Some(inode)
+    }
+}
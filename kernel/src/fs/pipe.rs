@@ -1,24 +1,25 @@
-use alloc::string::String;
-use super::File;
-use crate::sync::UPSafeCell;
+use super::{File, Stat, StatMode};
+use crate::errno::Errno;
+use crate::sync::{SpinLock, SpinLockGuard};
+use alloc::collections::VecDeque;
 use alloc::sync::{Arc, Weak};
-use crate::proc::switch_thread;
+use crate::proc::{block_thread, get_cur_thread, wakeup_thread, ThreadControlBlock};
 
 pub struct Pipe {
     readable: bool,
     writable: bool,
-    buffer: Arc<UPSafeCell<PipeRingBuffer>>,
+    buffer: Arc<SpinLock<PipeRingBuffer>>,
 }
 
 impl Pipe {
-    pub fn read_end_with_buffer(buffer: Arc<UPSafeCell<PipeRingBuffer>>) -> Self {
+    pub fn read_end_with_buffer(buffer: Arc<SpinLock<PipeRingBuffer>>) -> Self {
         Self {
             readable: true,
             writable: false,
             buffer,
         }
     }
-    pub fn write_end_with_buffer(buffer: Arc<UPSafeCell<PipeRingBuffer>>) -> Self {
+    pub fn write_end_with_buffer(buffer: Arc<SpinLock<PipeRingBuffer>>) -> Self {
         Self {
             readable: false,
             writable: true,
@@ -42,6 +43,16 @@ pub struct PipeRingBuffer {
     tail: usize,
     status: RingBufferStatus,
     write_end: Option<Weak<Pipe>>,
+    /// The threads waiting for bytes to read
+    read_waiters: VecDeque<Arc<ThreadControlBlock>>,
+    /// The threads waiting for room to write
+    write_waiters: VecDeque<Arc<ThreadControlBlock>>,
+}
+
+fn wakeup_all(waiters: &mut VecDeque<Arc<ThreadControlBlock>>) {
+    for thread in waiters.drain(..) {
+        wakeup_thread(thread);
+    }
 }
 
 impl PipeRingBuffer {
@@ -52,6 +63,8 @@ impl PipeRingBuffer {
             tail: 0,
             status: RingBufferStatus::Empty,
             write_end: None,
+            read_waiters: VecDeque::new(),
+            write_waiters: VecDeque::new(),
         }
     }
     pub fn set_write_end(&mut self, write_end: &Arc<Pipe>) {
@@ -93,11 +106,23 @@ impl PipeRingBuffer {
     pub fn all_write_ends_closed(&self) -> bool {
         self.write_end.as_ref().unwrap().upgrade().is_none()
     }
+    /// Block the current thread until some bytes are written. The buffer is released meanwhile.
+    fn wait_for_read(mut this: SpinLockGuard<'_, Self>) {
+        this.read_waiters.push_back(get_cur_thread().unwrap());
+        drop(this);
+        block_thread();
+    }
+    /// Block the current thread until some bytes are read. The buffer is released meanwhile.
+    fn wait_for_write(mut this: SpinLockGuard<'_, Self>) {
+        this.write_waiters.push_back(get_cur_thread().unwrap());
+        drop(this);
+        block_thread();
+    }
 }
 
 /// Creates a pair of pipes (read end, write end).
 pub fn make_pipe_pair() -> (Arc<Pipe>, Arc<Pipe>) {
-    let buffer = Arc::new(unsafe { UPSafeCell::new(PipeRingBuffer::new()) });
+    let buffer = Arc::new(SpinLock::new(PipeRingBuffer::new()));
     let read_end = Arc::new(Pipe::read_end_with_buffer(buffer.clone()));
     let write_end = Arc::new(Pipe::write_end_with_buffer(buffer.clone()));
     buffer.exclusive_access().set_write_end(&write_end);
@@ -123,8 +148,7 @@ impl File for Pipe {
                 if ring_buffer.all_write_ends_closed() {
                     return read_cnt;
                 }
-                drop(ring_buffer);
-                switch_thread();
+                PipeRingBuffer::wait_for_read(ring_buffer);
                 continue;
             }
             if read_cnt == len {
@@ -134,9 +158,13 @@ impl File for Pipe {
                 buf[read_cnt] = ring_buffer.read_byte();
                 read_cnt += 1;
                 if read_cnt == len {
-                    return read_cnt;
+                    break;
                 }
             }
+            wakeup_all(&mut ring_buffer.write_waiters);
+            if read_cnt == len {
+                return read_cnt;
+            }
         }
     }
     fn write(&self, buf: &[u8]) -> usize {
@@ -147,8 +175,7 @@ impl File for Pipe {
             let mut ring_buffer = self.buffer.exclusive_access();
             let cur_write_cnt = ring_buffer.available_write();
             if cur_write_cnt == 0 {
-                drop(ring_buffer);
-                switch_thread();
+                PipeRingBuffer::wait_for_write(ring_buffer);
                 continue;
             }
             if write_cnt == len {
@@ -158,15 +185,30 @@ impl File for Pipe {
                 ring_buffer.write_byte(buf[write_cnt]);
                 write_cnt += 1;
                 if write_cnt == len {
-                    return write_cnt;
+                    break;
                 }
             }
+            wakeup_all(&mut ring_buffer.read_waiters);
+            if write_cnt == len {
+                return write_cnt;
+            }
         }
     }
-    fn seek(&self, _: usize) -> usize {
-        panic!("Pipe does not support seek operation");
+    fn seek(&self, _offset: isize, _whence: usize) -> Result<usize, Errno> {
+        Err(Errno::ESPIPE)
+    }
+    /// The size of a pipe is the number of bytes ready to be read
+    fn stat(&self) -> Stat {
+        let size = self.buffer.exclusive_access().available_read();
+        Stat::new(0, StatMode::FIFO, size as u64, 0)
     }
-    fn stat(&self) -> String {
-        panic!("Pipe does not support stat operation");
+}
+
+impl Drop for Pipe {
+    /// The readers waiting on the write end see it closed
+    fn drop(&mut self) {
+        if self.writable {
+            wakeup_all(&mut self.buffer.exclusive_access().read_waiters);
+        }
     }
-}
\ No newline at end of file
+}
@@ -1,11 +1,11 @@
-use alloc::string::String;
-use super::File;
+use super::{File, Stat, StatMode};
+use crate::errno::Errno;
 
 
 pub struct Stdin;
 pub struct Stdout;
 
-use crate::console::stdin::getchar;
+use crate::console::stdin::{available, getchar};
 use crate::print;
 
 impl File for Stdin {
@@ -15,22 +15,26 @@ impl File for Stdin {
     fn writable(&self) -> bool {
         false
     }
+    /// A signal interrupting the wait ends the read early
     fn read(&self, buf: &mut [u8]) -> usize {
-        for b in buf.iter_mut() {
-            *b = getchar();
+        for (i, b) in buf.iter_mut().enumerate() {
+            match getchar() {
+                Some(c) => *b = c,
+                None => return i,
+            }
         }
-        1
+        buf.len()
     }
     fn write(&self, _buf: &[u8]) -> usize {
         panic!("WTF? Cannot write to stdin!");
     }
-    /// do nothing
-    fn seek(&self, _offset: usize) -> usize {
-        0
+    fn seek(&self, _offset: isize, _whence: usize) -> Result<usize, Errno> {
+        Err(Errno::ESPIPE)
     }
 
-    fn stat(&self) -> String {
-        panic!("WTF? Cannot stat stdin!");
+    /// The size of the console is the number of bytes ready to be read, so it can be polled
+    fn stat(&self) -> Stat {
+        Stat::new(0, StatMode::CHR, available() as u64, 0)
     }
 }
 impl File for Stdout {
@@ -47,12 +51,11 @@ impl File for Stdout {
         print!("{}", core::str::from_utf8(buf).unwrap());
         buf.len()
     }
-    /// do nothing
-    fn seek(&self, _offset: usize) -> usize {
-        0
+    fn seek(&self, _offset: isize, _whence: usize) -> Result<usize, Errno> {
+        Err(Errno::ESPIPE)
     }
-    fn stat(&self) -> String {
-        panic!("WTF? Cannot stat stdout!");
+    fn stat(&self) -> Stat {
+        Stat::new(0, StatMode::CHR, 0, 0)
     }
 }
 
@@ -7,9 +7,12 @@ extern crate bitflags;
 extern crate alloc;
 
 use core::arch::{asm, global_asm};
+use core::hint::spin_loop;
+use core::sync::atomic::{AtomicBool, Ordering};
 use riscv::register::{mstatus, mepc, satp, pmpaddr0, pmpcfg0};
 
 mod config;
+mod errno;
 mod console;
 mod mm;
 mod sync;
@@ -67,6 +70,9 @@ fn from_m_to_s() {
     }
 }
 
+/// Set by the boot hart when the kernel is initialized, then the other harts go on
+static BOOTED: AtomicBool = AtomicBool::new(false);
+
 fn rust_init() {
     clear_bss();
     UART.init();
@@ -78,9 +84,26 @@ fn rust_init() {
     timer::set_next_trigger();
 }
 
+/// Initialize a hart other than the boot one
+fn rust_init_hart() {
+    mm::init_hart();
+    trap::init();
+    timer::set_next_trigger();
+}
+
 #[no_mangle]
 pub fn rust_main() {
-    rust_init();
-    println!("[kernel] Hello from kernel. This is CrazyDave's ACore implementation.");
-    proc::launch(proc::INIT_PCB.clone());
+    if proc::hart_id() == 0 {
+        rust_init();
+        println!("[kernel] Hello from kernel. This is CrazyDave's ACore implementation.");
+        proc::push_thread(proc::INIT_PCB.exclusive_access().get_thread(0));
+        BOOTED.store(true, Ordering::Release);
+    } else {
+        while !BOOTED.load(Ordering::Acquire) {
+            spin_loop();
+        }
+        rust_init_hart();
+        println!("[kernel] Hart {} is up.", proc::hart_id());
+    }
+    proc::run_threads();
 }
@@ -108,7 +108,6 @@ impl VirtAddr {
     pub fn get_page_offset(&self) -> usize {
         self.0 & (PAGE_SIZE - 1)
     }
-    #[allow(unused)]
     ///Check page aligned
     pub fn is_aligned(&self) -> bool {
         self.get_page_offset() == 0
@@ -1,6 +1,7 @@
 use super::addr::{PhysAddr, PhysPageNum};
+use super::swap::swap_out;
 use crate::config::*;
-use crate::sync::UPSafeCell;
+use crate::sync::SpinLock;
 use alloc::vec::Vec;
 use lazy_static::lazy_static;
 
@@ -48,7 +49,6 @@ impl FrameAllocator for StackFrameAllocator {
         if let Some(ppn) = self.recycled.pop() {
             Some(ppn.into())
         } else if self.cap == self.max_cap {
-            // todo: all frames are used, consider evicting some frames to disk
             None
         } else {
             let ppn = self.cap;
@@ -74,7 +74,7 @@ impl StackFrameAllocator {
 }
 
 lazy_static!{
-    pub static ref FRAME_ALLOCATOR: UPSafeCell<StackFrameAllocator> = unsafe {UPSafeCell::new(StackFrameAllocator::new())};
+    pub static ref FRAME_ALLOCATOR: SpinLock<StackFrameAllocator> = SpinLock::new(StackFrameAllocator::new());
 }
 
 pub fn init() {
@@ -89,9 +89,17 @@ pub fn init() {
     );
 }
 
+/// Allocate a frame, swap out user pages to free one if all frames are used
 pub fn frame_alloc() -> Option<FrameGuard> {
-    let ppn = FRAME_ALLOCATOR.exclusive_access().alloc()?;
-    Some(FrameGuard::new(ppn))
+    loop {
+        let ppn = FRAME_ALLOCATOR.exclusive_access().alloc();
+        if let Some(ppn) = ppn {
+            return Some(FrameGuard::new(ppn));
+        }
+        if !swap_out() {
+            return None;
+        }
+    }
 }
 pub fn frame_dealloc(ppn: PhysPageNum) {
     FRAME_ALLOCATOR.exclusive_access().dealloc(ppn);
@@ -13,6 +13,6 @@ static mut KERNEL_HEAP_SPACE: [u8; KERNEL_HEAP_SIZE] = [0; KERNEL_HEAP_SIZE];
 
 pub fn init() {
     unsafe {
-        KERNEL_HEAP.borrow_mut().init(KERNEL_HEAP_SPACE.as_ptr() as usize, KERNEL_HEAP_SIZE);
+        KERNEL_HEAP.lock().init(KERNEL_HEAP_SPACE.as_ptr() as usize, KERNEL_HEAP_SIZE);
     }
 }
\ No newline at end of file
@@ -1,19 +1,26 @@
 use super::page_table::{PTEFlags, PageTable};
 use crate::config::*;
+use crate::errno::Errno;
 use crate::mm::addr::{PhysAddr, VirtAddr, VirtPageNum};
 use crate::mm::frame_allocator::{frame_alloc, FrameGuard};
+use crate::mm::swap::{PageRef, UserPage};
+use crate::mm::tlb::shootdown;
 use crate::mm::PhysPageNum;
-use crate::sync::UPSafeCell;
+use crate::sync::SpinLock;
 use crate::utils::NumRange;
+use acore_fs::Inode;
 use alloc::collections::BTreeMap;
 use alloc::string::String;
 use alloc::sync::Arc;
+use alloc::vec;
 use alloc::vec::Vec;
 use bitflags::bitflags;
 use core::arch::asm;
 use core::cmp::{max, min};
+use core::mem::size_of;
 use lazy_static::lazy_static;
 use riscv::register::satp;
+use xmas_elf::header::{Class, Machine};
 use xmas_elf::program::Type;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -44,6 +51,24 @@ extern "C" {
     fn strampoline();
 }
 
+/// Allocate `count` frames, or none of them if the memory runs out
+fn alloc_frames(count: usize) -> Result<Vec<FrameGuard>, Errno> {
+    (0..count)
+        .map(|_| frame_alloc().ok_or(Errno::ENOMEM))
+        .collect()
+}
+
+/// The pages of the user range [start, start + len), `start` must be page aligned
+fn user_pages(start: usize, len: usize) -> Result<(VirtPageNum, VirtPageNum), Errno> {
+    let start_va = VirtAddr(start);
+    match start.checked_add(len) {
+        Some(end) if start_va.is_aligned() && len > 0 && end <= USER_SPACE_END => {
+            Ok((start_va.floor(), VirtAddr(end).ceil()))
+        }
+        _ => Err(Errno::EINVAL),
+    }
+}
+
 /// The manager of kernel space or user space
 pub struct MemoryManager {
     /// responsible for modifying and reading page table's memory area
@@ -54,34 +79,137 @@ pub struct MemoryManager {
     pub entry_point: usize,
 
     pub user_stack_bottom: usize,
+
+    /// The start of the heap area, 0 if there is no heap
+    pub heap_bottom: usize,
+
+    /// The program break, i.e. the end of the heap
+    pub brk: usize,
+
+    /// How far each user stack can grow down
+    pub stack_limit: RLimit,
+}
+
+/// A resource limit, the soft one is enforced and can be raised up to the hard one
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct RLimit {
+    pub cur: usize,
+    pub max: usize,
 }
 
 /// A continuous memory region, with same flags
 pub struct Area {
     start_vpn: VirtPageNum,
     end_vpn: VirtPageNum,
-    #[allow(unused)]
-    frame_guards: Vec<FrameGuard>,
+    /// The pages in use, a lazy area has none of them at first.
+    /// Shared by the processes forked from each other until one of them writes the page.
+    pages: BTreeMap<VirtPageNum, PageRef>,
     map_type: MapType,
     map_perm: MapPerm,
+    /// Created by mmap, so the user can unmap or protect it
+    mmap: bool,
+    /// A user stack, whose pages are zero-filled on their first access within the stack limit
+    stack: bool,
+    /// The file the pages are loaded from on their first access, if the area is lazy
+    backing: Option<Backing>,
+}
+
+/// The part of a file backing a lazy area, like an ELF segment.
+/// The file is held open as a running program, so it is neither reclaimed nor written.
+struct Backing {
+    inode: Arc<Inode>,
+    /// Where the byte at `offset` of the file is mapped
+    start_va: VirtAddr,
+    offset: usize,
+    /// The bytes after `start_va + file_size` are zeros, i.e. the BSS
+    file_size: usize,
+}
+
+impl Backing {
+    /// None if the file is opened for writing
+    fn new(inode: Arc<Inode>, start_va: VirtAddr, offset: usize, file_size: usize) -> Option<Self> {
+        if !inode.open_exec() {
+            return None;
+        }
+        Some(Self {
+            inode,
+            start_va,
+            offset,
+            file_size,
+        })
+    }
+
+    /// Fill the page at `vpn` with its contents in the file, and zeros outside of them
+    fn load(&self, vpn: VirtPageNum, page: &mut [u8]) {
+        let page_va = VirtAddr::from(vpn).0;
+        let start = max(page_va, self.start_va.0);
+        let end = min(page_va + PAGE_SIZE, self.start_va.0 + self.file_size);
+        if start < end {
+            self.inode.read_at(
+                self.offset + start - self.start_va.0,
+                &mut page[start - page_va..end - page_va],
+            );
+        }
+    }
+}
+
+impl Clone for Backing {
+    fn clone(&self) -> Self {
+        // the file is already held as a running program, which can't be opened for writing
+        let pinned = self.inode.open_exec();
+        debug_assert!(pinned);
+        Self {
+            inode: self.inode.clone(),
+            start_va: self.start_va,
+            offset: self.offset,
+            file_size: self.file_size,
+        }
+    }
+}
+
+impl Drop for Backing {
+    fn drop(&mut self) {
+        self.inode.close_exec();
+    }
 }
 
 impl Area {
-    pub fn new(
-        start_va: VirtAddr,
-        end_va: VirtAddr,
-        map_type: MapType,
-        map_perm: MapPerm,
-        frame_guards: Vec<FrameGuard>,
-    ) -> Self {
+    pub fn new(start_va: VirtAddr, end_va: VirtAddr, map_type: MapType, map_perm: MapPerm) -> Self {
         Self {
             start_vpn: start_va.floor(),
             end_vpn: end_va.ceil(),
             map_type,
             map_perm,
-            frame_guards,
+            pages: BTreeMap::new(),
+            mmap: false,
+            stack: false,
+            backing: None,
         }
     }
+
+    /// Put `frame` at `vpn`, which is mapped by the page table of `token`.
+    /// The pages of the user can be swapped out.
+    fn add_page(&mut self, vpn: VirtPageNum, frame: FrameGuard, token: usize) {
+        let page = UserPage::new(vpn, frame, self.map_perm.contains(MapPerm::U));
+        self.pages.insert(vpn, PageRef::new(page, token));
+    }
+
+    /// Split the area at `vpn`, it keeps [start_vpn, vpn) and the rest is returned
+    fn split_off(&mut self, vpn: VirtPageNum) -> Self {
+        let rest = Self {
+            start_vpn: vpn,
+            end_vpn: self.end_vpn,
+            pages: self.pages.split_off(&vpn),
+            map_type: self.map_type,
+            map_perm: self.map_perm,
+            mmap: self.mmap,
+            stack: self.stack,
+            backing: self.backing.clone(),
+        };
+        self.end_vpn = vpn;
+        rest
+    }
 }
 
 impl MemoryManager {
@@ -91,6 +219,12 @@ impl MemoryManager {
             areas: BTreeMap::new(),
             entry_point: 0,
             user_stack_bottom: 0,
+            heap_bottom: 0,
+            brk: 0,
+            stack_limit: RLimit {
+                cur: USER_STACK_MAX,
+                max: USER_STACK_MAX,
+            },
         }
     }
 
@@ -179,31 +313,35 @@ impl MemoryManager {
 
         mm
     }
-    pub fn from_elf(data: &[u8]) -> Self {
+    /// Create the user space of an ELF file, ENOEXEC if it's not a RISC-V 64 executable.
+    /// Only the headers are read here, the segments are loaded page by page on their first access.
+    pub fn from_elf(inode: Arc<Inode>) -> Result<Self, Errno> {
+        let data = read_elf_headers(&inode)?;
+        let elf = xmas_elf::ElfFile::new(&data).map_err(|_| Errno::ENOEXEC)?;
+        let elf_header = elf.header;
+        if elf_header.pt1.class() != Class::SixtyFour
+            || elf_header.pt2.machine().as_machine() != Machine::RISC_V
+        {
+            return Err(Errno::ENOEXEC);
+        }
         let mut mm = MemoryManager::empty();
         mm.map_trampoline();
-        let elf = xmas_elf::ElfFile::new(data).unwrap();
-        let elf_header = elf.header;
-        let magic = elf_header.pt1.magic;
-        assert_eq!(magic, [0x7f, 0x45, 0x4c, 0x46], "Invalid ELF magic number");
         let ph_cnt = elf_header.pt2.ph_count();
         let mut max_end_va = VirtAddr(0);
         for i in 0..ph_cnt {
-            let ph = elf.program_header(i).unwrap();
+            let ph = elf.program_header(i).map_err(|_| Errno::ENOEXEC)?;
             match ph.get_type() {
                 Ok(ty) => {
                     if ty == Type::Load {
+                        // the segments must fit below the heap
+                        let end = ph.virtual_addr().checked_add(ph.mem_size());
+                        if ph.file_size() > ph.mem_size()
+                            || end.map_or(true, |end| end > USER_HEAP_BASE as u64)
+                        {
+                            return Err(Errno::ENOEXEC);
+                        }
                         let start_va = VirtAddr(ph.virtual_addr() as usize);
                         let end_va = VirtAddr((ph.virtual_addr() + ph.mem_size()) as usize);
-                        // println!(
-                        //     "i = {}, start_vpn = {:?}, end_vpn = {:?}, start_va = {:?}, \
-                        // end_va = {:?}",
-                        //     i,
-                        //     start_va.floor(),
-                        //     end_va.ceil(),
-                        //     start_va,
-                        //     end_va
-                        // );
                         max_end_va = max(max_end_va, end_va);
                         let mut map_perm = MapPerm::U;
                         let ph_flags = ph.flags();
@@ -216,38 +354,82 @@ impl MemoryManager {
                         if ph_flags.is_execute() {
                             map_perm |= MapPerm::X;
                         }
-                        mm.insert_area(
-                            start_va,
-                            end_va,
-                            MapType::Framed,
-                            map_perm,
-                            Some(
-                                &elf.input
-                                    [ph.offset() as usize..(ph.offset() + ph.file_size()) as usize],
-                            ),
+                        let mut area = Area::new(start_va, end_va, MapType::Framed, map_perm);
+                        let offset = ph.offset() as usize;
+                        let file_size = ph.file_size() as usize;
+                        area.backing = Some(
+                            Backing::new(inode.clone(), start_va, offset, file_size)
+                                .ok_or(Errno::ETXTBSY)?,
                         );
+                        mm.areas.insert(start_va.0, area);
                     }
                 }
-                Err(_) => {
-                    panic!("Invalid ELF program header type")
-                }
+                Err(_) => return Err(Errno::ENOEXEC),
             }
         }
-        // align end_of_elf_data
-        let user_stack_bottom: usize = VirtAddr::from(max_end_va.ceil()).0 + PAGE_SIZE;
+        // align end_of_elf_data, there is a guard page below each user stack
+        let user_stack_bottom: usize = VirtAddr::from(max_end_va.ceil()).0 + USER_STACK_GUARD;
 
         mm.user_stack_bottom = user_stack_bottom;
         mm.entry_point = elf.header.pt2.entry_point() as usize;
-        mm
+
+        // the heap is empty at first
+        mm.insert_area(
+            USER_HEAP_BASE.into(),
+            USER_HEAP_BASE.into(),
+            MapType::Framed,
+            MapPerm::R | MapPerm::W | MapPerm::U,
+            None,
+        );
+        mm.heap_bottom = USER_HEAP_BASE;
+        mm.brk = USER_HEAP_BASE;
+        Ok(mm)
     }
 
     /// Clone a memory manager. For fork.
-    pub fn from_existed(another_mm: &Self) -> Self {
+    /// The user pages are shared read-only by both, and copied on the first write to them.
+    pub fn from_existed(another_mm: &mut Self) -> Self {
         let mut mm = MemoryManager::empty();
         mm.entry_point = another_mm.entry_point;
         mm.user_stack_bottom = another_mm.user_stack_bottom;
+        mm.heap_bottom = another_mm.heap_bottom;
+        mm.brk = another_mm.brk;
+        mm.stack_limit = another_mm.stack_limit;
         mm.map_trampoline();
-        for (_, area) in another_mm.areas.iter() {
+        for (key, area) in another_mm.areas.iter() {
+            if area.map_type == MapType::Framed && area.map_perm.contains(MapPerm::U) {
+                let flags = PTEFlags::from_bits((area.map_perm - MapPerm::W).bits).unwrap();
+                let token = mm.page_table.token();
+                let mut pages = BTreeMap::new();
+                for (vpn, page) in area.pages.iter() {
+                    // shared before mapping, so it's not swapped out by the allocation in it
+                    let page = PageRef::new(page.page().clone(), token);
+                    // the pages not loaded yet or swapped out are mapped on their next access
+                    if let Some(pte) = another_mm
+                        .page_table
+                        .find_pte(*vpn)
+                        .filter(|pte| pte.is_valid())
+                    {
+                        another_mm.page_table.set_flags(*vpn, flags);
+                        let dirty = pte.flags() & PTEFlags::D;
+                        mm.page_table.map(*vpn, pte.ppn(), flags | dirty);
+                    }
+                    pages.insert(*vpn, page);
+                }
+                let shared = Area {
+                    start_vpn: area.start_vpn,
+                    end_vpn: area.end_vpn,
+                    pages,
+                    map_type: area.map_type,
+                    map_perm: area.map_perm,
+                    mmap: area.mmap,
+                    stack: area.stack,
+                    backing: area.backing.clone(),
+                };
+                mm.areas.insert(*key, shared);
+                continue;
+            }
+            // the kernel writes the trap contexts through their frames, they can't be shared
             mm.insert_area(
                 area.start_vpn.into(),
                 area.end_vpn.into(),
@@ -255,6 +437,10 @@ impl MemoryManager {
                 area.map_perm,
                 None,
             );
+            if area.mmap {
+                let start_va: VirtAddr = area.start_vpn.into();
+                mm.areas.get_mut(&start_va.0).unwrap().mmap = true;
+            }
             for vpn in NumRange::new(area.start_vpn, area.end_vpn) {
                 let src_data = another_mm
                     .page_table
@@ -265,32 +451,25 @@ impl MemoryManager {
                 dst_data.copy_from_slice(src_data);
             }
         }
+        // the other threads of the parent may still write the pages made read-only
+        shootdown(another_mm.page_table.token());
         mm
     }
-    fn get_area_frame_guards(
-        &mut self,
-        start_va: VirtAddr,
-        end_va: VirtAddr,
-        map_type: MapType,
-        map_perm: MapPerm,
-    ) -> Vec<FrameGuard> {
-        let mut frame_guards = Vec::new();
-        for vpn in NumRange::<VirtPageNum>::new(start_va.floor(), end_va.ceil()) {
-            let ppn: PhysPageNum;
-            match map_type {
+    fn map_area(&mut self, area: &mut Area) {
+        let token = self.page_table.token();
+        let flags = PTEFlags::from_bits(area.map_perm.bits).unwrap();
+        for vpn in NumRange::new(area.start_vpn, area.end_vpn) {
+            match area.map_type {
                 MapType::Identical => {
-                    ppn = PhysPageNum(vpn.0);
+                    self.page_table.map(vpn, PhysPageNum(vpn.0), flags);
                 }
                 MapType::Framed => {
                     let frame = frame_alloc().unwrap();
-                    ppn = frame.ppn;
-                    frame_guards.push(frame);
+                    self.page_table.map(vpn, frame.ppn, flags);
+                    area.add_page(vpn, frame, token);
                 }
             }
-            self.page_table
-                .map(vpn, ppn, PTEFlags::from_bits(map_perm.bits).unwrap());
         }
-        frame_guards
     }
     fn map_trampoline(&mut self) {
         self.page_table.map(
@@ -313,13 +492,24 @@ impl MemoryManager {
         //     start_va.0,
         //     end_va.0
         // );
-        let frame_guards = self.get_area_frame_guards(start_va, end_va, map_type, map_perm);
-        let area = Area::new(start_va, end_va, map_type, map_perm, frame_guards);
+        let mut area = Area::new(start_va, end_va, map_type, map_perm);
+        self.map_area(&mut area);
         self.areas.insert(start_va.0, area);
         if let Some(data) = data {
             self.write(start_va, data);
         }
     }
+    /// Reserve [bottom, top) for a user stack. No page is mapped until the stack grows into it.
+    pub fn insert_stack(&mut self, bottom: VirtAddr, top: VirtAddr) {
+        let mut area = Area::new(
+            bottom,
+            top,
+            MapType::Framed,
+            MapPerm::R | MapPerm::W | MapPerm::U,
+        );
+        area.stack = true;
+        self.areas.insert(bottom.0, area);
+    }
     /// release virtual memory area
     pub fn remove_area(&mut self, start_va: VirtAddr) {
         // println!(
@@ -328,9 +518,22 @@ impl MemoryManager {
         //     start_va.0
         // );
         let area = self.areas.remove(&start_va.0).unwrap();
-        for vpn in NumRange::new(area.start_vpn, area.end_vpn) {
-            self.page_table.unmap(vpn);
+        match area.map_type {
+            MapType::Identical => {
+                for vpn in NumRange::new(area.start_vpn, area.end_vpn) {
+                    self.page_table.unmap(vpn);
+                }
+            }
+            // the pages not loaded yet or swapped out are not mapped
+            MapType::Framed => {
+                for vpn in area.pages.keys() {
+                    if self.page_table.is_mapped(*vpn) {
+                        self.page_table.unmap(*vpn);
+                    }
+                }
+            }
         }
+        shootdown(self.page_table.token());
     }
 
     #[no_mangle]
@@ -342,50 +545,396 @@ impl MemoryManager {
             asm!("sfence.vma");
         }
     }
-    /// get data from the specified virtual address and length
-    pub fn read(&self, start_va: VirtAddr, len: usize) -> Vec<u8> {
-        let mut cur_vpn = start_va.floor();
-        let mut cur_start = start_va.get_page_offset();
-        let mut data = Vec::new();
-        loop {
-            let cur_end = min(cur_start + len - data.len(), PAGE_SIZE);
-            let src = &self
-                .page_table
-                .find_pte(cur_vpn)
-                .unwrap()
-                .ppn()
-                .get_bytes_array()[cur_start..cur_end];
-            data.extend_from_slice(src);
-            if data.len() >= len {
+    /// Move the program break to `new_brk`, mapping or unmapping the heap pages in between
+    pub fn set_brk(&mut self, new_brk: usize) -> Result<(), Errno> {
+        if new_brk < self.heap_bottom || new_brk > self.heap_bottom + USER_HEAP_LIMIT {
+            return Err(Errno::ENOMEM);
+        }
+        let token = self.page_table.token();
+        let heap = self.areas.get_mut(&self.heap_bottom).ok_or(Errno::ENOMEM)?;
+        let new_end_vpn = VirtAddr(new_brk).ceil();
+        if new_end_vpn > heap.end_vpn {
+            let frame_guards = alloc_frames(new_end_vpn.0 - heap.end_vpn.0)?;
+            let flags = PTEFlags::from_bits(heap.map_perm.bits).unwrap();
+            for frame in frame_guards {
+                self.page_table.map(heap.end_vpn, frame.ppn, flags);
+                heap.add_page(heap.end_vpn, frame, token);
+                heap.end_vpn.0 += 1;
+            }
+        } else {
+            while heap.end_vpn > new_end_vpn {
+                heap.end_vpn.0 -= 1;
+                if self.page_table.is_mapped(heap.end_vpn) {
+                    self.page_table.unmap(heap.end_vpn);
+                }
+                heap.pages.remove(&heap.end_vpn);
+            }
+            shootdown(token);
+        }
+        self.brk = new_brk;
+        Ok(())
+    }
+
+    /// Map `len` bytes of memory filled with `data` and zeros, return the start address.
+    /// The mapping is at `hint` if that range is free, otherwise anywhere in the mmap region.
+    pub fn mmap(
+        &mut self,
+        hint: usize,
+        len: usize,
+        map_perm: MapPerm,
+        data: Option<&[u8]>,
+    ) -> Result<usize, Errno> {
+        if len == 0 || len > USER_SPACE_END - USER_MMAP_BASE {
+            return Err(Errno::EINVAL);
+        }
+        let pages = len.div_ceil(PAGE_SIZE);
+        let start_vpn = self.find_free_pages(hint, pages).ok_or(Errno::ENOMEM)?;
+        let frame_guards = alloc_frames(pages)?;
+        let start_va: VirtAddr = start_vpn.into();
+        let mut area = Area::new(
+            start_va,
+            VirtAddr(start_va.0 + pages * PAGE_SIZE),
+            MapType::Framed,
+            map_perm,
+        );
+        area.mmap = true;
+        let token = self.page_table.token();
+        let flags = PTEFlags::from_bits(map_perm.bits).unwrap();
+        for (i, frame) in frame_guards.into_iter().enumerate() {
+            let vpn = VirtPageNum(start_vpn.0 + i);
+            self.page_table.map(vpn, frame.ppn, flags);
+            area.add_page(vpn, frame, token);
+        }
+        self.areas.insert(start_va.0, area);
+        if let Some(data) = data {
+            self.write(start_va, data);
+        }
+        Ok(start_va.0)
+    }
+
+    /// Unmap the pages in [start, start + len). They must be mapped by mmap, or not mapped at all.
+    pub fn munmap(&mut self, start: usize, len: usize) -> Result<(), Errno> {
+        let (start_vpn, end_vpn) = user_pages(start, len)?;
+        for key in self.split_mmap_areas(start_vpn, end_vpn)? {
+            self.remove_area(VirtAddr(key));
+        }
+        Ok(())
+    }
+
+    /// Change the permission of the pages in [start, start + len), which must be mapped by mmap
+    pub fn mprotect(&mut self, start: usize, len: usize, map_perm: MapPerm) -> Result<(), Errno> {
+        let (start_vpn, end_vpn) = user_pages(start, len)?;
+        let mapped: usize = self
+            .areas
+            .values()
+            .map(|area| {
+                min(area.end_vpn.0, end_vpn.0).saturating_sub(max(area.start_vpn.0, start_vpn.0))
+            })
+            .sum();
+        if mapped != end_vpn.0 - start_vpn.0 {
+            return Err(Errno::ENOMEM);
+        }
+        let flags = PTEFlags::from_bits(map_perm.bits).unwrap();
+        for key in self.split_mmap_areas(start_vpn, end_vpn)? {
+            let area = self.areas.get_mut(&key).unwrap();
+            area.map_perm = map_perm;
+            for (vpn, page) in area.pages.iter() {
+                if !self.page_table.is_mapped(*vpn) {
+                    continue;
+                }
+                // a page shared after fork stays read-only until it's copied
+                let flags = if Arc::strong_count(page.page()) > 1 {
+                    flags - PTEFlags::W
+                } else {
+                    flags
+                };
+                self.page_table.set_flags(*vpn, flags);
+            }
+        }
+        shootdown(self.page_table.token());
+        Ok(())
+    }
+
+    /// Find `pages` free pages in the mmap region, at `hint` if possible
+    fn find_free_pages(&self, hint: usize, pages: usize) -> Option<VirtPageNum> {
+        let base = VirtAddr(USER_MMAP_BASE).floor();
+        let limit = VirtAddr(USER_SPACE_END).floor();
+        let is_free = |start: VirtPageNum| {
+            start >= base
+                && start.0 + pages <= limit.0
+                && self
+                    .areas
+                    .values()
+                    .all(|area| area.end_vpn <= start || area.start_vpn.0 >= start.0 + pages)
+        };
+        let hint = VirtAddr(hint).floor();
+        if hint.0 < limit.0 && is_free(hint) {
+            return Some(hint);
+        }
+        // the first fit, the areas are sorted by their start addresses
+        let mut start = base;
+        for area in self.areas.values() {
+            if area.end_vpn <= start {
+                continue;
+            }
+            if area.start_vpn.0 >= start.0 + pages {
                 break;
             }
-            cur_vpn.0 += 1;
-            cur_start = 0;
+            start = area.end_vpn;
+        }
+        is_free(start).then_some(start)
+    }
+
+    /// Split the areas overlapping [start_vpn, end_vpn) at its bounds, return the keys of the
+    /// areas inside. Only areas created by mmap can be split.
+    fn split_mmap_areas(
+        &mut self,
+        start_vpn: VirtPageNum,
+        end_vpn: VirtPageNum,
+    ) -> Result<Vec<usize>, Errno> {
+        let keys: Vec<usize> = self
+            .areas
+            .iter()
+            .filter(|(_, area)| area.start_vpn < end_vpn && start_vpn < area.end_vpn)
+            .map(|(key, _)| *key)
+            .collect();
+        if keys.iter().any(|key| !self.areas[key].mmap) {
+            return Err(Errno::EINVAL);
+        }
+        let mut inside = Vec::new();
+        for key in keys {
+            let mut area = self.areas.remove(&key).unwrap();
+            if area.start_vpn < start_vpn {
+                let rest = area.split_off(start_vpn);
+                self.areas.insert(key, area);
+                area = rest;
+            }
+            if area.end_vpn > end_vpn {
+                let rest = area.split_off(end_vpn);
+                self.areas.insert(VirtAddr::from(end_vpn).0, rest);
+            }
+            let start_va: VirtAddr = area.start_vpn.into();
+            self.areas.insert(start_va.0, area);
+            inside.push(start_va.0);
+        }
+        Ok(inside)
+    }
+
+    /// Find the area containing `vpn`
+    fn area_of(&self, vpn: VirtPageNum) -> Option<&Area> {
+        self.areas
+            .values()
+            .find(|area| area.start_vpn <= vpn && vpn < area.end_vpn)
+    }
+
+    /// Whether `vpn` is writable, but still shared read-only after fork
+    fn is_copy_on_write(&self, vpn: VirtPageNum) -> bool {
+        let Some(area) = self.area_of(vpn) else {
+            return false;
+        };
+        let Some(pte) = self.page_table.find_pte(vpn) else {
+            return false;
+        };
+        area.map_type == MapType::Framed
+            && area.map_perm.contains(MapPerm::U | MapPerm::W)
+            && pte.is_valid()
+            && !pte.is_writable()
+    }
+
+    /// Give `vpn` its own frame and make it writable again, on the first write after fork.
+    /// Return false if it's not a copy-on-write page, or the memory runs out.
+    pub fn copy_on_write(&mut self, vpn: VirtPageNum) -> bool {
+        if !self.is_copy_on_write(vpn) {
+            return false;
+        }
+        let token = self.page_table.token();
+        let area = self
+            .areas
+            .values_mut()
+            .find(|area| area.start_vpn <= vpn && vpn < area.end_vpn)
+            .unwrap();
+        let flags = PTEFlags::from_bits(area.map_perm.bits).unwrap();
+        if Arc::strong_count(area.pages[&vpn].page()) == 1 {
+            self.page_table.set_flags(vpn, flags);
+            return true;
         }
-        data
+        // a shared page is never swapped out, so it stays in the frame mapped here
+        let Some(new_frame) = frame_alloc() else {
+            return false;
+        };
+        let ppn = self.page_table.find_ppn(vpn).unwrap();
+        new_frame
+            .ppn
+            .get_bytes_array()
+            .copy_from_slice(ppn.get_bytes_array());
+        self.page_table.remap(vpn, new_frame.ppn, flags);
+        // the other threads may still read the shared frame
+        shootdown(token);
+        area.add_page(vpn, new_frame, token);
+        true
     }
 
-    pub fn read_str(&self, start_va: VirtAddr) -> String {
-        let mut data = String::new();
-        let mut va = start_va;
+    /// Map the page at `vpn` on its first access, if it's in a lazy area and not loaded yet,
+    /// or swapped out, or loaded by another process sharing it.
+    /// A user stack grows down to `vpn` if it stays within the stack limit.
+    /// Return false if it's not such a page, or the memory runs out.
+    pub fn populate(&mut self, vpn: VirtPageNum) -> bool {
+        if self.page_table.is_mapped(vpn) {
+            return false;
+        }
+        let token = self.page_table.token();
+        let stack_limit = self.stack_limit.cur;
+        let Some(area) = self
+            .areas
+            .values_mut()
+            .find(|area| area.start_vpn <= vpn && vpn < area.end_vpn)
+        else {
+            return false;
+        };
+        let grows = area.stack && (area.end_vpn.0 - vpn.0) * PAGE_SIZE <= stack_limit;
+        if area.map_type == MapType::Identical
+            || (area.backing.is_none() && !grows && !area.pages.contains_key(&vpn))
+        {
+            return false;
+        }
+        // allocate the page table entry first, the page may be swapped out by the allocation
+        self.page_table.find_mut_pte_create(vpn);
+        let mut flags = PTEFlags::from_bits(area.map_perm.bits).unwrap();
+        let ppn = match (area.pages.get(&vpn), &area.backing) {
+            (Some(page), _) => {
+                let Some(ppn) = page.page().swap_in() else {
+                    return false;
+                };
+                if Arc::strong_count(page.page()) > 1 {
+                    flags -= PTEFlags::W;
+                }
+                ppn
+            }
+            (None, backing) => {
+                let Some(frame) = frame_alloc() else {
+                    return false;
+                };
+                // the frame of a stack page is zero-filled already
+                if let Some(backing) = backing {
+                    backing.load(vpn, frame.ppn.get_bytes_array());
+                }
+                let ppn = frame.ppn;
+                area.add_page(vpn, frame, token);
+                ppn
+            }
+        };
+        self.page_table.map(vpn, ppn, flags);
+        true
+    }
+
+    /// Check that the user range `[ptr, ptr + len)` is mapped with U and R, or W if `writable`.
+    /// The pages of lazy areas in it are loaded.
+    pub fn check_user(&mut self, ptr: usize, len: usize, writable: bool) -> Result<(), Errno> {
+        if len == 0 {
+            return Ok(());
+        }
+        let end = match ptr.checked_add(len) {
+            Some(end) if end <= USER_SPACE_END => end,
+            _ => return Err(Errno::EFAULT),
+        };
+        for vpn in NumRange::new(VirtAddr(ptr).floor(), VirtAddr(end).ceil()) {
+            self.populate(vpn);
+            self.user_ppn(vpn, writable)?;
+        }
+        Ok(())
+    }
+
+    fn user_ppn(&self, vpn: VirtPageNum, writable: bool) -> Result<PhysPageNum, Errno> {
+        let pte = self.page_table.find_pte(vpn).ok_or(Errno::EFAULT)?;
+        let permitted = if writable {
+            pte.is_writable() || self.is_copy_on_write(vpn)
+        } else {
+            pte.is_readable()
+        };
+        if pte.is_valid() && pte.is_user() && permitted {
+            Ok(pte.ppn())
+        } else {
+            Err(Errno::EFAULT)
+        }
+    }
+
+    /// Copy `len` bytes in from the user space
+    pub fn copy_in(&mut self, ptr: usize, len: usize) -> Result<Vec<u8>, Errno> {
+        self.check_user(ptr, len, false)?;
+        let mut data = Vec::with_capacity(len);
+        let mut va = VirtAddr(ptr);
+        while data.len() < len {
+            let start = va.get_page_offset();
+            let end = min(start + len - data.len(), PAGE_SIZE);
+            self.populate(va.floor());
+            let page = self.user_ppn(va.floor(), false)?.get_bytes_array();
+            data.extend_from_slice(&page[start..end]);
+            va.0 += end - start;
+        }
+        Ok(data)
+    }
+
+    /// Copy a value of `T` in from the user space
+    pub fn copy_in_val<T: Copy>(&mut self, ptr: usize) -> Result<T, Errno> {
+        let data = self.copy_in(ptr, size_of::<T>())?;
+        Ok(unsafe { (data.as_ptr() as *const T).read_unaligned() })
+    }
+
+    /// Copy a '\0' terminated string in from the user space, which is at most `max_len` bytes
+    pub fn copy_in_str(&mut self, ptr: usize, max_len: usize) -> Result<String, Errno> {
+        let mut data = Vec::new();
+        let mut va = VirtAddr(ptr);
         loop {
-            let c: u8 = self
-                .page_table
-                .find_pte(va.floor())
-                .unwrap()
-                .ppn()
-                .get_bytes_array()[va.get_page_offset()];
-            if c == 0 {
-                break;
+            if va.0 >= USER_SPACE_END {
+                return Err(Errno::EFAULT);
+            }
+            self.populate(va.floor());
+            let page = &self.user_ppn(va.floor(), false)?.get_bytes_array()[va.get_page_offset()..];
+            match page.iter().position(|c| *c == 0) {
+                Some(len) if data.len() + len <= max_len => {
+                    data.extend_from_slice(&page[..len]);
+                    break;
+                }
+                _ if data.len() + page.len() > max_len => return Err(Errno::ENAMETOOLONG),
+                _ => data.extend_from_slice(page),
             }
-            data.push(c as char);
-            va.0 += 1;
+            va.0 += page.len();
         }
-        data
+        String::from_utf8(data).map_err(|_| Errno::EINVAL)
     }
 
-    /// copy data to the specified virtual address
-    pub fn write(&self, start_va: VirtAddr, data: &[u8]) {
+    /// Copy data out to the user space
+    pub fn copy_out(&mut self, ptr: usize, data: &[u8]) -> Result<(), Errno> {
+        self.check_user(ptr, data.len(), true)?;
+        let mut va = VirtAddr(ptr);
+        let mut copied = 0;
+        while copied < data.len() {
+            // it may be swapped out since checked
+            self.populate(va.floor());
+            if self.is_copy_on_write(va.floor()) && !self.copy_on_write(va.floor()) {
+                return Err(Errno::ENOMEM);
+            }
+            let start = va.get_page_offset();
+            let end = min(start + data.len() - copied, PAGE_SIZE);
+            self.user_ppn(va.floor(), true)?.get_bytes_array()[start..end]
+                .copy_from_slice(&data[copied..copied + end - start]);
+            self.page_table.set_dirty(va.floor());
+            copied += end - start;
+            va.0 += end - start;
+        }
+        Ok(())
+    }
+
+    /// Copy a value of `T` out to the user space
+    pub fn copy_out_val<T: Copy>(&mut self, ptr: usize, val: &T) -> Result<(), Errno> {
+        self.copy_out(ptr, unsafe {
+            core::slice::from_raw_parts(val as *const T as *const u8, size_of::<T>())
+        })
+    }
+
+    /// Copy data to the specified virtual address, without checking the permissions.
+    /// Only for the kernel itself, e.g. loading the ELF and pushing the args of exec.
+    pub fn write(&mut self, start_va: VirtAddr, data: &[u8]) {
         let mut cur_dst_vpn = start_va.floor();
         let mut cur_src_start: usize = 0;
         let mut cur_dst_start = start_va.get_page_offset();
@@ -396,6 +945,7 @@ impl MemoryManager {
                 end
             );
             let src = &data[cur_src_start..cur_src_end];
+            self.populate(cur_dst_vpn);
             let dst = &mut self
                 .page_table
                 .find_pte(cur_dst_vpn)
@@ -403,6 +953,7 @@ impl MemoryManager {
                 .ppn()
                 .get_bytes_array()[cur_dst_start..cur_dst_start + src.len()];
             dst.copy_from_slice(src);
+            self.page_table.set_dirty(cur_dst_vpn);
             cur_src_start += src.len();
             if cur_src_start >= end {
                 break;
@@ -420,9 +971,36 @@ impl MemoryManager {
     }
 }
 
-impl Area {}
+/// More program headers than this aren't read
+const MAX_ELF_HEADERS_SIZE: usize = 16 * PAGE_SIZE;
+
+/// Read the ELF header and the program headers, which are usually in the first page
+fn read_elf_headers(inode: &Inode) -> Result<Vec<u8>, Errno> {
+    let mut data = vec![0u8; PAGE_SIZE];
+    let len = inode.read_at(0, &mut data);
+    data.truncate(len);
+    let ph_end = {
+        let pt2 = xmas_elf::ElfFile::new(&data)
+            .map_err(|_| Errno::ENOEXEC)?
+            .header
+            .pt2;
+        pt2.ph_offset() as usize + pt2.ph_count() as usize * pt2.ph_entry_size() as usize
+    };
+    // don't trust the sizes in a bad header for the allocation
+    if ph_end > MAX_ELF_HEADERS_SIZE {
+        return Err(Errno::ENOEXEC);
+    }
+    if ph_end > len {
+        data.resize(ph_end, 0);
+        // the program headers are cut off
+        if inode.read_at(len, &mut data[len..]) < ph_end - len {
+            return Err(Errno::ENOEXEC);
+        }
+    }
+    Ok(data)
+}
 
 lazy_static! {
-    pub static ref KERNEL_MM: Arc<UPSafeCell<MemoryManager>> =
-        Arc::new(unsafe { UPSafeCell::new(MemoryManager::new_kernel()) });
+    pub static ref KERNEL_MM: Arc<SpinLock<MemoryManager>> =
+        Arc::new(SpinLock::new(MemoryManager::new_kernel()));
 }
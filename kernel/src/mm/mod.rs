@@ -4,10 +4,14 @@ mod page_table;
 mod frame_allocator;
 mod mem_manager;
 mod loader;
+mod swap;
+mod tlb;
 
 pub use addr::{PhysAddr, PhysPageNum, VirtAddr};
 pub use frame_allocator::{frame_alloc, frame_dealloc, FrameGuard};
-pub use mem_manager::{MapPerm, MapType, MemoryManager, KERNEL_MM
+pub use page_table::PageTable;
+pub use tlb::set_running;
+pub use mem_manager::{MapPerm, MapType, MemoryManager, RLimit, KERNEL_MM
 };
 
 pub fn init() {
@@ -15,4 +19,9 @@ pub fn init() {
     frame_allocator::init();
     // KERNEL_MM.exclusive_access().page_table.visualize();
     KERNEL_MM.exclusive_access().activate();
+}
+
+/// Enable paging on a hart other than the boot one, with the kernel space set up by `init`
+pub fn init_hart() {
+    KERNEL_MM.exclusive_access().activate();
 }
\ No newline at end of file
@@ -86,13 +86,13 @@ impl PageTable {
             frame_guards: vec![frame],
         }
     }
-    // /// Create a temporary page table, used for fetching user space data in kernel mode
-    // pub fn from_token(satp: usize) -> Self {
-    //     Self {
-    //         root_ppn: (satp & ((1usize << 44) - 1)).into(),
-    //         frame_guards: vec![],
-    //     }
-    // }
+    /// Create a temporary page table, used for accessing the page table of another space
+    pub fn from_token(satp: usize) -> Self {
+        Self {
+            root_ppn: (satp & ((1usize << 44) - 1)).into(),
+            frame_guards: vec![],
+        }
+    }
     /// Create a virtual-physical mapping
     pub fn map(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
         // println!("token: {:#x}, map: vpn: {:#x}, ppn: {:#x}", self.root_ppn.0, vpn.0, ppn.0);
@@ -113,6 +113,38 @@ impl PageTable {
         *pte = PageTableEntry::empty();
     }
 
+    /// Change the flags of an existing mapping, its accessed and dirty bits are kept
+    pub fn set_flags(&mut self, vpn: VirtPageNum, flags: PTEFlags) {
+        let pte = self.find_mut_pte(vpn).unwrap();
+        assert!(pte.is_valid(), "Trying to change a non-existed mapping! vpn: {:?}", vpn);
+        let kept = pte.flags() & (PTEFlags::A | PTEFlags::D);
+        *pte = PageTableEntry::new(pte.ppn(), flags | kept | PTEFlags::V);
+    }
+
+    /// Clear the accessed bit of an existing mapping
+    pub fn clear_accessed(&mut self, vpn: VirtPageNum) {
+        let pte = self.find_mut_pte(vpn).unwrap();
+        *pte = PageTableEntry::new(pte.ppn(), pte.flags() - PTEFlags::A);
+    }
+
+    /// Set the dirty bit of an existing mapping, for a write to the page by the kernel
+    pub fn set_dirty(&mut self, vpn: VirtPageNum) {
+        let pte = self.find_mut_pte(vpn).unwrap();
+        *pte = PageTableEntry::new(pte.ppn(), pte.flags() | PTEFlags::D);
+    }
+
+    /// Whether `vpn` is mapped
+    pub fn is_mapped(&self, vpn: VirtPageNum) -> bool {
+        self.find_pte(vpn).is_some_and(|pte| pte.is_valid())
+    }
+
+    /// Replace an existing mapping
+    pub fn remap(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
+        let pte = self.find_mut_pte(vpn).unwrap();
+        assert!(pte.is_valid(), "Trying to remap a non-existed mapping! vpn: {:?}", vpn);
+        *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+    }
+
     /// The identifier of the page table
     pub fn token(&self) -> usize {
         // SV39 mode is 0b1000
@@ -0,0 +1,242 @@
+//! Swapping user pages out to the swap region of the block device, when the frames run out
+
+use super::addr::{PhysPageNum, VirtPageNum};
+use super::frame_allocator::{frame_alloc, FrameGuard};
+use super::page_table::{PTEFlags, PageTable};
+use super::tlb::{running_elsewhere, shootdown};
+use crate::config::{PAGE_SIZE, SWAP_PAGES, SWAP_START_BLOCK};
+use crate::drivers::BLOCK_DEVICE;
+use crate::sync::SpinLock;
+use acore_fs::BLOCK_SIZE;
+use alloc::collections::VecDeque;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+
+const BLOCKS_PER_PAGE: usize = PAGE_SIZE / BLOCK_SIZE;
+
+/// A page of memory, in a frame or swapped out to the disk
+pub struct UserPage {
+    vpn: VirtPageNum,
+    inner: SpinLock<UserPageInner>,
+}
+
+struct UserPageInner {
+    /// None if the page is swapped out
+    frame: Option<FrameGuard>,
+    /// The copy in the swap region, it's still the same as the frame unless the page is dirty
+    slot: Option<usize>,
+    /// The tokens of the page tables mapping the page
+    tokens: Vec<usize>,
+}
+
+impl UserPage {
+    /// A page at `vpn` in `frame`, which is swapped out when the frames run out if `swappable`
+    pub fn new(vpn: VirtPageNum, frame: FrameGuard, swappable: bool) -> Arc<Self> {
+        let page = Arc::new(Self {
+            vpn,
+            inner: SpinLock::new(UserPageInner {
+                frame: Some(frame),
+                slot: None,
+                tokens: Vec::new(),
+            }),
+        });
+        if swappable {
+            SWAP_MANAGER
+                .exclusive_access()
+                .clock
+                .push_back(Arc::downgrade(&page));
+        }
+        page
+    }
+
+    /// The frame of the page, None if it's swapped out
+    pub fn ppn(&self) -> Option<PhysPageNum> {
+        self.inner
+            .exclusive_access()
+            .frame
+            .as_ref()
+            .map(|frame| frame.ppn)
+    }
+
+    /// Read the page back from the swap region if it's swapped out, return its frame.
+    /// Return None if the memory runs out.
+    pub fn swap_in(self: &Arc<Self>) -> Option<PhysPageNum> {
+        if let Some(ppn) = self.ppn() {
+            return Some(ppn);
+        }
+        // allocate first, it may swap out other pages
+        let frame = frame_alloc()?;
+        let mut inner = self.inner.exclusive_access();
+        // another process sharing the page may have swapped it in meanwhile
+        if let Some(frame) = inner.frame.as_ref() {
+            return Some(frame.ppn);
+        }
+        read_slot(inner.slot.unwrap(), frame.ppn);
+        let ppn = frame.ppn;
+        inner.frame = Some(frame);
+        drop(inner);
+        SWAP_MANAGER
+            .exclusive_access()
+            .clock
+            .push_back(Arc::downgrade(self));
+        Some(ppn)
+    }
+}
+
+impl Drop for UserPage {
+    fn drop(&mut self) {
+        if let Some(slot) = self.inner.exclusive_access().slot {
+            SWAP_MANAGER.exclusive_access().free_slots.push(slot);
+        }
+    }
+}
+
+/// A user page mapped by the page table of `token`, so the page can be unmapped from it when
+/// it's swapped out
+pub struct PageRef {
+    page: Arc<UserPage>,
+    token: usize,
+}
+
+impl PageRef {
+    pub fn new(page: Arc<UserPage>, token: usize) -> Self {
+        page.inner.exclusive_access().tokens.push(token);
+        Self { page, token }
+    }
+
+    pub fn page(&self) -> &Arc<UserPage> {
+        &self.page
+    }
+}
+
+impl Drop for PageRef {
+    fn drop(&mut self) {
+        let mut inner = self.page.inner.exclusive_access();
+        let i = inner.tokens.iter().position(|t| *t == self.token).unwrap();
+        inner.tokens.swap_remove(i);
+    }
+}
+
+struct SwapManager {
+    /// The pages in frames which can be swapped out, the clock hand is at the front
+    clock: VecDeque<Weak<UserPage>>,
+    /// The slots never used are from `next_slot` on
+    next_slot: usize,
+    free_slots: Vec<usize>,
+}
+
+impl SwapManager {
+    fn alloc_slot(&mut self) -> Option<usize> {
+        if let Some(slot) = self.free_slots.pop() {
+            Some(slot)
+        } else if self.next_slot < SWAP_PAGES {
+            self.next_slot += 1;
+            Some(self.next_slot - 1)
+        } else {
+            None
+        }
+    }
+}
+
+lazy_static! {
+    static ref SWAP_MANAGER: SpinLock<SwapManager> = SpinLock::new(SwapManager {
+        clock: VecDeque::new(),
+        next_slot: 0,
+        free_slots: Vec::new(),
+    });
+}
+
+/// Wait for another hart choosing a page to swap out, if any
+pub fn wait_swap_out() {
+    drop(SWAP_MANAGER.exclusive_access());
+}
+
+fn read_slot(slot: usize, ppn: PhysPageNum) {
+    let page = ppn.get_bytes_array();
+    for (i, block) in page.chunks_mut(BLOCK_SIZE).enumerate() {
+        BLOCK_DEVICE.read_block(SWAP_START_BLOCK + slot * BLOCKS_PER_PAGE + i, block);
+    }
+}
+
+fn write_slot(slot: usize, ppn: PhysPageNum) {
+    let page = ppn.get_bytes_array();
+    for (i, block) in page.chunks(BLOCK_SIZE).enumerate() {
+        BLOCK_DEVICE.write_block(SWAP_START_BLOCK + slot * BLOCKS_PER_PAGE + i, block);
+    }
+}
+
+/// Swap out a page to free its frame, chosen by the clock algorithm with the accessed bits.
+/// A page accessed since the hand passed it gets another chance, a page shared after fork is
+/// skipped, and so is a page of a process running on another hart, which may be changing the
+/// page table meanwhile. The page is written to the disk after the swap lock is released.
+/// Return false if no page can be swapped out.
+pub fn swap_out() -> bool {
+    // the pages looked at are dropped after the lock is released, since the last one of a page
+    // frees its slot with the lock
+    let mut visited = Vec::new();
+    let mut swap = SWAP_MANAGER.exclusive_access();
+    // the second round finds a page whose accessed bit is cleared in the first one
+    for _ in 0..2 * swap.clock.len() {
+        let Some(page) = swap.clock.pop_front() else {
+            return false;
+        };
+        let Some(page) = page.upgrade() else {
+            continue;
+        };
+        visited.push(page);
+        let page = visited.last().unwrap();
+        // the one in the clock is weak, so the page is shared if there are more than 2
+        if Arc::strong_count(page) > 2 {
+            swap.clock.push_back(Arc::downgrade(page));
+            continue;
+        }
+        let mut inner = page.inner.exclusive_access();
+        let Some(ppn) = inner.frame.as_ref().map(|frame| frame.ppn) else {
+            continue;
+        };
+        // the last process mapping it is dropping it
+        let Some(&token) = inner.tokens.first() else {
+            continue;
+        };
+        if running_elsewhere(token) {
+            swap.clock.push_back(Arc::downgrade(page));
+            continue;
+        }
+        let mut page_table = PageTable::from_token(token);
+        let pte = page_table
+            .find_pte(page.vpn)
+            .filter(|pte| pte.is_valid())
+            .map(|pte| pte.flags());
+        if pte.is_some_and(|flags| flags.contains(PTEFlags::A)) {
+            // no other hart runs the page table, so nothing sets the bits meanwhile
+            page_table.clear_accessed(page.vpn);
+            swap.clock.push_back(Arc::downgrade(page));
+            continue;
+        }
+        let dirty = pte.is_some_and(|flags| flags.contains(PTEFlags::D));
+        let write = if inner.slot.is_none() || dirty {
+            let Some(slot) = inner.slot.or_else(|| swap.alloc_slot()) else {
+                swap.clock.push_front(Arc::downgrade(page));
+                return false;
+            };
+            Some(slot)
+        } else {
+            None
+        };
+        // unmapped before the lock is released, the process may run on another hart afterwards
+        if pte.is_some() {
+            page_table.unmap(page.vpn);
+            shootdown(token);
+        }
+        // the page is out of the clock and stays locked, so a fault on it waits for the write
+        drop(swap);
+        if let Some(slot) = write {
+            write_slot(slot, ppn);
+            inner.slot = Some(slot);
+        }
+        inner.frame = None;
+        return true;
+    }
+    false
+}
@@ -0,0 +1,66 @@
+//! The page tables in use by the harts, and flushing their TLBs when a mapping is downgraded
+//! or removed.
+//!
+//! A hart flushes its TLB whenever it switches between the kernel and the user, so only the
+//! harts running the user of the same page table may cache the old mapping. There's no SBI,
+//! so they get a machine software interrupt through the CLINT, and `__mtime_trap` flushes the
+//! TLB before clearing it.
+
+use super::swap::wait_swap_out;
+use crate::config::{MAX_HARTS, MSIP};
+use crate::proc::hart_id;
+use crate::sync::SpinLock;
+use core::arch::asm;
+use core::hint::spin_loop;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// No page table of the user is in use
+const IDLE: usize = 0;
+
+/// The token of the page table each hart runs, including in the kernel on behalf of the user
+#[allow(clippy::declare_interior_mutable_const)]
+const NOT_RUNNING: AtomicUsize = AtomicUsize::new(IDLE);
+static RUNNING: [AtomicUsize; MAX_HARTS] = [NOT_RUNNING; MAX_HARTS];
+
+/// Only one shootdown at a time, so a cleared interrupt is the answer to the current one
+static SHOOTDOWN: SpinLock<()> = SpinLock::new(());
+
+/// Mark the page table of `token` in use by the current hart, or none with None.
+/// The other harts don't swap out its pages meanwhile, since it may be changing them.
+pub fn set_running(token: Option<usize>) {
+    RUNNING[hart_id()].store(token.unwrap_or(IDLE), Ordering::SeqCst);
+    // a swap-out in progress may have missed it
+    wait_swap_out();
+}
+
+/// Whether a hart other than the current one is running the page table of `token`
+pub fn running_elsewhere(token: usize) -> bool {
+    let cur = hart_id();
+    RUNNING
+        .iter()
+        .enumerate()
+        .any(|(hart, running)| hart != cur && running.load(Ordering::SeqCst) == token)
+}
+
+/// Flush the TLBs of the other harts running the page table of `token`, after a mapping in it
+/// is downgraded or removed. The current hart flushes its own when it returns to the user.
+pub fn shootdown(token: usize) {
+    let _guard = SHOOTDOWN.exclusive_access();
+    let cur = hart_id();
+    let running: [bool; MAX_HARTS] =
+        core::array::from_fn(|hart| hart != cur && RUNNING[hart].load(Ordering::SeqCst) == token);
+    let harts = (0..MAX_HARTS).filter(|hart| running[*hart]);
+    let msip = |hart: usize| (MSIP as *mut u32).wrapping_add(hart);
+    unsafe {
+        // the new page table entries are seen before the interrupts
+        asm!("fence rw, o");
+        for hart in harts.clone() {
+            msip(hart).write_volatile(1);
+        }
+        for hart in harts {
+            while msip(hart).read_volatile() != 0 {
+                spin_loop();
+            }
+        }
+    }
+}
@@ -1,50 +1,44 @@
 use super::pcb::{ProcessControlBlock, ProcessState};
 use alloc::collections::BTreeMap;
 
-use crate::proc::scheduler::Scheduler;
+use crate::proc::scheduler::{new_scheduler, Scheduler};
 
 use crate::console::shutdown;
 use crate::println;
 use crate::proc::ctx::ThreadContext;
+use crate::proc::processor::{get_cur_thread, schedule};
 use crate::proc::resource::get_trap_ctx_addr_by_tid;
-use crate::proc::switch::__switch;
 use crate::proc::thread::{ThreadControlBlock, ThreadState};
 use crate::proc::INIT_PCB;
-use crate::sync::UPSafeCell;
+use crate::sync::SpinLock;
 use crate::trap::TrapContext;
-use alloc::sync::Arc;
+use alloc::boxed::Box;
+use alloc::sync::{Arc, Weak};
 use alloc::vec::Vec;
+use core::hint::spin_loop;
 use lazy_static::lazy_static;
 use crate::timer::remove_timer;
 
 pub struct ThreadManager {
-    cur: Option<Arc<ThreadControlBlock>>,
-    scheduler: Scheduler,
+    scheduler: Box<dyn Scheduler>,
     pid2pcb: BTreeMap<usize, Arc<ProcessControlBlock>>,
 }
 
 impl ThreadManager {
     pub fn new() -> Self {
         ThreadManager {
-            cur: None,
             // procs: Vec::new(),
-            scheduler: Scheduler::new(),
+            scheduler: new_scheduler(),
             pid2pcb: BTreeMap::new(),
         }
     }
-    pub fn current(&self) -> Option<Arc<ThreadControlBlock>> {
-        self.cur.as_ref().map(Arc::clone)
-    }
 }
 
 lazy_static! {
-    pub static ref THREAD_MANAGER: UPSafeCell<ThreadManager> =
-        unsafe { UPSafeCell::new(ThreadManager::new()) };
+    pub static ref THREAD_MANAGER: SpinLock<ThreadManager> =
+        SpinLock::new(ThreadManager::new());
 }
 
-pub fn get_cur_thread() -> Option<Arc<ThreadControlBlock>> {
-    THREAD_MANAGER.exclusive_access().current()
-}
 pub fn get_cur_proc() -> Arc<ProcessControlBlock> {
     // println!("[kernel] get_cur_proc");
     get_cur_thread().unwrap().pcb.upgrade().unwrap()
@@ -72,62 +66,73 @@ pub fn get_cur_trap_ctx_user_va() -> usize {
     )
 }
 
-/// Suspend current process and switch to a ready one
-pub fn switch_thread() {
-    let mut inner = THREAD_MANAGER.exclusive_access();
-    if let Some(next_thread) = inner.scheduler.pop() {
-        if let Some(cur_thread) = inner.cur.clone() {
-            let mut next_inner = next_thread.exclusive_access();
-            next_inner.state = ThreadState::Running;
-            let next_thr_ctx: *mut ThreadContext = &mut next_inner.thread_ctx as *mut _;
-
-            let mut cur_inner = cur_thread.exclusive_access();
-            if cur_inner.state!= ThreadState::Blocked {
-                cur_inner.state = ThreadState::Ready;
-                inner.scheduler.push(cur_thread.clone());
-            }
-            let cur_thr_ctx: *mut ThreadContext = &mut cur_inner.thread_ctx as *mut _;
-
-            drop(next_inner);
-            drop(cur_inner);
-            inner.cur = Some(next_thread);
-
-            drop(inner);
+/// Switch the current thread out to the idle loop of the hart, see `run_threads`
+fn switch_out(thread: Arc<ThreadControlBlock>) {
+    let mut inner = thread.exclusive_access();
+    inner.clock.charge_system();
+    match inner.state {
+        ThreadState::Blocked => inner.clock.usage.nvcsw += 1,
+        ThreadState::Exited => {}
+        _ => inner.clock.usage.nivcsw += 1,
+    }
+    let thr_ctx = &mut inner.thread_ctx as *mut ThreadContext;
+    drop(inner);
+    // the processor keeps it alive until the switch is done
+    drop(thread);
+    schedule(thr_ctx);
+}
 
-            unsafe {
-                __switch(cur_thr_ctx, next_thr_ctx);
-            }
-            return;
-        } else {
-            // no current thread, just switch to next
-            let mut next_inner = next_thread.exclusive_access();
-            next_inner.state = ThreadState::Running;
-            let next_thr_ctx = &next_inner.thread_ctx as *const _;
-
-            let unused_thr_ctx = &mut ThreadContext::empty() as *mut _;
-
-            drop(next_inner);
-            inner.cur = Some(next_thread);
-            drop(inner);
-            // println!("going to switch");
-            unsafe {
-                __switch(unused_thr_ctx, next_thr_ctx);
-            }
-            return;
-        }
+/// Suspend current thread and let the hart run a ready one, which may be this thread again
+pub fn switch_thread() {
+    let thread = get_cur_thread().unwrap();
+    let mut inner = thread.exclusive_access();
+    if inner.state == ThreadState::Running {
+        inner.state = ThreadState::Ready;
     }
-    // no other ready thread, do nothing
+    drop(inner);
+    switch_out(thread);
 }
 
-/// Block current thread, and switch to a ready one.
+/// Block current thread until `wakeup_thread`. It may be woken up on another hart before it
+/// gets here, then it's ready already and only gives up the hart.
 pub fn block_thread() {
     let thread = get_cur_thread().unwrap();
-    let mut thr_inner = thread.exclusive_access();
-    thr_inner.state = ThreadState::Blocked;
-    drop(thr_inner);
+    let mut inner = thread.exclusive_access();
+    if inner.state == ThreadState::Running {
+        inner.state = ThreadState::Blocked;
+    }
+    drop(inner);
+    switch_out(thread);
+}
 
-    // THREAD_MANAGER.exclusive_access().cur = None;
-    switch_thread();
+/// Make the other threads of the exiting process exit. The ones running on other harts exit
+/// when they trap, see `trap_handler`, and we wait until they switch out.
+fn stop_other_threads(proc: &Arc<ProcessControlBlock>, cur: &Arc<ThreadControlBlock>) {
+    proc.exclusive_access().state = ProcessState::Exiting;
+    loop {
+        // a running thread may create more threads meanwhile
+        let threads: Vec<_> = proc
+            .exclusive_access()
+            .threads
+            .iter()
+            .flatten()
+            .filter(|thread| !Arc::ptr_eq(thread, cur))
+            .cloned()
+            .collect();
+        let mut running = false;
+        for thread in threads {
+            let mut inner = thread.exclusive_access();
+            if inner.on_cpu {
+                running = true;
+            } else {
+                inner.state = ThreadState::Exited;
+            }
+        }
+        if !running {
+            break;
+        }
+        spin_loop();
+    }
 }
 
 /// Exit current proc and switch to a ready one. If the current proc is init, then shutdown.
@@ -140,12 +145,12 @@ pub fn exit_thread(exit_code: i32) {
 
     // record exit code
     cur_thr_inner.exit_code = Some(exit_code);
+    cur_thr_inner.state = ThreadState::Exited;
 
-    // clear cur thread's resources
-    cur_thr_inner.res = None;
-
+    // clear cur thread's resources, which needs the pcb, so release the tcb first
+    let res = cur_thr_inner.res.take();
     drop(cur_thr_inner);
-    drop(cur_thr);
+    drop(res);
 
     if tid == 0 {
         // this is the main thread of the process
@@ -154,19 +159,21 @@ pub fn exit_thread(exit_code: i32) {
             println!("[kernel] Goodbye! exit code: {}", exit_code);
             shutdown();
         }
+        stop_other_threads(&cur_proc, &cur_thr);
         remove_from_pid2pcb(pid);
-        let mut cur_proc_inner = cur_proc.exclusive_access();
-        // mark current proc as a zombie, and its pcb will be recycled later
-        cur_proc_inner.state = ProcessState::Zombie;
-        cur_proc_inner.exit_code = exit_code;
 
-        // move all children to init
+        // move all children to init, which is locked before its descendants like in waitpid
+        let children = core::mem::take(&mut cur_proc.exclusive_access().children);
         let mut init_inner = INIT_PCB.exclusive_access();
-        for child in cur_proc_inner.children.iter() {
+        for child in children {
             child.exclusive_access().parent = Some(Arc::downgrade(&INIT_PCB));
-            init_inner.children.push(Arc::clone(child));
+            init_inner.children.push(child);
         }
         drop(init_inner);
+        // some of them may be zombies already
+        wakeup_waiters(&INIT_PCB);
+
+        let mut cur_proc_inner = cur_proc.exclusive_access();
 
         // dealloc all threads' resources
         let mut recycle_resources = Vec::new();
@@ -191,14 +198,22 @@ pub fn exit_thread(exit_code: i32) {
 
         // require access to cur_proc_inner again
         let mut cur_proc_inner = cur_proc.exclusive_access();
-        cur_proc_inner.children.clear();
         cur_proc_inner.mm.recycle_data_pages();
         cur_proc_inner.fd_table.clear();
 
         // remove all threads except the main thread, whose tcb will be deallocated in waitpid
         while cur_proc_inner.threads.len() > 1 {
-            cur_proc_inner.threads.pop();
+            if let Some(thread) = cur_proc_inner.threads.pop().flatten() {
+                let usage = thread.exclusive_access().clock.usage;
+                cur_proc_inner.exited_usage.add(&usage);
+            }
         }
+
+        // mark current proc as a zombie at last, and its pcb will be recycled by the parent
+        cur_proc_inner.state = ProcessState::Zombie;
+        cur_proc_inner.exit_code = exit_code;
+        drop(cur_proc_inner);
+        notify_parent(&cur_proc);
     }
 
     drop(cur_proc);
@@ -206,8 +221,7 @@ pub fn exit_thread(exit_code: i32) {
     // now this thread's tcb still exists
     // give out control flow and never come back
     // waiting for parent to release all its resources. e.g. page table
-    THREAD_MANAGER.exclusive_access().cur = None;
-    switch_thread()
+    switch_out(cur_thr);
 }
 
 /// Push a newly created thread to the scheduler's ready queue.
@@ -216,14 +230,27 @@ pub fn push_thread(tcb: Arc<ThreadControlBlock>) {
     inner.scheduler.push(tcb);
 }
 
+/// Account a timer tick to the current thread, return whether it should give up the CPU
+pub fn tick_thread() -> bool {
+    let cur = get_cur_thread().unwrap();
+    THREAD_MANAGER.exclusive_access().scheduler.tick(&cur)
+}
+
+/// Make a blocked thread ready. If it hasn't switched out yet, the hart running it pushes it
+/// to the scheduler after the switch, so its context is saved before another hart picks it up.
 pub fn wakeup_thread(tcb: Arc<ThreadControlBlock>) {
     let mut thr_inner = tcb.exclusive_access();
-    thr_inner.state = ThreadState::Ready;
-    drop(thr_inner);
-    push_thread(tcb);
+    match thr_inner.state {
+        ThreadState::Blocked | ThreadState::Running => {
+            thr_inner.state = ThreadState::Ready;
+            if !thr_inner.on_cpu {
+                push_thread(tcb.clone());
+            }
+        }
+        ThreadState::Ready | ThreadState::Exited => {}
+    }
 }
 
-#[allow(unused)]
 /// Fetch a thread from the scheduler's ready queue and let it possess the cpu.
 pub fn pop_thread() -> Option<Arc<ThreadControlBlock>> {
     let mut inner = THREAD_MANAGER.exclusive_access();
@@ -232,7 +259,43 @@ pub fn pop_thread() -> Option<Arc<ThreadControlBlock>> {
 
 pub fn remove_thread(tcb: Arc<ThreadControlBlock>) {
     let mut inner = THREAD_MANAGER.exclusive_access();
-    inner.scheduler.remove(tcb);
+    inner.scheduler.remove(&tcb);
+}
+
+/// Wake up the threads of `proc` waiting for its children
+pub fn wakeup_waiters(proc: &ProcessControlBlock) {
+    let waiters = core::mem::take(&mut proc.exclusive_access().wait_queue);
+    for thread in waiters {
+        wakeup_thread(thread);
+    }
+}
+
+/// Tell the parent of `proc` that it has exited or stopped
+pub fn notify_parent(proc: &ProcessControlBlock) {
+    // the child is released before the parent is locked
+    let parent = proc
+        .exclusive_access()
+        .parent
+        .as_ref()
+        .and_then(Weak::upgrade);
+    if let Some(parent) = parent {
+        wakeup_waiters(&parent);
+    }
+}
+
+/// The processes in group `pgid`
+pub fn pgid2pcbs(pgid: usize) -> Vec<Arc<ProcessControlBlock>> {
+    // a pcb is locked before the manager, so look at them after releasing it
+    let procs: Vec<_> = THREAD_MANAGER
+        .exclusive_access()
+        .pid2pcb
+        .values()
+        .cloned()
+        .collect();
+    procs
+        .into_iter()
+        .filter(|proc| proc.exclusive_access().pgid == pgid)
+        .collect()
 }
 
 pub fn pid2pcb(pid: usize) -> Option<Arc<ProcessControlBlock>> {
@@ -261,20 +324,3 @@ pub fn remove_from_pid2pcb(pid: usize) {
         panic!("remove_from_pid2pcb: pid {} not found", pid);
     }
 }
-
-pub fn launch(proc: Arc<ProcessControlBlock>) {
-    let thread = proc.exclusive_access().get_thread(0);
-    let mut inner = THREAD_MANAGER.exclusive_access();
-    inner.cur = Some(thread);
-    inner.cur.as_ref().unwrap().exclusive_access().state = ThreadState::Running;
-    drop(inner);
-    let cur_thr = get_cur_thread().unwrap();
-    let cur_thr_inner = cur_thr.exclusive_access();
-    let unused_thr_ctx = &mut ThreadContext::empty() as *mut _;
-    let cur_thr_ctx = &cur_thr_inner.thread_ctx as *const _;
-    drop(cur_thr_inner);
-    drop(cur_thr);
-    unsafe {
-        __switch(unused_thr_ctx, cur_thr_ctx);
-    }
-}
@@ -5,19 +5,24 @@ mod scheduler;
 
 mod action;
 mod pcb;
+mod processor;
 mod signal;
 mod switch;
 mod thread;
+mod usage;
 
 pub use action::SignalAction;
 pub use manager::{
-    block_thread, exit_thread, get_cur_proc, get_cur_thread, get_cur_trap_ctx,
-    get_cur_trap_ctx_user_va, get_cur_user_token, launch, pid2pcb, push_thread, switch_thread,
-    wakeup_thread,
+    block_thread, exit_thread, get_cur_proc, get_cur_trap_ctx, get_cur_trap_ctx_user_va,
+    get_cur_user_token, pgid2pcbs, pid2pcb, push_thread, switch_thread, tick_thread, wakeup_thread,
 };
-pub use pcb::INIT_PCB;
+pub use pcb::{ProcessControlBlock, INIT_PCB};
+pub use processor::{get_cur_thread, hart_id, run_threads, try_get_cur_thread};
+pub use scheduler::{NICE_MAX, NICE_MIN};
 pub use signal::{
-    check_signals_error_of_current, current_add_signal, handle_signals, SignalFlags, MAX_SIG,
+    check_interrupt, check_signals_error_of_current, current_add_fault_signal, current_add_signal,
+    handle_signals, send_signal, SignalFlags, MAX_SIG,
 };
 
 pub use thread::ThreadControlBlock;
+pub use usage::{RUsage, Usage};
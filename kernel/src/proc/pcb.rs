@@ -1,6 +1,8 @@
+use crate::errno::Errno;
 use crate::fs::kernel_file::{KernelFile, OpenFlags};
 use crate::fs::stdio::{Stdin, Stdout};
 use crate::fs::File;
+use crate::mm::set_running;
 use crate::mm::MemoryManager;
 use crate::mm::VirtAddr;
 use crate::mm::KERNEL_MM;
@@ -8,21 +10,23 @@ use crate::proc::action::SignalActions;
 use crate::proc::manager::insert_to_pid2pcb;
 use crate::proc::resource::{pid_alloc, PIDGuard, RecycleAllocator};
 use crate::proc::thread::ThreadControlBlock;
-use crate::proc::{push_thread, SignalFlags};
-use crate::sync::{Mutex, UPSafeCell};
+use crate::proc::{push_thread, SignalFlags, Usage};
+use crate::sync::{Mutex, SpinLock, SpinLockGuard};
 use crate::trap::TrapContext;
-use alloc::collections::BTreeMap;
+use acore_fs::Inode;
+use alloc::collections::{BTreeMap, VecDeque};
 use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::sync::Weak;
 use alloc::vec::Vec;
-use core::cell::RefMut;
 use crate::sync::Condvar;
 use lazy_static::lazy_static;
 
 #[derive(Debug)]
 pub enum ProcessState {
     Ready,
+    /// The main thread is exiting, and the other threads exit when they trap
+    Exiting,
     Zombie,
 }
 
@@ -30,7 +34,7 @@ pub struct ProcessControlBlock {
     // immutable
     pub pid: PIDGuard,
     // mutable
-    pub inner: UPSafeCell<ProcessControlBlockInner>,
+    pub inner: SpinLock<ProcessControlBlockInner>,
 }
 
 pub struct ProcessControlBlockInner {
@@ -45,12 +49,22 @@ pub struct ProcessControlBlockInner {
     pub signal_mask: SignalFlags,
     // the signal which is being handling
     pub handling_sig: isize,
+    // the address of the last fault, passed to the handler of the signal it raised
+    pub fault_addr: Option<usize>,
     // Signal actions
     pub signal_actions: SignalActions,
     // if the task is killed
     pub killed: bool,
     // if the task is frozen by a signal
     pub frozen: bool,
+    // the signal which stopped the task, until wait4 reports it to the parent
+    pub stopped_by: Option<usize>,
+    // the signal which killed the task, reported by wait4
+    pub term_signal: Option<usize>,
+    // the process group, whose processes get the signals from the console keys together
+    pub pgid: usize,
+    // threads waiting for a child to exit or stop, woken up by a signal too
+    pub wait_queue: VecDeque<Arc<ThreadControlBlock>>,
     // backup of the trap context when handling a signal
     pub trap_ctx_backup: Option<TrapContext>,
     pub threads: Vec<Option<Arc<ThreadControlBlock>>>,
@@ -58,39 +72,52 @@ pub struct ProcessControlBlockInner {
     pub thread_res_allocator: RecycleAllocator,
     pub mutex_list: Vec<Option<Arc<dyn Mutex>>>,
     pub condvar_list: Vec<Option<Arc<Condvar>>>,
+    // current working directory, always ends with '/'
+    pub cwd: String,
+    // usage of the threads removed from the process
+    pub exited_usage: Usage,
+    // usage of the children reaped by waitpid, and of their reaped children
+    pub children_usage: Usage,
 }
 
 impl ProcessControlBlock {
-    pub fn from_elf(data: &[u8]) -> Arc<Self> {
-        let mm = MemoryManager::from_elf(data);
+    pub fn from_elf(inode: Arc<Inode>) -> Result<Arc<Self>, Errno> {
+        let mm = MemoryManager::from_elf(inode)?;
         let entry_point = mm.entry_point;
         let proc_user_stack_bottom = mm.user_stack_bottom;
 
         let pid_guard = pid_alloc();
+        let pgid = pid_guard.0;
 
         let proc = Arc::new(Self {
             pid: pid_guard,
-            inner: unsafe {
-                UPSafeCell::new(ProcessControlBlockInner {
-                    state: ProcessState::Ready,
-                    parent: None,
-                    children: Vec::new(),
-                    exit_code: 0,
-                    mm,
-                    fd_table: FileDescriptorTable::new(),
-                    signals: SignalFlags::empty(),
-                    signal_mask: SignalFlags::empty(),
-                    handling_sig: -1,
-                    signal_actions: SignalActions::default(),
-                    killed: false,
-                    frozen: false,
-                    trap_ctx_backup: None,
-                    threads: Vec::new(),
-                    thread_res_allocator: RecycleAllocator::new(),
-                    mutex_list: Vec::new(),
-                    condvar_list: Vec::new(),
-                })
-            },
+            inner: SpinLock::new(ProcessControlBlockInner {
+                state: ProcessState::Ready,
+                parent: None,
+                children: Vec::new(),
+                exit_code: 0,
+                mm,
+                fd_table: FileDescriptorTable::new(),
+                signals: SignalFlags::empty(),
+                signal_mask: SignalFlags::empty(),
+                handling_sig: -1,
+                fault_addr: None,
+                signal_actions: SignalActions::default(),
+                killed: false,
+                frozen: false,
+                stopped_by: None,
+                term_signal: None,
+                pgid,
+                wait_queue: VecDeque::new(),
+                trap_ctx_backup: None,
+                threads: Vec::new(),
+                thread_res_allocator: RecycleAllocator::new(),
+                mutex_list: Vec::new(),
+                condvar_list: Vec::new(),
+                cwd: String::from("/"),
+                exited_usage: Usage::default(),
+                children_usage: Usage::default(),
+            }),
         });
 
         // create a main thread
@@ -118,12 +145,12 @@ impl ProcessControlBlock {
         proc_inner.threads.push(Some(thread.clone()));
         drop(proc_inner);
 
-        proc
+        Ok(proc)
     }
     pub fn token(&self) -> usize {
         self.inner.exclusive_access().mm.page_table.token()
     }
-    pub fn exclusive_access(&self) -> RefMut<'_, ProcessControlBlockInner> {
+    pub fn exclusive_access(&self) -> SpinLockGuard<'_, ProcessControlBlockInner> {
         self.inner.exclusive_access()
     }
     pub fn getpid(&self) -> usize {
@@ -136,34 +163,42 @@ impl ProcessControlBlock {
             1,
             "fork only supports single-threaded process"
         );
-        let mm = MemoryManager::from_existed(&parent_inner.mm);
+        let mm = MemoryManager::from_existed(&mut parent_inner.mm);
         let pid_guard = pid_alloc();
         // println!("new pid: {}", pid_guard.0);
 
         let pcb = Arc::new(ProcessControlBlock {
             pid: pid_guard,
-            inner: unsafe {
-                UPSafeCell::new(ProcessControlBlockInner {
-                    state: ProcessState::Ready,
-                    parent: Some(Arc::downgrade(self)),
-                    children: Vec::new(),
-                    exit_code: 0,
-                    mm,
-                    fd_table: parent_inner.fd_table.clone(),
-                    signals: SignalFlags::empty(),
-                    // inherit parent's signal mask and signal actions
-                    signal_mask: parent_inner.signal_mask,
-                    handling_sig: -1,
-                    signal_actions: parent_inner.signal_actions.clone(),
-                    killed: false,
-                    frozen: false,
-                    trap_ctx_backup: None,
-                    threads: Vec::new(),
-                    thread_res_allocator: RecycleAllocator::new(),
-                    mutex_list: Vec::new(),
-                    condvar_list: Vec::new(),
-                })
-            },
+            inner: SpinLock::new(ProcessControlBlockInner {
+                state: ProcessState::Ready,
+                parent: Some(Arc::downgrade(self)),
+                children: Vec::new(),
+                exit_code: 0,
+                mm,
+                fd_table: parent_inner.fd_table.clone(),
+                signals: SignalFlags::empty(),
+                // inherit parent's signal mask and signal actions
+                signal_mask: parent_inner.signal_mask,
+                handling_sig: -1,
+                fault_addr: None,
+                signal_actions: parent_inner.signal_actions.clone(),
+                killed: false,
+                frozen: false,
+                stopped_by: None,
+                term_signal: None,
+                // the child is in the group of its parent
+                pgid: parent_inner.pgid,
+                wait_queue: VecDeque::new(),
+                trap_ctx_backup: None,
+                threads: Vec::new(),
+                thread_res_allocator: RecycleAllocator::new(),
+                mutex_list: Vec::new(),
+                condvar_list: Vec::new(),
+                // inherit parent's working directory
+                cwd: parent_inner.cwd.clone(),
+                exited_usage: Usage::default(),
+                children_usage: Usage::default(),
+            }),
         });
 
         parent_inner.children.push(Arc::clone(&pcb));
@@ -181,6 +216,8 @@ impl ProcessControlBlock {
             // no need to map again since the memory manager is cloned
             false,
         ));
+        // inherit parent's priority
+        tcb.sched().nice = parent_inner.get_thread(0).sched().nice;
         let mut child_inner = pcb.exclusive_access();
         child_inner.threads.push(Some(tcb.clone()));
         drop(child_inner);
@@ -197,7 +234,9 @@ impl ProcessControlBlock {
 
         pcb
     }
-    pub fn exec(self: &Arc<Self>, data: &[u8], args: Vec<String>) {
+    /// Replace the user space with `mm`, which is created from the new ELF file by the caller,
+    /// so a bad file fails exec before the old one is torn down
+    pub fn exec(self: &Arc<Self>, mm: MemoryManager, args: Vec<String>) {
         // println!("exec: pid {}, args: {:?}", self.getpid(), args);
         let mut proc_inner = self.exclusive_access();
         assert_eq!(
@@ -205,12 +244,13 @@ impl ProcessControlBlock {
             1,
             "exec only supports single-threaded process"
         );
-        let mm = MemoryManager::from_elf(data);
+        let stack_limit = proc_inner.mm.stack_limit;
         let user_stack_bottom = mm.user_stack_bottom;
         let entry_point = mm.entry_point;
 
         // substitute memory manager
         proc_inner.mm = mm;
+        set_running(Some(proc_inner.mm.page_table.token()));
 
         // add the mapping for main thread again since mm has been changed
         let thread = proc_inner.get_thread(0);
@@ -261,6 +301,9 @@ impl ProcessControlBlock {
         // align to 8 bytes
         user_sp -= user_sp % core::mem::size_of::<usize>();
 
+        // the stack limit is kept across exec, the args are pushed within the default one
+        mm.stack_limit = stack_limit;
+
         // init trap ctx
         let mut trap_ctx = TrapContext::app_init_context(
             entry_point,
@@ -279,6 +322,9 @@ impl ProcessControlBlock {
             _ => false,
         }
     }
+    pub fn is_exiting(&self) -> bool {
+        matches!(self.inner.exclusive_access().state, ProcessState::Exiting)
+    }
     // pub fn set_state(&self, state: ProcessState) {
     //     self.inner.exclusive_access().state = state;
     // }
@@ -297,6 +343,14 @@ impl ProcessControlBlockInner {
     pub fn thread_count(&self) -> usize {
         self.threads.len()
     }
+    /// The usage of the threads of the process, alive or not
+    pub fn usage(&self) -> Usage {
+        let mut usage = self.exited_usage;
+        for thread in self.threads.iter().flatten() {
+            usage.add(&thread.exclusive_access().clock.usage);
+        }
+        usage
+    }
     pub fn get_thread(&self, tid: usize) -> Arc<ThreadControlBlock> {
         self.threads[tid]
             .as_ref()
@@ -307,15 +361,13 @@ impl ProcessControlBlockInner {
 
 lazy_static! {
     pub static ref INIT_PCB: Arc<ProcessControlBlock> = {
-        let kernel_file = KernelFile::from_path("/bin/init", OpenFlags::RDONLY).unwrap();
-        let v = kernel_file.read_all();
-        ProcessControlBlock::from_elf(v.as_slice())
+        let kernel_file = KernelFile::from_path("/", "/bin/init", OpenFlags::RDONLY).unwrap();
+        ProcessControlBlock::from_elf(kernel_file.inode()).expect("/bin/init is not executable")
     };
 }
 
 pub struct FileDescriptorTable {
     fd_table: BTreeMap<usize, Arc<dyn File + Send + Sync>>,
-    recycled: Vec<usize>,
 }
 impl FileDescriptorTable {
     pub fn new() -> Self {
@@ -325,32 +377,30 @@ impl FileDescriptorTable {
                 (1, Arc::new(Stdout)),
                 (2, Arc::new(Stdout)),
             ]),
-            recycled: Vec::new(),
         }
     }
     pub fn clone(&self) -> Self {
         FileDescriptorTable {
             fd_table: self.fd_table.clone(),
-            recycled: self.recycled.clone(),
         }
     }
+    /// Put the file at the lowest free fd
     pub fn insert_file(&mut self, file: Arc<dyn File + Send + Sync>) -> isize {
-        let fd = if let Some(fd) = self.recycled.pop() {
-            fd
-        } else {
-            self.fd_table.len()
-        };
+        let fd = (0..).find(|fd| !self.fd_table.contains_key(fd)).unwrap();
         self.fd_table.insert(fd, file);
         fd as isize
     }
+    /// Put the file at `fd`, closing the one there
+    pub fn insert_file_at(&mut self, fd: usize, file: Arc<dyn File + Send + Sync>) {
+        self.fd_table.insert(fd, file);
+    }
 
-    pub fn dealloc_fd(&mut self, fd: usize) -> isize {
+    pub fn dealloc_fd(&mut self, fd: usize) -> Result<(), Errno> {
         if let Some(_) = self.fd_table.remove(&fd) {
-            self.recycled.push(fd);
-            0
+            Ok(())
         } else {
             // panic!("fd {} not found", fd);
-            -1
+            Err(Errno::EBADF)
         }
     }
     pub fn get_file(&self, fd: usize) -> Option<Arc<dyn File + Send + Sync>> {
@@ -358,6 +408,5 @@ impl FileDescriptorTable {
     }
     pub fn clear(&mut self) {
         self.fd_table.clear();
-        self.recycled.clear();
     }
 }
@@ -0,0 +1,123 @@
+//! The harts. Each runs the ready threads one by one in its idle loop, and a thread gives up
+//! the hart by switching back to the loop, which puts it back to the scheduler if it's ready.
+//! So a thread is never in the scheduler before its context is saved, and another hart can't
+//! pick it up too early.
+//!
+//! With nothing to run, the hart waits for an interrupt with wfi. The kernel runs with sstatus.SIE
+//! clear, but wfi still wakes up on an interrupt enabled in sie, and the idle loop handles it.
+//! A thread woken up by another hart waits for the next tick at most.
+
+use crate::config::MAX_HARTS;
+use crate::mm::set_running;
+use crate::proc::ctx::ThreadContext;
+use crate::proc::manager::{pop_thread, push_thread};
+use crate::proc::switch::__switch;
+use crate::proc::thread::{ThreadControlBlock, ThreadState};
+use crate::sync::SpinLock;
+use crate::trap::handle_timer_interrupt;
+use alloc::sync::Arc;
+use core::arch::asm;
+use lazy_static::lazy_static;
+use riscv::register::sip;
+
+pub struct Processor {
+    /// The thread running on the hart
+    cur: Option<Arc<ThreadControlBlock>>,
+    /// The context of the idle loop, saved while a thread runs
+    idle_ctx: ThreadContext,
+}
+
+impl Processor {
+    pub fn new() -> Self {
+        Self {
+            cur: None,
+            idle_ctx: ThreadContext::empty(),
+        }
+    }
+    pub fn current(&self) -> Option<Arc<ThreadControlBlock>> {
+        self.cur.as_ref().map(Arc::clone)
+    }
+}
+
+lazy_static! {
+    /// Only accessed by its own hart, except for reporting kernel bugs
+    static ref PROCESSORS: [SpinLock<Processor>; MAX_HARTS] =
+        core::array::from_fn(|_| SpinLock::new(Processor::new()));
+}
+
+/// The id of the current hart, kept in tp by the kernel
+pub fn hart_id() -> usize {
+    let id;
+    unsafe {
+        asm!("mv {}, tp", out(reg) id);
+    }
+    id
+}
+
+pub fn get_cur_thread() -> Option<Arc<ThreadControlBlock>> {
+    PROCESSORS[hart_id()].exclusive_access().current()
+}
+
+/// Like `get_cur_thread`, but None if the processor is in use, for reporting kernel bugs
+pub fn try_get_cur_thread() -> Option<Arc<ThreadControlBlock>> {
+    PROCESSORS[hart_id()].try_access()?.current()
+}
+
+/// Switch from the current thread, whose context is saved to `thr_ctx`, to the idle loop.
+/// It comes back here when some hart picks it up again.
+pub fn schedule(thr_ctx: *mut ThreadContext) {
+    let idle_ctx = &PROCESSORS[hart_id()].exclusive_access().idle_ctx as *const ThreadContext;
+    unsafe {
+        __switch(thr_ctx, idle_ctx);
+    }
+}
+
+/// Wait for an interrupt, the tick may wake up some threads
+fn idle() {
+    unsafe {
+        asm!("wfi");
+    }
+    if sip::read().ssoft() {
+        handle_timer_interrupt();
+    }
+}
+
+/// The idle loop of the current hart, on its boot stack
+pub fn run_threads() -> ! {
+    loop {
+        let Some(next) = pop_thread() else {
+            idle();
+            continue;
+        };
+        let mut next_inner = next.exclusive_access();
+        if next_inner.state != ThreadState::Ready {
+            // its process exited after it was pushed
+            continue;
+        }
+        next_inner.state = ThreadState::Running;
+        next_inner.on_cpu = true;
+        next_inner.clock.restart();
+        let next_ctx = &next_inner.thread_ctx as *const ThreadContext;
+        drop(next_inner);
+        // the other harts leave the pages of its process alone while it runs here
+        set_running(next.pcb.upgrade().map(|pcb| pcb.token()));
+
+        let mut processor = PROCESSORS[hart_id()].exclusive_access();
+        let idle_ctx = &mut processor.idle_ctx as *mut ThreadContext;
+        processor.cur = Some(next);
+        drop(processor);
+        unsafe {
+            __switch(idle_ctx, next_ctx);
+        }
+
+        // the thread has switched out and its context is saved
+        set_running(None);
+        let prev = PROCESSORS[hart_id()].exclusive_access().cur.take().unwrap();
+        let mut prev_inner = prev.exclusive_access();
+        prev_inner.on_cpu = false;
+        // a blocked thread is pushed by the one waking it up, and an exited one is dropped
+        if prev_inner.state == ThreadState::Ready {
+            push_thread(prev.clone());
+        }
+    }
+}
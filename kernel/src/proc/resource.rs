@@ -1,7 +1,9 @@
-use crate::config::{KERNEL_STACK_SIZE, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT_BASE, USER_STACK_SIZE};
+use crate::config::{
+    KERNEL_STACK_SIZE, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT_BASE, USER_STACK_GUARD, USER_STACK_MAX,
+};
 use crate::mm::{MapPerm, MapType, PhysPageNum, VirtAddr, KERNEL_MM};
 use crate::proc::pcb::ProcessControlBlock;
-use crate::sync::UPSafeCell;
+use crate::sync::SpinLock;
 use alloc::sync::{Arc, Weak};
 use alloc::vec::Vec;
 use lazy_static::lazy_static;
@@ -39,8 +41,8 @@ impl RecycleAllocator {
 }
 
 lazy_static! {
-    pub static ref PID_ALLOCATOR: UPSafeCell<RecycleAllocator> =
-        unsafe { UPSafeCell::new(RecycleAllocator::new()) };
+    pub static ref PID_ALLOCATOR: SpinLock<RecycleAllocator> =
+        SpinLock::new(RecycleAllocator::new());
 }
 #[derive(Debug)]
 pub struct PIDGuard(pub usize);
@@ -60,7 +62,7 @@ pub fn get_trap_ctx_addr_by_tid(tid: usize) -> usize {
 }
 
 pub fn get_user_stack_bottom_by_tid(user_stack_base: usize, tid: usize) -> usize {
-    user_stack_base + tid * (PAGE_SIZE + USER_STACK_SIZE)
+    user_stack_base + tid * (USER_STACK_GUARD + USER_STACK_MAX)
 }
 
 pub struct ThreadResource {
@@ -86,15 +88,12 @@ impl ThreadResource {
     pub fn add_mapping(&self) {
         let proc = self.pcb.upgrade().unwrap();
         let mut inner = proc.exclusive_access();
-        // map user stack
+        // reserve user stack, it's mapped as it grows
         let user_stack_bottom = get_user_stack_bottom_by_tid(self.user_stack_base, self.tid);
-        let user_stack_top = user_stack_bottom + USER_STACK_SIZE;
-        inner.mm.insert_area(
+        let user_stack_top = user_stack_bottom + USER_STACK_MAX;
+        inner.mm.insert_stack(
             VirtAddr::from(user_stack_bottom),
             VirtAddr::from(user_stack_top),
-            MapType::Framed,
-            MapPerm::R | MapPerm::W | MapPerm::U,
-            None,
         );
         // map trampoline
         let trap_ctx_addr = get_trap_ctx_addr_by_tid(self.tid);
@@ -115,7 +114,7 @@ impl ThreadResource {
         ).unwrap()
     }
     pub fn get_user_stack_top(&self) -> usize {
-        get_user_stack_bottom_by_tid(self.user_stack_base, self.tid) + USER_STACK_SIZE
+        get_user_stack_bottom_by_tid(self.user_stack_base, self.tid) + USER_STACK_MAX
     }
 }
 
@@ -137,8 +136,8 @@ impl Drop for ThreadResource {
 }
 
 lazy_static! {
-    static ref KERNEL_STACK_ALLOCATOR: UPSafeCell<RecycleAllocator> =
-        unsafe { UPSafeCell::new(RecycleAllocator::new()) };
+    static ref KERNEL_STACK_ALLOCATOR: SpinLock<RecycleAllocator> =
+        SpinLock::new(RecycleAllocator::new());
 }
 
 /// kernel stack guard with kid
@@ -0,0 +1,33 @@
+use super::Scheduler;
+use crate::proc::thread::ThreadControlBlock;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+/// First come first served. A thread runs until it blocks, yields or exits, so the programs
+/// spinning for others to make progress hang.
+pub struct FifoScheduler {
+    queue: VecDeque<Arc<ThreadControlBlock>>,
+}
+
+impl FifoScheduler {
+    pub fn new() -> Self {
+        Self {
+            queue: VecDeque::new(),
+        }
+    }
+}
+
+impl Scheduler for FifoScheduler {
+    fn push(&mut self, tcb: Arc<ThreadControlBlock>) {
+        self.queue.push_back(tcb);
+    }
+    fn pop(&mut self) -> Option<Arc<ThreadControlBlock>> {
+        self.queue.pop_front()
+    }
+    fn remove(&mut self, tcb: &Arc<ThreadControlBlock>) {
+        self.queue.retain(|t| !Arc::ptr_eq(t, tcb));
+    }
+    fn tick(&mut self, _cur: &Arc<ThreadControlBlock>) -> bool {
+        false
+    }
+}
@@ -0,0 +1,88 @@
+use super::{SchedInfo, Scheduler};
+use crate::proc::thread::ThreadControlBlock;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::cmp::min;
+
+/// The number of queues
+const LEVELS: usize = 4;
+/// All threads go back to the highest queue every such ticks, so the ones at the lower queues
+/// don't starve, and the ones becoming interactive get their priority back
+const BOOST_PERIOD: usize = 100;
+
+/// The time slice at `level` in ticks, longer for the lower queues
+fn time_slice(level: usize) -> usize {
+    1 << level
+}
+
+/// Multi-level feedback queue. A thread starts at the highest queue, and moves down one queue
+/// when it uses up its time slice there, even over several runs. A thread from a higher queue
+/// always runs first.
+pub struct MlfqScheduler {
+    queues: [VecDeque<Arc<ThreadControlBlock>>; LEVELS],
+    ticks: usize,
+    /// The number of priority boosts so far
+    epoch: usize,
+}
+
+impl MlfqScheduler {
+    pub fn new() -> Self {
+        Self {
+            queues: Default::default(),
+            ticks: 0,
+            epoch: 0,
+        }
+    }
+
+    /// Move a thread to the highest queue if it hasn't been through the last boost, e.g. it
+    /// was blocked then
+    fn catch_up(&self, sched: &mut SchedInfo) {
+        if sched.epoch != self.epoch {
+            sched.epoch = self.epoch;
+            sched.level = 0;
+            sched.ticks = 0;
+        }
+    }
+
+    fn boost(&mut self) {
+        self.epoch += 1;
+        for level in 1..LEVELS {
+            while let Some(tcb) = self.queues[level].pop_front() {
+                self.queues[0].push_back(tcb);
+            }
+        }
+    }
+}
+
+impl Scheduler for MlfqScheduler {
+    fn push(&mut self, tcb: Arc<ThreadControlBlock>) {
+        let mut sched = tcb.sched();
+        self.catch_up(&mut sched);
+        let level = sched.level;
+        drop(sched);
+        self.queues[level].push_back(tcb);
+    }
+    fn pop(&mut self) -> Option<Arc<ThreadControlBlock>> {
+        self.queues.iter_mut().find_map(|queue| queue.pop_front())
+    }
+    fn remove(&mut self, tcb: &Arc<ThreadControlBlock>) {
+        for queue in self.queues.iter_mut() {
+            queue.retain(|t| !Arc::ptr_eq(t, tcb));
+        }
+    }
+    fn tick(&mut self, cur: &Arc<ThreadControlBlock>) -> bool {
+        self.ticks += 1;
+        if self.ticks % BOOST_PERIOD == 0 {
+            self.boost();
+        }
+        let mut sched = cur.sched();
+        self.catch_up(&mut sched);
+        sched.ticks += 1;
+        if sched.ticks < time_slice(sched.level) {
+            return false;
+        }
+        sched.ticks = 0;
+        sched.level = min(sched.level + 1, LEVELS - 1);
+        true
+    }
+}
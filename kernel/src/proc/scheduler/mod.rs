@@ -0,0 +1,70 @@
+//! Scheduling policies, chosen at build time by the features sched-fifo, sched-rr and
+//! sched-mlfq. Stride scheduling is used if none of them is enabled.
+
+mod fifo;
+mod mlfq;
+mod rr;
+mod stride;
+
+use crate::proc::thread::ThreadControlBlock;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use fifo::FifoScheduler;
+use mlfq::MlfqScheduler;
+use rr::RrScheduler;
+use stride::StrideScheduler;
+
+/// The highest priority, a thread with it gets the most CPU time
+pub const NICE_MIN: i32 = -20;
+/// The lowest priority
+pub const NICE_MAX: i32 = 19;
+
+/// Information for scheduling, kept in the thread. Each policy uses a part of it.
+pub struct SchedInfo {
+    pub nice: i32,
+    /// The virtual time the thread has run, for stride scheduling
+    pub pass: u64,
+    /// The queue of the thread in MLFQ, 0 is the highest one
+    pub level: usize,
+    /// The ticks the thread has run in its time slice
+    pub ticks: usize,
+    /// The last priority boost of MLFQ the thread has been through
+    pub epoch: usize,
+}
+
+impl SchedInfo {
+    pub fn new() -> Self {
+        Self {
+            nice: 0,
+            pass: 0,
+            level: 0,
+            ticks: 0,
+            epoch: 0,
+        }
+    }
+}
+
+/// A scheduling policy, which keeps the ready threads and picks the next one to run
+pub trait Scheduler: Send {
+    /// Add a ready thread
+    fn push(&mut self, tcb: Arc<ThreadControlBlock>);
+    /// Take the thread to run next
+    fn pop(&mut self) -> Option<Arc<ThreadControlBlock>>;
+    /// Remove a thread from the ready ones, e.g. when its process exits
+    fn remove(&mut self, tcb: &Arc<ThreadControlBlock>);
+    /// Account a timer tick to the running thread, return whether it should give up the CPU
+    fn tick(&mut self, cur: &Arc<ThreadControlBlock>) -> bool;
+}
+
+/// Create the scheduler of the policy chosen by the features
+pub fn new_scheduler() -> Box<dyn Scheduler> {
+    if cfg!(feature = "sched-fifo") {
+        Box::new(FifoScheduler::new())
+    } else if cfg!(feature = "sched-rr") {
+        Box::new(RrScheduler::new())
+    } else if cfg!(feature = "sched-mlfq") {
+        Box::new(MlfqScheduler::new())
+    } else {
+        Box::new(StrideScheduler::new())
+    }
+}
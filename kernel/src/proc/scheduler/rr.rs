@@ -0,0 +1,38 @@
+use super::Scheduler;
+use crate::config::RR_TIME_SLICE;
+use crate::proc::thread::ThreadControlBlock;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+/// Round robin, each thread runs for a time slice of `RR_TIME_SLICE` ticks at most
+pub struct RrScheduler {
+    queue: VecDeque<Arc<ThreadControlBlock>>,
+}
+
+impl RrScheduler {
+    pub fn new() -> Self {
+        Self {
+            queue: VecDeque::new(),
+        }
+    }
+}
+
+impl Scheduler for RrScheduler {
+    fn push(&mut self, tcb: Arc<ThreadControlBlock>) {
+        self.queue.push_back(tcb);
+    }
+    /// The thread picked gets a whole time slice
+    fn pop(&mut self) -> Option<Arc<ThreadControlBlock>> {
+        let tcb = self.queue.pop_front()?;
+        tcb.sched().ticks = 0;
+        Some(tcb)
+    }
+    fn remove(&mut self, tcb: &Arc<ThreadControlBlock>) {
+        self.queue.retain(|t| !Arc::ptr_eq(t, tcb));
+    }
+    fn tick(&mut self, cur: &Arc<ThreadControlBlock>) -> bool {
+        let mut sched = cur.sched();
+        sched.ticks += 1;
+        sched.ticks >= RR_TIME_SLICE
+    }
+}
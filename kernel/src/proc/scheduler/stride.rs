@@ -0,0 +1,67 @@
+use super::{Scheduler, NICE_MIN};
+use crate::proc::thread::ThreadControlBlock;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::cmp::max;
+
+/// The weights of nice -20 to 19, the same as Linux. One level less gets about 10% more CPU time.
+const NICE_TO_WEIGHT: [u64; 40] = [
+    88761, 71755, 56483, 46273, 36291, 29154, 23254, 18705, 14949, 11916, 9548, 7620, 6100, 4904,
+    3906, 3121, 2501, 1991, 1586, 1277, 1024, 820, 655, 526, 423, 335, 272, 215, 172, 137, 110, 87,
+    70, 56, 45, 36, 29, 23, 18, 15,
+];
+
+/// The pass advanced by a time slice at weight 1
+const BIG_STRIDE: u64 = 1 << 32;
+
+/// The pass advanced by a time slice at `nice`, inversely proportional to its weight
+fn stride(nice: i32) -> u64 {
+    BIG_STRIDE / NICE_TO_WEIGHT[(nice - NICE_MIN) as usize]
+}
+
+/// Stride scheduling, each thread gets CPU time in proportion to the weight of its nice value.
+/// The time slice is a tick.
+pub struct StrideScheduler {
+    queue: VecDeque<Arc<ThreadControlBlock>>,
+    /// The pass of the last thread picked. A thread joining the queue starts from it at least,
+    /// so it can't take the CPU for long after sleeping.
+    pass: u64,
+}
+
+impl StrideScheduler {
+    pub fn new() -> Self {
+        Self {
+            queue: VecDeque::new(),
+            pass: 0,
+        }
+    }
+}
+
+impl Scheduler for StrideScheduler {
+    fn push(&mut self, tcb: Arc<ThreadControlBlock>) {
+        let mut sched = tcb.sched();
+        sched.pass = max(sched.pass, self.pass);
+        drop(sched);
+        self.queue.push_back(tcb);
+    }
+    /// Pick the thread with the smallest pass, the earliest one in the queue if there's a tie
+    fn pop(&mut self) -> Option<Arc<ThreadControlBlock>> {
+        let (i, _) = self
+            .queue
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, tcb)| tcb.sched().pass)?;
+        let tcb = self.queue.remove(i).unwrap();
+        let mut sched = tcb.sched();
+        self.pass = sched.pass;
+        sched.pass += stride(sched.nice);
+        drop(sched);
+        Some(tcb)
+    }
+    fn remove(&mut self, tcb: &Arc<ThreadControlBlock>) {
+        self.queue.retain(|t| !Arc::ptr_eq(t, tcb));
+    }
+    fn tick(&mut self, _cur: &Arc<ThreadControlBlock>) -> bool {
+        true
+    }
+}
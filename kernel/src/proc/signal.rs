@@ -1,4 +1,7 @@
-use crate::proc::{get_cur_proc, get_cur_thread, switch_thread};
+use crate::console::stdin::wakeup_readers;
+use crate::proc::manager::notify_parent;
+use crate::proc::pcb::{ProcessControlBlock, ProcessControlBlockInner};
+use crate::proc::{get_cur_proc, get_cur_thread, switch_thread, wakeup_thread};
 use bitflags::*;
 
 pub const MAX_SIG: usize = 31;
@@ -41,19 +44,29 @@ bitflags! {
 }
 
 impl SignalFlags {
+    /// The signals which stop the process by default
+    pub fn stop_signals() -> Self {
+        Self::SIGSTOP | Self::SIGTSTP | Self::SIGTTIN | Self::SIGTTOU
+    }
     pub fn check_error(&self) -> Option<(i32, &'static str)> {
         if self.contains(Self::SIGINT) {
             Some((-2, "Killed, SIGINT=2"))
         } else if self.contains(Self::SIGILL) {
             Some((-4, "Illegal Instruction, SIGILL=4"))
+        } else if self.contains(Self::SIGTRAP) {
+            Some((-5, "Trace/Breakpoint Trap, SIGTRAP=5"))
         } else if self.contains(Self::SIGABRT) {
             Some((-6, "Aborted, SIGABRT=6"))
+        } else if self.contains(Self::SIGBUS) {
+            Some((-7, "Bus Error, SIGBUS=7"))
         } else if self.contains(Self::SIGFPE) {
             Some((-8, "Erroneous Arithmetic Operation, SIGFPE=8"))
         } else if self.contains(Self::SIGKILL) {
             Some((-9, "Killed, SIGKILL=9"))
         } else if self.contains(Self::SIGSEGV) {
             Some((-11, "Segmentation Fault, SIGSEGV=11"))
+        } else if self.contains(Self::SIGSYS) {
+            Some((-31, "Bad System Call, SIGSYS=31"))
         } else {
             //println!("[K] signalflags check_error  {:?}", self);
             None
@@ -79,14 +92,89 @@ pub fn current_add_signal(signal: SignalFlags) {
     //     inner.signals
     // );
 }
+/// Send `signal` to `proc`, e.g. by kill or a key on the console.
+/// SIGCONT continues the process at once, since a process stopped while blocked in the kernel
+/// doesn't trap to handle it. The threads in an interruptible wait wake up to look at the signal.
+pub fn send_signal(proc: &ProcessControlBlock, signal: SignalFlags) {
+    let mut inner = proc.exclusive_access();
+    inner.signals |= signal;
+    if signal == SignalFlags::SIGCONT {
+        inner.frozen = false;
+        inner.stopped_by = None;
+        inner.signals.remove(SignalFlags::stop_signals());
+    }
+    let waiters = core::mem::take(&mut inner.wait_queue);
+    drop(inner);
+    for thread in waiters {
+        wakeup_thread(thread);
+    }
+    wakeup_readers();
+}
+
+/// Stop the process if a signal stopping it by default is pending, return true if it's stopped
+fn stop_if_pending(inner: &mut ProcessControlBlockInner) -> bool {
+    for sig in 0..(MAX_SIG + 1) {
+        let signal = SignalFlags::from_bits(1 << sig).unwrap();
+        if !SignalFlags::stop_signals().contains(signal)
+            || !inner.signals.contains(signal)
+            || inner.signal_mask.contains(signal)
+        {
+            continue;
+        }
+        if signal == SignalFlags::SIGSTOP || inner.signal_actions.table[sig].handler == 0 {
+            inner.signals.remove(signal);
+            inner.frozen = true;
+            inner.stopped_by = Some(sig);
+            return true;
+        }
+    }
+    false
+}
+
+/// Whether a pending signal should interrupt a blocking syscall: one killing the process, or
+/// one caught by a handler. It's handled on the way back to the user.
+fn has_interrupting_signal(inner: &ProcessControlBlockInner) -> bool {
+    let pending = inner.signals - inner.signal_mask;
+    if pending.check_error().is_some() {
+        return true;
+    }
+    (1..(MAX_SIG + 1)).any(|sig| {
+        pending.contains(SignalFlags::from_bits(1 << sig).unwrap())
+            && inner.signal_actions.table[sig].handler != 0
+    })
+}
+
+/// Called by a thread woken up in an interruptible wait. A pending stop signal stops the process
+/// here, as the thread doesn't trap to handle it, and the thread goes on waiting while stopped.
+/// Return true if the wait should be given up for another signal.
+pub fn check_interrupt() -> bool {
+    let cur_proc = get_cur_proc();
+    let mut inner = cur_proc.exclusive_access();
+    let stopped = stop_if_pending(&mut inner);
+    let interrupted = has_interrupting_signal(&inner);
+    drop(inner);
+    if stopped {
+        notify_parent(&cur_proc);
+    }
+    interrupted
+}
+
+/// Raise `signal` for a fault of the current proc at `addr`, e.g. SIGSEGV for a bad access
+pub fn current_add_fault_signal(signal: SignalFlags, addr: usize) {
+    let cur_proc = get_cur_proc();
+    let mut inner = cur_proc.exclusive_access();
+    inner.signals |= signal;
+    inner.fault_addr = Some(addr);
+}
 fn call_kernel_signal_handler(signal: SignalFlags) {
     let cur_proc = get_cur_proc();
     let mut inner = cur_proc.exclusive_access();
 
     match signal {
         SignalFlags::SIGSTOP => {
-            inner.frozen = true;
-            inner.signals ^= SignalFlags::SIGSTOP;
+            stop_if_pending(&mut inner);
+            drop(inner);
+            notify_parent(&cur_proc);
         }
         SignalFlags::SIGCONT => {
             if inner.signals.contains(SignalFlags::SIGCONT) {
@@ -102,7 +190,8 @@ fn call_kernel_signal_handler(signal: SignalFlags) {
 
 fn call_user_signal_handler(sig: usize, signal: SignalFlags) {
     let cur_thr = get_cur_thread().unwrap();
-    let cur_thr_inner = cur_thr.exclusive_access();
+    // release the tcb before locking the pcb, which is locked first elsewhere
+    let trap_ctx = cur_thr.exclusive_access().get_trap_ctx();
     let cur_proc = cur_thr.pcb.upgrade().unwrap();
     let mut cur_proc_inner = cur_proc.exclusive_access();
 
@@ -116,14 +205,23 @@ fn call_user_signal_handler(sig: usize, signal: SignalFlags) {
 
         // modify trap ctx to jump to user handler
         // backup trap ctx
-        let trap_ctx = cur_thr_inner.get_trap_ctx();
         cur_proc_inner.trap_ctx_backup = Some(*trap_ctx);
 
         // modify trapframe
         trap_ctx.sepc = handler;
 
-        // put args (a0)
+        // put args (a0), and the fault address (a1) if the signal is raised by a fault
         trap_ctx.x[10] = sig;
+        if signal.intersects(
+            SignalFlags::SIGILL | SignalFlags::SIGTRAP | SignalFlags::SIGBUS | SignalFlags::SIGSEGV,
+        ) {
+            trap_ctx.x[11] = cur_proc_inner.fault_addr.take().unwrap_or(0);
+        }
+    } else if signal.intersects(SignalFlags::SIGTSTP | SignalFlags::SIGTTIN | SignalFlags::SIGTTOU) {
+        // default action of the terminal stop signals is to stop the process, like SIGSTOP
+        stop_if_pending(&mut cur_proc_inner);
+        drop(cur_proc_inner);
+        notify_parent(&cur_proc);
     } else {
         // default action
         // println!("[K] task/call_user_signal_handler: default action: ignore it or kill process");
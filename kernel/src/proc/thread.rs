@@ -2,9 +2,10 @@ use crate::mm::PhysPageNum;
 use crate::proc::ctx::ThreadContext;
 use crate::proc::pcb::ProcessControlBlock;
 use crate::proc::resource::{kernel_stack_alloc, KernelStackGuard, ThreadResource};
-use crate::sync::UPSafeCell;
+use crate::proc::scheduler::SchedInfo;
+use crate::proc::usage::UsageClock;
+use crate::sync::{SpinLock, SpinLockGuard};
 use alloc::sync::{Arc, Weak};
-use core::cell::RefMut;
 use crate::trap::TrapContext;
 
 #[derive(Copy, Clone, PartialEq)]
@@ -12,13 +13,17 @@ pub enum ThreadState {
     Ready,
     Running,
     Blocked,
+    /// Never runs again, its tcb is kept for the exit code
+    Exited,
 }
 
 pub struct ThreadControlBlock {
     pub pcb: Weak<ProcessControlBlock>,
     pub kernel_stack: KernelStackGuard,
 
-    inner: UPSafeCell<ThreadControlBlockInner>,
+    inner: SpinLock<ThreadControlBlockInner>,
+    /// Apart from the inner, the scheduler uses it while the inner is locked in run_threads
+    sched: SpinLock<SchedInfo>,
 }
 pub struct ThreadControlBlockInner {
     pub res: Option<ThreadResource>,
@@ -26,6 +31,10 @@ pub struct ThreadControlBlockInner {
     pub thread_ctx: ThreadContext,
     pub state: ThreadState,
     pub exit_code: Option<i32>,
+    /// A hart is running it, and its context is not saved until the hart switches it out
+    pub on_cpu: bool,
+    /// The CPU time and events of the thread, see sys_getrusage
+    pub clock: UsageClock,
 }
 
 impl ThreadControlBlockInner {
@@ -42,18 +51,25 @@ impl ThreadControlBlock {
         ThreadControlBlock {
             pcb: Arc::downgrade(&pcb),
             kernel_stack: kernel_stack_guard,
-            inner: unsafe {
-                UPSafeCell::new(ThreadControlBlockInner {
-                    res: Some(res),
-                    trap_ctx_ppn,
-                    thread_ctx: ThreadContext::new(kernel_stack_top),
-                    state: ThreadState::Ready,
-                    exit_code: None,
-                })
-            },
+            inner: SpinLock::new(ThreadControlBlockInner {
+                res: Some(res),
+                trap_ctx_ppn,
+                thread_ctx: ThreadContext::new(kernel_stack_top),
+                state: ThreadState::Ready,
+                exit_code: None,
+                on_cpu: false,
+                clock: UsageClock::new(),
+            }),
+            sched: SpinLock::new(SchedInfo::new()),
         }
     }
-    pub fn exclusive_access(&self) -> RefMut<'_, ThreadControlBlockInner> {
+    pub fn exclusive_access(&self) -> SpinLockGuard<'_, ThreadControlBlockInner> {
         self.inner.exclusive_access()
     }
+    pub fn try_access(&self) -> Option<SpinLockGuard<'_, ThreadControlBlockInner>> {
+        self.inner.try_access()
+    }
+    pub fn sched(&self) -> SpinLockGuard<'_, SchedInfo> {
+        self.sched.exclusive_access()
+    }
 }
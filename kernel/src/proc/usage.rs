@@ -0,0 +1,79 @@
+use crate::timer::{get_time, time_to_us};
+
+/// The CPU time a thread has used and what happened meanwhile.
+/// The times are durations read from mtime, converted only when reported.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Usage {
+    /// Time in user mode
+    pub utime: usize,
+    /// Time in the kernel on behalf of the thread
+    pub stime: usize,
+    /// Switches because the thread blocked, e.g. sleeping or waiting for a lock
+    pub nvcsw: usize,
+    /// Switches because the thread was preempted or yielded
+    pub nivcsw: usize,
+    /// Page faults resolved by loading, growing or copying the page
+    pub page_faults: usize,
+}
+
+/// The usage reported by sys_getrusage
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct RUsage {
+    pub utime_us: usize,
+    pub stime_us: usize,
+    pub nvcsw: usize,
+    pub nivcsw: usize,
+    pub page_faults: usize,
+}
+
+impl Usage {
+    pub fn add(&mut self, other: &Usage) {
+        self.utime += other.utime;
+        self.stime += other.stime;
+        self.nvcsw += other.nvcsw;
+        self.nivcsw += other.nivcsw;
+        self.page_faults += other.page_faults;
+    }
+    pub fn to_rusage(&self) -> RUsage {
+        RUsage {
+            utime_us: time_to_us(self.utime),
+            stime_us: time_to_us(self.stime),
+            nvcsw: self.nvcsw,
+            nivcsw: self.nivcsw,
+            page_faults: self.page_faults,
+        }
+    }
+}
+
+/// The usage of a thread, and when it was last charged
+pub struct UsageClock {
+    pub usage: Usage,
+    stamp: usize,
+}
+
+impl UsageClock {
+    pub fn new() -> Self {
+        Self {
+            usage: Usage::default(),
+            stamp: get_time(),
+        }
+    }
+    /// Charge the time since the last stamp as user time, when the thread traps into the kernel
+    pub fn charge_user(&mut self) {
+        let now = get_time();
+        self.usage.utime += now - self.stamp;
+        self.stamp = now;
+    }
+    /// Charge the time since the last stamp as system time, when the thread returns to the user
+    /// or is switched out
+    pub fn charge_system(&mut self) {
+        let now = get_time();
+        self.usage.stime += now - self.stamp;
+        self.stamp = now;
+    }
+    /// Start the clock again when the thread is switched in, the time off the CPU is not charged
+    pub fn restart(&mut self) {
+        self.stamp = get_time();
+    }
+}
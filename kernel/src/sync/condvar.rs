@@ -1,10 +1,10 @@
 use crate::proc::{block_thread, get_cur_thread, wakeup_thread, ThreadControlBlock};
-use crate::sync::{Mutex, UPSafeCell};
+use crate::sync::{Mutex, SpinLock};
 use alloc::collections::VecDeque;
 use alloc::sync::Arc;
 
 pub struct Condvar {
-    pub inner: UPSafeCell<CondvarInner>,
+    pub inner: SpinLock<CondvarInner>,
 }
 pub struct CondvarInner {
     pub wait_queue: VecDeque<Arc<ThreadControlBlock>>,
@@ -13,11 +13,9 @@ pub struct CondvarInner {
 impl Condvar{
     pub fn new() -> Self {
         Self {
-            inner: unsafe {
-                UPSafeCell::new(CondvarInner {
-                    wait_queue: VecDeque::new(),
-                })
-            }
+            inner: SpinLock::new(CondvarInner {
+                wait_queue: VecDeque::new(),
+            })
         }
     }
     pub fn signal(&self) {
@@ -27,13 +25,14 @@ impl Condvar{
         }
     }
     pub fn wait(&self, mutex: Arc<dyn Mutex>) {
-        // println!("Condvar wait, mutex will be unlocked");
-        mutex.unlock();
-        // println!("Condvar wait, mutex is unlocked now");
+        // queued before unlocking, so a signal from another hart in between is not lost
         let mut inner = self.inner.exclusive_access();
         inner.wait_queue.push_back(get_cur_thread().unwrap());
         drop(inner);
         // println!("Condvar wait, mutex will be unlocked");
+        mutex.unlock();
+        // println!("Condvar wait, mutex is unlocked now");
+        // println!("Condvar wait, mutex will be unlocked");
         block_thread();
         // println!("Condvar wait done, mutex will be locked again");
         mutex.lock();
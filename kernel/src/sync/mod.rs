@@ -1,8 +1,8 @@
-mod up;
+mod spin;
 mod mutex;
 mod condvar;
 
-pub use up::UPSafeCell;
+pub use spin::{SpinLock, SpinLockGuard};
 
 pub use mutex::{Mutex, SpinMutex, BlockedMutex};
-pub use condvar::Condvar;
\ No newline at end of file
+pub use condvar::Condvar;
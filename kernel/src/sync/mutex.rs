@@ -1,5 +1,5 @@
 use crate::proc::{block_thread, get_cur_thread, switch_thread, wakeup_thread, ThreadControlBlock};
-use crate::sync::UPSafeCell;
+use crate::sync::SpinLock;
 use alloc::collections::VecDeque;
 use alloc::sync::Arc;
 
@@ -9,12 +9,12 @@ pub trait Mutex: Sync + Send {
 }
 
 pub struct SpinMutex {
-    is_locked: UPSafeCell<bool>,
+    is_locked: SpinLock<bool>,
 }
 impl SpinMutex {
     pub fn new() -> Self {
         Self {
-            is_locked: unsafe { UPSafeCell::new(false) },
+            is_locked: SpinLock::new(false),
         }
     }
 }
@@ -42,7 +42,7 @@ impl Mutex for SpinMutex {
 }
 
 pub struct BlockedMutex {
-    inner: UPSafeCell<BlockedMutexInner>,
+    inner: SpinLock<BlockedMutexInner>,
 }
 
 pub struct BlockedMutexInner {
@@ -53,12 +53,10 @@ pub struct BlockedMutexInner {
 impl BlockedMutex {
     pub fn new() -> Self {
         Self {
-            inner: unsafe {
-                UPSafeCell::new(BlockedMutexInner {
-                    is_locked: false,
-                    wait_queue: VecDeque::new(),
-                })
-            },
+            inner: SpinLock::new(BlockedMutexInner {
+                is_locked: false,
+                wait_queue: VecDeque::new(),
+            }),
         }
     }
 }
@@ -0,0 +1,82 @@
+//! Spin locks shared by the harts
+
+use crate::proc::hart_id;
+use core::cell::UnsafeCell;
+use core::hint::spin_loop;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// No hart holds the lock
+const NO_OWNER: usize = usize::MAX;
+
+/// Wrap a data structure shared by the harts inside it. Call `exclusive_access`
+/// to spin until the other harts release it.
+///
+/// The kernel is never interrupted, so a hart taking the lock it holds already is a bug,
+/// which panics instead of spinning forever.
+pub struct SpinLock<T> {
+    locked: AtomicBool,
+    /// The hart holding the lock
+    owner: AtomicUsize,
+    inner: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for SpinLock<T> {}
+unsafe impl<T: Send> Send for SpinLock<T> {}
+
+impl<T> SpinLock<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            owner: AtomicUsize::new(NO_OWNER),
+            inner: UnsafeCell::new(value),
+        }
+    }
+    /// Spin until the lock is free, then hold it until the guard is dropped
+    pub fn exclusive_access(&self) -> SpinLockGuard<'_, T> {
+        if self.owner.load(Ordering::Relaxed) == hart_id() {
+            panic!("a lock is taken again by the hart holding it");
+        }
+        while !self.acquire() {
+            spin_loop();
+        }
+        self.guard()
+    }
+    /// Like `exclusive_access`, but None if the lock is held
+    pub fn try_access(&self) -> Option<SpinLockGuard<'_, T>> {
+        self.acquire().then(|| self.guard())
+    }
+    fn acquire(&self) -> bool {
+        self.locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+    fn guard(&self) -> SpinLockGuard<'_, T> {
+        self.owner.store(hart_id(), Ordering::Relaxed);
+        SpinLockGuard { lock: self }
+    }
+}
+
+pub struct SpinLockGuard<'a, T> {
+    lock: &'a SpinLock<T>,
+}
+
+impl<T> Deref for SpinLockGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.inner.get() }
+    }
+}
+
+impl<T> DerefMut for SpinLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.inner.get() }
+    }
+}
+
+impl<T> Drop for SpinLockGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.owner.store(NO_OWNER, Ordering::Relaxed);
+        self.lock.locked.store(false, Ordering::Release);
+    }
+}
@@ -1,291 +1,312 @@
 //! File and filesystem-related syscalls
 
-use crate::fs::kernel_file::{KernelFile, OpenFlags, CWD, ROOT};
+use crate::config::MAX_USER_STR_LEN;
+use crate::errno::{Errno, SysResult};
+use crate::fs::kernel_file::{KernelFile, OpenFlags};
+use crate::fs::path::Path;
 use crate::fs::pipe::make_pipe_pair;
-use crate::fs::File;
-use crate::mm::VirtAddr;
-use crate::print;
+use crate::fs::{File, Stat};
 use crate::proc::get_cur_proc;
-use acore_fs::Inode;
-use alloc::string::String;
-use alloc::sync::Arc;
+use acore_fs::DiskInodeType;
 use alloc::vec::Vec;
+use core::mem::size_of;
 
 /// write buf of length `len`  to a file with `fd`
-pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
+pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> SysResult {
     let cur_proc = get_cur_proc();
-    let inner = cur_proc.exclusive_access();
-    let vec = inner.mm.read((buf as usize).into(), len);
+    let mut inner = cur_proc.exclusive_access();
+    let vec = inner.mm.copy_in(buf as usize, len)?;
     if let Some(file) = inner.get_file(fd) {
         if !file.writable() {
-            -1
+            Err(Errno::EBADF)
         } else {
             let file = file.clone();
             drop(inner);
-            file.write(vec.as_slice()) as isize
+            Ok(file.write(vec.as_slice()) as isize)
         }
     } else {
-        -1
+        Err(Errno::EBADF)
     }
 }
-pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> isize {
+pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> SysResult {
     let cur_proc = get_cur_proc();
-    let inner = cur_proc.exclusive_access();
+    let mut inner = cur_proc.exclusive_access();
     if let Some(file) = inner.get_file(fd) {
         if !file.readable() {
-            -1
+            Err(Errno::EBADF)
         } else {
+            // fail before consuming any data
+            inner.mm.check_user(buf as usize, len, true)?;
             let mut vec = Vec::new();
             vec.resize(len, 0);
             let file = file.clone();
             drop(inner);
             let ret = file.read(vec.as_mut_slice());
-            let inner = cur_proc.exclusive_access();
-            inner.mm.write((buf as usize).into(), vec.as_slice());
-            ret as isize
+            let mut inner = cur_proc.exclusive_access();
+            inner.mm.copy_out(buf as usize, &vec[..ret])?;
+            Ok(ret as isize)
         }
     } else {
-        -1
+        Err(Errno::EBADF)
     }
 }
 
-pub fn sys_open(path: *const u8, flags: u32) -> isize {
+/// Read entries of directory `fd` into buf, see `Dirent` for the format
+pub fn sys_getdents(fd: usize, buf: *mut u8, len: usize) -> SysResult {
     let cur_proc = get_cur_proc();
     let mut inner = cur_proc.exclusive_access();
-    let path = inner.mm.read_str(VirtAddr::from(path as usize));
-    // println!("sys_open: path = {}, flags = {}", path, flags);
-    if let Some(file) = KernelFile::from_path(path.as_str(), OpenFlags::from_bits(flags).unwrap()) {
-        inner.fd_table.insert_file(file)
+    if let Some(file) = inner.get_file(fd) {
+        inner.mm.check_user(buf as usize, len, true)?;
+        let mut vec = Vec::new();
+        vec.resize(len, 0);
+        let file = file.clone();
+        drop(inner);
+        let ret = file.getdents(vec.as_mut_slice())?;
+        if ret > 0 {
+            let mut inner = cur_proc.exclusive_access();
+            inner.mm.copy_out(buf as usize, &vec[..ret])?;
+        }
+        Ok(ret as isize)
     } else {
-        -1
+        Err(Errno::EBADF)
     }
 }
 
-pub fn sys_close(fd: usize) -> isize {
+/// Reposition the offset of file `fd`, return the new offset
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> SysResult {
+    let cur_proc = get_cur_proc();
+    let inner = cur_proc.exclusive_access();
+    if let Some(file) = inner.get_file(fd) {
+        let file = file.clone();
+        drop(inner);
+        Ok(file.seek(offset, whence)? as isize)
+    } else {
+        Err(Errno::EBADF)
+    }
+}
+
+pub fn sys_open(path: *const u8, flags: u32) -> SysResult {
+    let cur_proc = get_cur_proc();
+    let mut inner = cur_proc.exclusive_access();
+    let path = inner.mm.copy_in_str(path as usize, MAX_USER_STR_LEN)?;
+    // println!("sys_open: path = {}, flags = {}", path, flags);
+    let flags = OpenFlags::from_bits(flags).ok_or(Errno::EINVAL)?;
+    let file = KernelFile::from_path(inner.cwd.as_str(), path.as_str(), flags)?;
+    Ok(inner.fd_table.insert_file(file))
+}
+
+pub fn sys_close(fd: usize) -> SysResult {
     let cur_proc = get_cur_proc();
     let mut inner = cur_proc.exclusive_access();
-    inner.fd_table.dealloc_fd(fd)
+    inner.fd_table.dealloc_fd(fd)?;
+    Ok(0)
 }
 
-pub fn sys_pipe(pipe: *mut usize) -> isize {
+pub fn sys_pipe(pipe: *mut usize) -> SysResult {
     let cur_proc = get_cur_proc();
     let mut inner = cur_proc.exclusive_access();
+    inner.mm.check_user(pipe as usize, size_of::<[usize; 2]>(), true)?;
     let (pipe_read, pipe_write) = make_pipe_pair();
     let read_fd = inner.fd_table.insert_file(pipe_read);
     let write_fd = inner.fd_table.insert_file(pipe_write);
 
     // write back the file descriptors to the pipe ptr
-    let data = &[read_fd as usize, write_fd as usize];
-    let byte_len = data.len() * core::mem::size_of::<usize>();
-    inner.mm.write(VirtAddr::from(pipe as usize), unsafe {
-        core::slice::from_raw_parts(data.as_ptr() as *const u8, byte_len)
-    });
-    0
+    let data = [read_fd as usize, write_fd as usize];
+    inner.mm.copy_out_val(pipe as usize, &data)?;
+    Ok(0)
 }
 
-pub fn sys_dup(fd: usize) -> isize {
+pub fn sys_dup(fd: usize) -> SysResult {
     let cur_proc = get_cur_proc();
     let mut inner = cur_proc.exclusive_access();
     if let Some(file) = inner.get_file(fd) {
-        inner.fd_table.insert_file(file.clone())
+        Ok(inner.fd_table.insert_file(file.clone()))
     } else {
-        -1
+        Err(Errno::EBADF)
     }
 }
 
-pub fn sys_fstat(fd: usize) -> isize {
+/// Make `new_fd` refer to the file of `old_fd`, closing the file at `new_fd` first
+pub fn sys_dup2(old_fd: usize, new_fd: usize) -> SysResult {
     let cur_proc = get_cur_proc();
-    let inner = cur_proc.exclusive_access();
-    if let Some(file) = inner.get_file(fd) {
-        print!("{}", file.stat());
-        0
-    } else {
-        -1
+    let mut inner = cur_proc.exclusive_access();
+    let file = inner.get_file(old_fd).ok_or(Errno::EBADF)?;
+    if (new_fd as isize) < 0 {
+        return Err(Errno::EBADF);
     }
+    if new_fd != old_fd {
+        inner.fd_table.insert_file_at(new_fd, file);
+    }
+    Ok(new_fd as isize)
 }
 
-/// Remove redundant `.` and `..` in the path
-pub fn simplify_path(path: &str) -> String {
-    let parts: Vec<&str> = path.split('/').collect();
-    let mut simplified: Vec<&str> = Vec::new();
-
-    for part in parts {
-        if part == "." {
-            continue;
-        } else if part == ".." {
-            if simplified.len() > 1 {
-                simplified.pop();
-            }
-        } else {
-            simplified.push(part);
-        }
+/// Write the status of file `fd` to `st`
+pub fn sys_fstat(fd: usize, st: *mut Stat) -> SysResult {
+    let cur_proc = get_cur_proc();
+    let mut inner = cur_proc.exclusive_access();
+    if let Some(file) = inner.get_file(fd) {
+        let stat = file.stat();
+        inner.mm.copy_out_val(st as usize, &stat)?;
+        Ok(0)
+    } else {
+        Err(Errno::EBADF)
     }
-    simplified.join("/")
 }
 
-/// Change current pwd. path should be an absolute path which points to a directory.
-pub fn sys_cd(path: *const u8) -> isize {
+/// Change the pwd of current process. path should point to a directory.
+pub fn sys_cd(path: *const u8) -> SysResult {
     // read the path from the user space
     let cur_proc = get_cur_proc();
-    let inner = cur_proc.exclusive_access();
-    let path = inner.mm.read_str(VirtAddr::from(path as usize));
-
-    if path.chars().last() != Some('/') {
-        return -1;
-    }
+    let mut inner = cur_proc.exclusive_access();
+    let path = inner.mm.copy_in_str(path as usize, MAX_USER_STR_LEN)?;
 
+    let path = Path::new(inner.cwd.as_str(), path.as_str()).ok_or(Errno::ENOENT)?;
     // check if the dir exists
-    if let Some(_) = KernelFile::from_path(path.as_str(), OpenFlags::RDONLY) {
-        // change the current working directory
-        let mut cwd = CWD.exclusive_access();
-        *cwd = simplify_path(&path);
-        0
-    } else {
-        // directory does not exist
-        -1
+    let inode = path.inode().ok_or(Errno::ENOENT)?;
+    if !inode.is_dir() {
+        return Err(Errno::ENOTDIR);
     }
+    inner.cwd = path.to_string(true);
+    Ok(0)
 }
 
 /// Get current working directory, which is a string. The method is similar to sys_read
-pub fn sys_getcwd(buf: *const u8, len: usize) -> isize {
-    let pwd = CWD.exclusive_access();
-    let pwd_str = pwd.as_str();
+pub fn sys_getcwd(buf: *const u8, len: usize) -> SysResult {
+    let cur_proc = get_cur_proc();
+    let mut inner = cur_proc.exclusive_access();
+    let pwd_str = inner.cwd.as_str();
     if pwd_str.len() < len {
-        let cur_proc = get_cur_proc();
-        let inner = cur_proc.exclusive_access();
         let mut vec = Vec::new();
         vec.extend_from_slice(pwd_str.as_bytes());
-        inner.mm.write(VirtAddr::from(buf as usize), vec.as_slice());
-        vec.len() as isize
+        inner.mm.copy_out(buf as usize, vec.as_slice())?;
+        Ok(vec.len() as isize)
     } else {
         // buffer is not large enough
-        -1
+        Err(Errno::ERANGE)
     }
 }
 
-pub fn sys_cp(src: *const u8, dst: *const u8) -> isize {
+pub fn sys_cp(src: *const u8, dst: *const u8) -> SysResult {
     let cur_proc = get_cur_proc();
-    let inner = cur_proc.exclusive_access();
+    let mut inner = cur_proc.exclusive_access();
 
-    let src_path = inner.mm.read_str(VirtAddr::from(src as usize));
-    let dst_path = inner.mm.read_str(VirtAddr::from(dst as usize));
+    let src_path = inner.mm.copy_in_str(src as usize, MAX_USER_STR_LEN)?;
+    let dst_path = inner.mm.copy_in_str(dst as usize, MAX_USER_STR_LEN)?;
+    let cwd = inner.cwd.clone();
+    drop(inner);
 
-    if src_path == dst_path {
+    if Path::new(&cwd, &src_path) == Path::new(&cwd, &dst_path) {
         // print!("cp: cannot copy '{}' to itself\n", src_path);
-        return -1;
+        return Err(Errno::EINVAL);
     }
 
-    if let Some(src_file) = KernelFile::from_path(src_path.as_str(), OpenFlags::RDONLY) {
-        if let Some(dst_file) =
-            KernelFile::from_path(dst_path.as_str(), OpenFlags::CREATE | OpenFlags::WRONLY)
-        {
-            let data = src_file.read_all();
-            let write_size = dst_file.write(data.as_slice());
-            if write_size == data.len() {
-                // Successfully copied
-                0
-            } else {
-                // print!("Error occurred when writing to destination file\n");
-                -1
-            }
-        } else {
-            // print!("Error occurred when opening destination file\n");
-            -1
-        }
+    let src_file = KernelFile::from_path(&cwd, src_path.as_str(), OpenFlags::RDONLY)?;
+    let dst_file = KernelFile::from_path(
+        &cwd,
+        dst_path.as_str(),
+        OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC,
+    )?;
+    let data = src_file.read_all();
+    let write_size = dst_file.write(data.as_slice());
+    if write_size == data.len() {
+        // Successfully copied
+        Ok(0)
     } else {
-        // print!("Error occurred when opening source file\n");
-        -1
+        // print!("Error occurred when writing to destination file\n");
+        Err(Errno::EIO)
     }
 }
 
-pub fn get_dir_inode(path: Vec<&str>) -> Option<Arc<Inode>> {
-    let mut inode = ROOT.clone();
-    for dir_entry in path.iter() {
-        inode = inode.access_dir_entry(dir_entry, acore_fs::DiskInodeType::Directory, false)?;
-    }
-    Some(inode)
-}
-
-/// Only move the dir entry
-pub fn sys_mv(src: *const u8, dst: *const u8) -> isize {
+/// Only move the dir entry. A dst ending with '/' is the directory to move into.
+pub fn sys_mv(src: *const u8, dst: *const u8) -> SysResult {
     let cur_proc = get_cur_proc();
-    let inner = cur_proc.exclusive_access();
+    let mut inner = cur_proc.exclusive_access();
 
-    let src_path = inner.mm.read_str(VirtAddr::from(src as usize));
-    let dst_path = inner.mm.read_str(VirtAddr::from(dst as usize));
+    let src_path = inner.mm.copy_in_str(src as usize, MAX_USER_STR_LEN)?;
+    let dst_path = inner.mm.copy_in_str(dst as usize, MAX_USER_STR_LEN)?;
+    let (src, dst) = match (
+        Path::new(inner.cwd.as_str(), src_path.as_str()),
+        Path::new(inner.cwd.as_str(), dst_path.as_str()),
+    ) {
+        (Some(src), Some(dst)) => (src, dst),
+        _ => return Err(Errno::ENOENT),
+    };
+    drop(inner);
 
-    if src_path == dst_path {
-        // print!("mv: cannot move '{}' to itself\n", src_path);
-        return -1;
-    }
-    let mut src_path = src_path.split('/').skip(1).collect::<Vec<_>>();
-    let mut dst_path = dst_path.split('/').skip(1).collect::<Vec<_>>();
-    let src_file_name = src_path.pop().unwrap();
-    if src_file_name.is_empty() {
+    let src_file_name = match src.name() {
+        Some(name) if !src.is_dir => name,
         // print!("mv: cannot move directory\n");
-        return -1;
-    }
-    let dst_file_name = {
-        let s = dst_path.pop().unwrap();
-        if s.is_empty() {
-            src_file_name
-        } else {
-            s
-        }
+        _ => return Err(Errno::EISDIR),
+    };
+    let dst = if dst.is_dir {
+        Path::new(dst.to_string(true).as_str(), src_file_name).ok_or(Errno::ENOENT)?
+    } else {
+        dst
     };
+    if src == dst {
+        // print!("mv: cannot move '{}' to itself\n", src_path);
+        return Err(Errno::EINVAL);
+    }
 
-    if let Some(src_dir) = get_dir_inode(src_path) {
-        if let Some(dst_dir) = get_dir_inode(dst_path) {
-            if let Some(dst_inode) =
-                dst_dir.access_dir_entry(dst_file_name, acore_fs::DiskInodeType::File, false)
-            {
-                // dst_file still exists, remove it first
-                dst_inode.clear();
-                dst_dir.remove_dir_entry(dst_file_name);
-            }
-            // Move the file by changing its directory entry
-            if let Some(inode_id) = src_dir.remove_dir_entry(src_file_name) {
-                let mut fs = src_dir.fs.lock();
-                dst_dir.insert_dir_entry(dst_file_name, inode_id, &mut fs);
-            }
-            0
-        } else {
-            // print!("Error occurred when accessing destination directory\n");
-            -1
+    let (src_dir, dst_dir, dst_file_name) =
+        match (src.parent_inode(), dst.parent_inode(), dst.name()) {
+            (Some(src_dir), Some(dst_dir), Some(name)) => (src_dir, dst_dir, name),
+            // print!("Error occurred when accessing directories\n");
+            _ => return Err(Errno::ENOENT),
+        };
+    if src_dir
+        .access_dir_entry(src_file_name, DiskInodeType::File, false)
+        .is_none()
+    {
+        // nothing to move, keep dst_file untouched
+        return Err(Errno::ENOENT);
+    }
+    if let Some(dst_inode) = dst_dir.access_dir_entry(dst_file_name, DiskInodeType::File, false) {
+        // dst_file still exists, remove it first
+        if dst_inode.is_dir() {
+            return Err(Errno::EISDIR);
         }
-    } else {
-        // print!("Error occurred when accessing source directory\n");
-        -1
+        dst_dir.remove_dir_entry(dst_file_name);
+        dst_inode.reclaim();
+    }
+    // Move the file by changing its directory entry
+    if let Some(inode_id) = src_dir.remove_dir_entry(src_file_name) {
+        let mut fs = src_dir.fs.lock();
+        dst_dir.insert_dir_entry(dst_file_name, inode_id, &mut fs);
     }
+    Ok(0)
 }
 
 /// If is a dir, only remove when it is empty.
-pub fn sys_rm(path: *const u8) -> isize {
+pub fn sys_rm(path: *const u8) -> SysResult {
     let cur_proc = get_cur_proc();
-    let inner = cur_proc.exclusive_access();
-    let path = inner.mm.read_str(VirtAddr::from(path as usize));
-    let mut path = path.split('/').skip(1).collect::<Vec<_>>();
+    let mut inner = cur_proc.exclusive_access();
+    let path_str = inner.mm.copy_in_str(path as usize, MAX_USER_STR_LEN)?;
+    let path = Path::new(inner.cwd.as_str(), path_str.as_str());
+    drop(inner);
 
-    let mut file_name = path.pop().unwrap();
-    if file_name.is_empty() && !path.is_empty() {
-        file_name = path.pop().unwrap();
+    // `.` and `..` are still linked by their parents
+    if matches!(
+        path_str.trim_end_matches('/').rsplit('/').next(),
+        Some("." | "..")
+    ) {
+        return Err(Errno::EINVAL);
     }
-    if let Some(dir) = get_dir_inode(path) {
-        if let Some(inode) =
-            dir.access_dir_entry(file_name, acore_fs::DiskInodeType::File, false)
-        {
-            if inode.can_clear() {
-                inode.clear();
-                dir.remove_dir_entry(file_name);
-                0
-            } else {
-                -1
-            }
-        } else {
-            -1
-        }
+    let path = path.ok_or(Errno::ENOENT)?;
+    // the root has no name and is never removed
+    let file_name = path.name().ok_or(Errno::EBUSY)?;
+    let dir = path.parent_inode().ok_or(Errno::ENOENT)?;
+    let inode = dir
+        .access_dir_entry(file_name, DiskInodeType::File, false)
+        .ok_or(Errno::ENOENT)?;
+    if path.is_dir && !inode.is_dir() {
+        return Err(Errno::ENOTDIR);
+    }
+    if inode.can_clear() {
+        dir.remove_dir_entry(file_name);
+        inode.reclaim();
+        Ok(0)
     } else {
-        -1
+        Err(Errno::ENOTEMPTY)
     }
-
 }
@@ -0,0 +1,150 @@
+//! Memory-related syscalls
+
+use crate::config::PAGE_SIZE;
+use crate::errno::{Errno, SysResult};
+use crate::mm::{MapPerm, RLimit};
+use crate::proc::get_cur_proc;
+use alloc::vec;
+use bitflags::bitflags;
+use core::cmp::min;
+
+bitflags! {
+    /// prot of sys_mmap and sys_mprotect, the same values as Linux
+    pub struct MmapProt: u32 {
+        const READ = 1 << 0;
+        const WRITE = 1 << 1;
+        const EXEC = 1 << 2;
+    }
+}
+
+bitflags! {
+    /// flags of sys_mmap, the same values as Linux
+    pub struct MmapFlags: u32 {
+        const SHARED = 1 << 0;
+        const PRIVATE = 1 << 1;
+        const ANONYMOUS = 1 << 5;
+    }
+}
+
+/// resource of sys_getrlimit and sys_setrlimit, only the stack size is limited
+const RLIMIT_STACK: usize = 3;
+
+impl MmapProt {
+    /// A page can't be writable but not readable, and PROT_NONE is not supported
+    fn map_perm(&self) -> Result<MapPerm, Errno> {
+        let mut map_perm = MapPerm::U;
+        if self.intersects(Self::READ | Self::WRITE) {
+            map_perm |= MapPerm::R;
+        }
+        if self.contains(Self::WRITE) {
+            map_perm |= MapPerm::W;
+        }
+        if self.contains(Self::EXEC) {
+            map_perm |= MapPerm::X;
+        }
+        if map_perm == MapPerm::U {
+            return Err(Errno::EINVAL);
+        }
+        Ok(map_perm)
+    }
+}
+
+/// Set the program break to `addr` and return the new one. `addr == 0` only queries it.
+pub fn sys_brk(addr: usize) -> SysResult {
+    let cur_proc = get_cur_proc();
+    let mut inner = cur_proc.exclusive_access();
+    if addr != 0 {
+        inner.mm.set_brk(addr)?;
+    }
+    Ok(inner.mm.brk as isize)
+}
+
+/// Map `len` bytes of zeros, or of the file `fd` from `offset`, return the start address.
+/// Only private mappings are supported, so writes never reach the file.
+pub fn sys_mmap(
+    addr: usize,
+    len: usize,
+    prot: usize,
+    flags: usize,
+    fd: usize,
+    offset: usize,
+) -> SysResult {
+    let map_perm = MmapProt::from_bits(prot as u32)
+        .ok_or(Errno::EINVAL)?
+        .map_perm()?;
+    let flags = MmapFlags::from_bits(flags as u32).ok_or(Errno::EINVAL)?;
+    if flags.contains(MmapFlags::SHARED) || !flags.contains(MmapFlags::PRIVATE) {
+        return Err(Errno::EINVAL);
+    }
+    let cur_proc = get_cur_proc();
+    let data = if flags.contains(MmapFlags::ANONYMOUS) {
+        None
+    } else {
+        if offset % PAGE_SIZE != 0 {
+            return Err(Errno::EINVAL);
+        }
+        let inner = cur_proc.exclusive_access();
+        let file = inner.get_file(fd).ok_or(Errno::EBADF)?.clone();
+        drop(inner);
+        if !file.readable() {
+            return Err(Errno::EACCES);
+        }
+        // the part beyond the end of the file is left zero
+        let file_size = file.stat().size as usize;
+        let mut data = vec![0u8; min(len, file_size.saturating_sub(offset))];
+        let read_size = file.read_at(offset, &mut data)?;
+        data.truncate(read_size);
+        Some(data)
+    };
+    let mut inner = cur_proc.exclusive_access();
+    let start = inner.mm.mmap(addr, len, map_perm, data.as_deref())?;
+    Ok(start as isize)
+}
+
+pub fn sys_munmap(addr: usize, len: usize) -> SysResult {
+    let cur_proc = get_cur_proc();
+    let mut inner = cur_proc.exclusive_access();
+    inner.mm.munmap(addr, len)?;
+    Ok(0)
+}
+
+pub fn sys_mprotect(addr: usize, len: usize, prot: usize) -> SysResult {
+    let map_perm = MmapProt::from_bits(prot as u32)
+        .ok_or(Errno::EINVAL)?
+        .map_perm()?;
+    let cur_proc = get_cur_proc();
+    let mut inner = cur_proc.exclusive_access();
+    inner.mm.mprotect(addr, len, map_perm)?;
+    Ok(0)
+}
+
+/// Write the limit of `resource` to `rlim`
+pub fn sys_getrlimit(resource: usize, rlim: *mut RLimit) -> SysResult {
+    if resource != RLIMIT_STACK {
+        return Err(Errno::EINVAL);
+    }
+    let cur_proc = get_cur_proc();
+    let mut inner = cur_proc.exclusive_access();
+    let stack_limit = inner.mm.stack_limit;
+    inner.mm.copy_out_val(rlim as usize, &stack_limit)?;
+    Ok(0)
+}
+
+/// Set the limit of `resource` to `rlim`. The hard limit can be lowered but not raised.
+/// The new stack limit applies to the pages the stacks grow into from now on.
+pub fn sys_setrlimit(resource: usize, rlim: *const RLimit) -> SysResult {
+    if resource != RLIMIT_STACK {
+        return Err(Errno::EINVAL);
+    }
+    let cur_proc = get_cur_proc();
+    let mut inner = cur_proc.exclusive_access();
+    let stack_limit: RLimit = inner.mm.copy_in_val(rlim as usize)?;
+    if stack_limit.cur > stack_limit.max {
+        return Err(Errno::EINVAL);
+    }
+    if stack_limit.max > inner.mm.stack_limit.max {
+        return Err(Errno::EPERM);
+    }
+    inner.mm.stack_limit = stack_limit;
+    Ok(0)
+}
@@ -1,18 +1,27 @@
 mod fs;
+mod mm;
 mod proc;
 mod thread;
 mod sync;
 
-use crate::proc::SignalAction;
+use crate::config::SIGSYS_ON_UNKNOWN_SYSCALL;
+use crate::errno::{Errno, SysResult};
+use crate::fs::Stat;
+use crate::mm::RLimit;
+use crate::proc::{current_add_signal, get_cur_proc, RUsage, SignalAction, SignalFlags};
 use crate::syscall::sync::{sys_condvar_create, sys_condvar_signal, sys_condvar_wait, sys_mutex_create, sys_mutex_lock, sys_mutex_unlock, sys_sleep};
 use crate::syscall::thread::{sys_gettid, sys_thread_create, sys_waittid};
 use fs::*;
+use log::warn;
+use mm::*;
 use proc::*;
 
 const SYSCALL_DUP: usize = 24;
 const SYSCALL_OPEN: usize = 56;
 const SYSCALL_CLOSE: usize = 57;
 const SYSCALL_PIPE: usize = 59;
+const SYSCALL_GETDENTS: usize = 61;
+const SYSCALL_LSEEK: usize = 62;
 const SYSCALL_READ: usize = 63;
 const SYSCALL_WRITE: usize = 64;
 const SYSCALL_EXIT: usize = 93;
@@ -22,10 +31,21 @@ const SYSCALL_KILL: usize = 129;
 const SYSCALL_SIGACTION: usize = 134;
 const SYSCALL_SIGPROCMASK: usize = 135;
 const SYSCALL_SIGRETURN: usize = 139;
+const SYSCALL_SETPRIORITY: usize = 140;
+const SYSCALL_GETPRIORITY: usize = 141;
+const SYSCALL_SETPGID: usize = 154;
+const SYSCALL_GETPGID: usize = 155;
+const SYSCALL_GETRLIMIT: usize = 163;
+const SYSCALL_SETRLIMIT: usize = 164;
+const SYSCALL_GETRUSAGE: usize = 165;
 const SYSCALL_GET_TIME: usize = 169;
 const SYSCALL_GETPID: usize = 172;
+const SYSCALL_BRK: usize = 214;
+const SYSCALL_MUNMAP: usize = 215;
 const SYSCALL_FORK: usize = 220;
 const SYSCALL_EXEC: usize = 221;
+const SYSCALL_MMAP: usize = 222;
+const SYSCALL_MPROTECT: usize = 226;
 const SYSCALL_WAITPID: usize = 260;
 const SYSCALL_THREAD_CREATE: usize = 1000;
 const SYSCALL_GETTID: usize = 1001;
@@ -43,15 +63,21 @@ const SYSCALL_GETCWD: usize = 2002;
 const SYSCALL_CP: usize = 2003;
 const SYSCALL_MV: usize = 2004;
 const SYSCALL_RM: usize = 2005;
+const SYSCALL_DUP2: usize = 2006;
+const SYSCALL_WAIT4: usize = 2007;
+const SYSCALL_TCSETPGRP: usize = 2008;
 const SYSCALL_SHUTDOWN: usize = 9999;
 
-pub fn syscall(syscall_id: usize, args: [usize; 3]) -> isize {
+/// Dispatch the syscall, an error is returned to the user as `-errno`
+pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
     // println!("[kernel] syscall: pid = {}, id = {}, args = {:?}", sys_getpid(), syscall_id, args);
-    match syscall_id {
+    let ret = match syscall_id {
         SYSCALL_DUP => sys_dup(args[0]),
         SYSCALL_OPEN => sys_open(args[0] as *const u8, args[1] as u32),
         SYSCALL_CLOSE => sys_close(args[0]),
         SYSCALL_PIPE => sys_pipe(args[0] as *mut usize),
+        SYSCALL_GETDENTS => sys_getdents(args[0], args[1] as *mut u8, args[2]),
+        SYSCALL_LSEEK => sys_lseek(args[0], args[1] as isize, args[2]),
         SYSCALL_READ => sys_read(args[0], args[1] as *const u8, args[2]),
         SYSCALL_WRITE => sys_write(args[0], args[1] as *const u8, args[2]),
         SYSCALL_EXIT => sys_exit(args[0] as i32),
@@ -65,27 +91,63 @@ pub fn syscall(syscall_id: usize, args: [usize; 3]) -> isize {
         ),
         SYSCALL_SIGPROCMASK => sys_sigprocmask(args[0] as u32),
         SYSCALL_SIGRETURN => sys_sigreturn(),
+        SYSCALL_SETPRIORITY => sys_setpriority(args[0], args[1], args[2] as isize),
+        SYSCALL_GETPRIORITY => sys_getpriority(args[0], args[1]),
+        SYSCALL_SETPGID => sys_setpgid(args[0], args[1]),
+        SYSCALL_GETPGID => sys_getpgid(args[0]),
+        SYSCALL_GETRLIMIT => sys_getrlimit(args[0], args[1] as *mut RLimit),
+        SYSCALL_SETRLIMIT => sys_setrlimit(args[0], args[1] as *const RLimit),
+        SYSCALL_GETRUSAGE => sys_getrusage(args[0] as isize, args[1] as *mut RUsage),
         SYSCALL_GET_TIME => sys_get_time(),
         SYSCALL_GETPID => sys_getpid(),
+        SYSCALL_BRK => sys_brk(args[0]),
+        SYSCALL_MUNMAP => sys_munmap(args[0], args[1]),
+        SYSCALL_MMAP => sys_mmap(args[0], args[1], args[2], args[3], args[4], args[5]),
+        SYSCALL_MPROTECT => sys_mprotect(args[0], args[1], args[2]),
         SYSCALL_FORK => sys_fork(),
         SYSCALL_EXEC => sys_exec(args[0] as *const u8, args[1] as *const usize),
-        SYSCALL_WAITPID => sys_waitpid(args[0] as isize, args[1] as *mut i32),
+        SYSCALL_WAITPID => sys_waitpid(args[0] as isize, args[1] as *mut i32, args[2]),
         SYSCALL_THREAD_CREATE => sys_thread_create(args[0], args[1]),
         SYSCALL_GETTID => sys_gettid(),
-        SYSCALL_WAITTID => sys_waittid(args[0]) as isize,
+        SYSCALL_WAITTID => sys_waittid(args[0]),
         SYSCALL_MUTEX_CREATE => sys_mutex_create(args[0] == 1),
         SYSCALL_MUTEX_LOCK => sys_mutex_lock(args[0]),
         SYSCALL_MUTEX_UNLOCK => sys_mutex_unlock(args[0]),
         SYSCALL_CONDVAR_CREATE => sys_condvar_create(),
         SYSCALL_CONDVAR_SIGNAL => sys_condvar_signal(args[0]),
         SYSCALL_CONDVAR_WAIT => sys_condvar_wait(args[0], args[1]),
-        SYSCALL_FSTAT => sys_fstat(args[0]),
+        SYSCALL_FSTAT => sys_fstat(args[0], args[1] as *mut Stat),
         SYSCALL_CD => sys_cd(args[0] as *const u8),
         SYSCALL_GETCWD => sys_getcwd(args[0] as *mut u8, args[1]),
         SYSCALL_CP => sys_cp(args[0] as *const u8, args[1] as *const u8),
         SYSCALL_MV => sys_mv(args[0] as *const u8, args[1] as *const u8),
         SYSCALL_RM => sys_rm(args[0] as *const u8),
+        SYSCALL_DUP2 => sys_dup2(args[0], args[1]),
+        SYSCALL_WAIT4 => sys_wait4(
+            args[0] as isize,
+            args[1] as *mut i32,
+            args[2],
+            args[3] as *mut RUsage,
+        ),
+        SYSCALL_TCSETPGRP => sys_tcsetpgrp(args[0] as isize),
         SYSCALL_SHUTDOWN => sys_shutdown(),
-        _ => panic!("Unsupported syscall_id: {}", syscall_id),
+        _ => sys_unknown(syscall_id),
+    };
+    match ret {
+        Ok(ret) => ret,
+        Err(errno) => -(errno as isize),
+    }
+}
+
+/// A bad syscall id only fails the caller, instead of the whole kernel
+fn sys_unknown(syscall_id: usize) -> SysResult {
+    warn!(
+        "[kernel] Unsupported syscall_id: {}, pid = {}",
+        syscall_id,
+        get_cur_proc().getpid()
+    );
+    if SIGSYS_ON_UNKNOWN_SYSCALL {
+        current_add_signal(SignalFlags::SIGSYS);
     }
+    Err(Errno::ENOSYS)
 }
@@ -1,15 +1,20 @@
+use crate::config::MAX_USER_STR_LEN;
 use crate::console::shutdown;
+use crate::console::stdin::{set_foreground, NO_FOREGROUND};
+use crate::errno::{Errno, SysResult};
 use crate::fs::kernel_file::{KernelFile, OpenFlags};
-use crate::mm::VirtAddr;
+use crate::mm::MemoryManager;
 use crate::proc::{
-    exit_thread, get_cur_proc, get_cur_thread, pid2pcb, switch_thread,
-    SignalAction, SignalFlags, MAX_SIG,
+    block_thread, check_interrupt, exit_thread, get_cur_proc, get_cur_thread, pid2pcb, send_signal,
+    switch_thread, ProcessControlBlock, RUsage, SignalAction, SignalFlags, Usage, MAX_SIG,
+    NICE_MAX, NICE_MIN,
 };
 use crate::timer::get_time_ms;
 use crate::trap::TrapContext;
 use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
+use core::mem::size_of;
 
 pub fn sys_exit(exit_code: i32) -> ! {
     // println!("[kernel] sys_exit: pid: {}", sys_getpid());
@@ -17,20 +22,76 @@ pub fn sys_exit(exit_code: i32) -> ! {
     panic!("Unreachable in sys_exit!");
 }
 
-pub fn sys_yield() -> isize {
+pub fn sys_yield() -> SysResult {
     // println!("[kernel] sys_yield: pid: {}", sys_getpid());
     switch_thread();
     // println!("[kernel] back from switch: pid: {}", sys_getpid());
-    0
+    Ok(0)
 }
 
-pub fn sys_get_time() -> isize {
-    get_time_ms() as isize
+pub fn sys_get_time() -> SysResult {
+    Ok(get_time_ms() as isize)
 }
-pub fn sys_getpid() -> isize {
-    get_cur_proc().pid.0 as isize
+pub fn sys_getpid() -> SysResult {
+    Ok(get_cur_proc().pid.0 as isize)
 }
-pub fn sys_fork() -> isize {
+/// which of sys_setpriority and sys_getpriority, only processes are supported
+const PRIO_PROCESS: usize = 0;
+
+/// The process `who` of sys_setpriority and sys_getpriority, 0 for the current one
+fn prio_proc(which: usize, who: usize) -> Result<Arc<ProcessControlBlock>, Errno> {
+    if which != PRIO_PROCESS {
+        return Err(Errno::EINVAL);
+    }
+    if who == 0 {
+        Ok(get_cur_proc())
+    } else {
+        pid2pcb(who).ok_or(Errno::ESRCH)
+    }
+}
+
+/// Set the nice value of the threads of a process, clamped to [-20, 19].
+/// The CPU time of a thread is in proportion to the weight of its nice value, see Scheduler.
+pub fn sys_setpriority(which: usize, who: usize, nice: isize) -> SysResult {
+    let proc = prio_proc(which, who)?;
+    let nice = nice.clamp(NICE_MIN as isize, NICE_MAX as isize) as i32;
+    for thread in proc.exclusive_access().threads.iter().flatten() {
+        thread.sched().nice = nice;
+    }
+    Ok(0)
+}
+
+/// Return 20 - nice of a process like Linux, so it's never negative
+pub fn sys_getpriority(which: usize, who: usize) -> SysResult {
+    let proc = prio_proc(which, who)?;
+    let inner = proc.exclusive_access();
+    let thread = inner.threads.iter().flatten().next().ok_or(Errno::ESRCH)?;
+    let nice = thread.sched().nice;
+    Ok((20 - nice) as isize)
+}
+/// who of sys_getrusage
+const RUSAGE_SELF: isize = 0;
+const RUSAGE_CHILDREN: isize = -1;
+const RUSAGE_THREAD: isize = 1;
+
+/// Write the usage of the current process, its reaped children or the current thread to `usage`
+pub fn sys_getrusage(who: isize, usage: *mut RUsage) -> SysResult {
+    let thread = get_cur_thread().unwrap();
+    // charge the time of this syscall so far
+    thread.exclusive_access().clock.charge_system();
+    let proc = thread.pcb.upgrade().unwrap();
+    let mut inner = proc.exclusive_access();
+    let rusage = match who {
+        RUSAGE_SELF => inner.usage(),
+        RUSAGE_CHILDREN => inner.children_usage,
+        RUSAGE_THREAD => thread.exclusive_access().clock.usage,
+        _ => return Err(Errno::EINVAL),
+    }
+    .to_rusage();
+    inner.mm.copy_out_val(usage as usize, &rusage)?;
+    Ok(0)
+}
+pub fn sys_fork() -> SysResult {
     // println!("[kernel] sys_fork: pid: {}", sys_getpid());
     let cur_proc = get_cur_proc();
     let new_proc = cur_proc.fork();
@@ -41,123 +102,235 @@ pub fn sys_fork() -> isize {
     let trap_ctx: &mut TrapContext = thr_inner.get_trap_ctx();
     // for child process, fork returns 0. modify x[10] manually.
     trap_ctx.x[10] = 0;
-    new_pid as isize
+    Ok(new_pid as isize)
 }
-pub fn sys_exec(path: *const u8, mut args: *const usize) -> isize {
+pub fn sys_exec(path: *const u8, mut args: *const usize) -> SysResult {
     // println!("[kernel] sys_exec: pid: {} path: {}", sys_getpid(), path as usize);
     let cur_proc = get_cur_proc();
-    let inner = cur_proc.exclusive_access();
+    let mut inner = cur_proc.exclusive_access();
 
-    let path = inner.mm.read_str(VirtAddr::from(path as usize));
+    let path = inner.mm.copy_in_str(path as usize, MAX_USER_STR_LEN)?;
 
     // println!("[kernel] sys_exec: path: {}", path);
 
     // fetch args in user addr space
     let mut args_vec: Vec<String> = Vec::new();
     loop {
-        let args_pa = inner
-            .mm
-            .page_table
-            .find_pa(VirtAddr::from(args as usize))
-            .unwrap()
-            .0;
-        unsafe {
-            let arg_str_ptr = *(args_pa as *const usize);
-            if arg_str_ptr == 0 {
-                break;
-            }
-            args_vec.push(inner.mm.read_str(VirtAddr::from(arg_str_ptr)));
-            args = args.add(1);
+        let arg_str_ptr: usize = inner.mm.copy_in_val(args as usize)?;
+        if arg_str_ptr == 0 {
+            break;
         }
+        args_vec.push(inner.mm.copy_in_str(arg_str_ptr, MAX_USER_STR_LEN)?);
+        args = args.wrapping_add(1);
     }
+    let cwd = inner.cwd.clone();
     drop(inner);
 
-    if let Some(app_kernel_file) = KernelFile::from_path(path.as_str(), OpenFlags::RDONLY) {
-        let all_data = app_kernel_file.read_all();
-        let argc = args_vec.len();
-        cur_proc.exec(all_data.as_slice(), args_vec);
-        // return argc because cx.x[10] will be covered with it later
-        argc as isize
-    } else {
-        -1
+    let app_kernel_file = KernelFile::from_path(&cwd, path.as_str(), OpenFlags::RDONLY)?;
+    // a bad file fails here, the caller keeps running
+    let mm = MemoryManager::from_elf(app_kernel_file.inode())?;
+    let argc = args_vec.len();
+    cur_proc.exec(mm, args_vec);
+    // return argc because cx.x[10] will be covered with it later
+    Ok(argc as isize)
+}
+/// Don't wait if no child has changed its state, return EAGAIN
+const WNOHANG: usize = 1;
+/// Report the stopped children too
+const WUNTRACED: usize = 2;
+
+/// What happened to the child a parent has waited for
+enum ChildEvent {
+    /// It exited and is reaped, with its exit code, the signal killing it and its usage
+    Exited(i32, Option<usize>, Usage),
+    /// It's stopped by the signal
+    Stopped(usize),
+}
+
+/// Whether `child` is chosen by `pid`: -1 for any child, `-pgid` for the ones in a group,
+/// or the pid itself
+fn child_matches(child: &ProcessControlBlock, pid: isize) -> bool {
+    match pid {
+        -1 => true,
+        pid if pid < -1 => child.exclusive_access().pgid == (-pid) as usize,
+        pid => child.getpid() == pid as usize,
     }
 }
-pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
-    // println!("[kernel] sys_waitpid: pid: {}", sys_getpid());
-    let cur_proc = get_cur_proc();
 
-    let mut inner = cur_proc.exclusive_access();
-    if !inner
-        .children
-        .iter()
-        .any(|p| pid == -1 || pid as usize == p.getpid())
-    {
-        // no such children, what the hell?
-        return -1;
+/// Wait until a child chosen by `pid` exits, or stops with WUNTRACED. The wait is given up
+/// for a signal which kills the caller or has a handler, with EINTR.
+fn wait_child(pid: isize, options: usize) -> Result<(usize, ChildEvent), Errno> {
+    let thread = get_cur_thread().unwrap();
+    let cur_proc = thread.pcb.upgrade().unwrap();
+    loop {
+        if check_interrupt() {
+            return Err(Errno::EINTR);
+        }
+        let mut inner = cur_proc.exclusive_access();
+        if !inner.children.iter().any(|p| child_matches(p, pid)) {
+            // no such children, what the hell?
+            return Err(Errno::ECHILD);
+        }
+        let zombie = inner
+            .children
+            .iter()
+            .position(|p| child_matches(p, pid) && p.is_zombie());
+        if let Some(ind) = zombie {
+            // its resources are released when the hart it exited on drops it too
+            let child = inner.children.remove(ind);
+            let child_inner = child.exclusive_access();
+            // the child's usage goes to the parent, with the children it has reaped
+            let mut usage = child_inner.usage();
+            usage.add(&child_inner.children_usage);
+            let event = ChildEvent::Exited(child_inner.exit_code, child_inner.term_signal, usage);
+            drop(child_inner);
+            inner.children_usage.add(&usage);
+            return Ok((child.getpid(), event));
+        }
+        if options & WUNTRACED != 0 {
+            for child in inner.children.iter().filter(|p| child_matches(p, pid)) {
+                // a stop is reported once
+                if let Some(sig) = child.exclusive_access().stopped_by.take() {
+                    return Ok((child.getpid(), ChildEvent::Stopped(sig)));
+                }
+            }
+        }
+        if options & WNOHANG != 0 {
+            return Err(Errno::EAGAIN);
+        }
+        // the children wake us up when they exit or stop
+        inner.wait_queue.push_back(thread.clone());
+        drop(inner);
+        block_thread();
     }
-    let pair = inner
-        .children
-        .iter()
-        .enumerate()
-        .find(|(_, p)| p.is_zombie() && (pid == -1 || pid as usize == p.getpid()));
-    if let Some((ind, _)) = pair {
-        let child = inner.children.remove(ind);
-        // the resource this proc holds should be able to released
-        assert_eq!(Arc::strong_count(&child), 1);
-        let pid = child.getpid();
-        let exit_code = child.exclusive_access().exit_code;
+}
+
+/// Reap a zombie child and write its exit code, blocking until there's one.
+/// With WNOHANG, EAGAIN if the children are all still running.
+pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32, options: usize) -> SysResult {
+    // println!("[kernel] sys_waitpid: pid: {}", sys_getpid());
+    // keep the zombie if its exit code can't be written back
+    get_cur_proc().exclusive_access().mm.check_user(
+        exit_code_ptr as usize,
+        size_of::<i32>(),
+        true,
+    )?;
+    let (pid, event) = wait_child(pid, options & WNOHANG)?;
+    let ChildEvent::Exited(exit_code, ..) = event else {
+        unreachable!("a stopped child is only reported with WUNTRACED");
+    };
+    get_cur_proc()
+        .exclusive_access()
+        .mm
+        .copy_out_val(exit_code_ptr as usize, &exit_code)?;
+    Ok(pid as isize)
+}
 
+/// Like `sys_waitpid`, but the status is encoded like Linux, with the child stopped or killed by
+/// a signal, and the usage of the reaped child is written to `rusage` if it's not null
+pub fn sys_wait4(
+    pid: isize,
+    status_ptr: *mut i32,
+    options: usize,
+    rusage: *mut RUsage,
+) -> SysResult {
+    let cur_proc = get_cur_proc();
+    let mut inner = cur_proc.exclusive_access();
+    inner
+        .mm
+        .check_user(status_ptr as usize, size_of::<i32>(), true)?;
+    if !rusage.is_null() {
         inner
             .mm
-            .write((exit_code_ptr as usize).into(), &exit_code.to_ne_bytes());
-        pid as isize
-    } else {
-        -2
+            .check_user(rusage as usize, size_of::<RUsage>(), true)?;
+    }
+    drop(inner);
+    let (pid, event) = wait_child(pid, options)?;
+    let status = match event {
+        ChildEvent::Exited(_, Some(sig), _) => sig as i32,
+        ChildEvent::Exited(exit_code, None, _) => (exit_code & 0xff) << 8,
+        ChildEvent::Stopped(sig) => ((sig as i32) << 8) | 0x7f,
+    };
+    let mut inner = cur_proc.exclusive_access();
+    inner.mm.copy_out_val(status_ptr as usize, &status)?;
+    if let (ChildEvent::Exited(_, _, usage), false) = (event, rusage.is_null()) {
+        inner.mm.copy_out_val(rusage as usize, &usage.to_rusage())?;
     }
+    Ok(pid as isize)
+}
+
+/// Move process `pid`, the caller or one of its children if 0, to group `pgid`, or a new group
+/// of its own if 0
+pub fn sys_setpgid(pid: usize, pgid: usize) -> SysResult {
+    let cur_proc = get_cur_proc();
+    let proc = if pid == 0 || pid == cur_proc.getpid() {
+        cur_proc
+    } else {
+        let inner = cur_proc.exclusive_access();
+        let child = inner.children.iter().find(|p| p.getpid() == pid).cloned();
+        child.ok_or(Errno::ESRCH)?
+    };
+    let pgid = if pgid == 0 { proc.getpid() } else { pgid };
+    proc.exclusive_access().pgid = pgid;
+    Ok(0)
+}
+
+/// The group of process `pid`, or the caller if 0
+pub fn sys_getpgid(pid: usize) -> SysResult {
+    let proc = if pid == 0 {
+        get_cur_proc()
+    } else {
+        pid2pcb(pid).ok_or(Errno::ESRCH)?
+    };
+    let pgid = proc.exclusive_access().pgid;
+    Ok(pgid as isize)
+}
+
+/// Make group `pgid` the foreground of the console, which gets the signals from Ctrl-C and Ctrl-Z.
+/// A negative `pgid` leaves no group in the foreground.
+pub fn sys_tcsetpgrp(pgid: isize) -> SysResult {
+    set_foreground(if pgid < 0 {
+        NO_FOREGROUND
+    } else {
+        pgid as usize
+    });
+    Ok(0)
 }
 
 pub fn sys_shutdown() -> ! {
     shutdown();
 }
-pub fn sys_kill(pid: usize, signal: u32) -> isize {
-    if let Some(pcb) = pid2pcb(pid) {
-        if let Some(flag) = SignalFlags::from_bits(signal) {
-            let mut inner = pcb.exclusive_access();
-            inner.signals |= flag;
-            0
-        } else {
-            -1
-        }
-    } else {
-        -1
+pub fn sys_kill(pid: usize, signum: u32) -> SysResult {
+    if signum as usize > MAX_SIG {
+        return Err(Errno::EINVAL);
     }
+    let pcb = pid2pcb(pid).ok_or(Errno::ESRCH)?;
+    let flag = SignalFlags::from_bits(1 << signum).ok_or(Errno::EINVAL)?;
+    send_signal(&pcb, flag);
+    Ok(0)
 }
 
-pub fn sys_sigprocmask(mask: u32) -> isize {
+pub fn sys_sigprocmask(mask: u32) -> SysResult {
     let proc = get_cur_proc();
     let mut inner = proc.exclusive_access();
     let old_mask = inner.signal_mask;
-    if let Some(flag) = SignalFlags::from_bits(mask) {
-        inner.signal_mask = flag;
-        old_mask.bits() as isize
-    } else {
-        -1
-    }
+    let flag = SignalFlags::from_bits(mask).ok_or(Errno::EINVAL)?;
+    inner.signal_mask = flag;
+    Ok(old_mask.bits() as isize)
 }
 
-pub fn sys_sigreturn() -> isize {
+pub fn sys_sigreturn() -> SysResult {
     let thread = get_cur_thread().unwrap();
-    let thr_inner = thread.exclusive_access();
+    let trap_ctx = thread.exclusive_access().get_trap_ctx();
     let proc = thread.pcb.upgrade().unwrap();
     let mut proc_inner = proc.exclusive_access();
     proc_inner.handling_sig = -1;
     // restore the trap context
-    let trap_ctx = thr_inner.get_trap_ctx();
     *trap_ctx = proc_inner.trap_ctx_backup.unwrap();
     // Here we return the value of a0 in the trap_ctx,
     // otherwise it will be overwritten after we trap
     // back to the original execution of the application.
-    trap_ctx.x[10] as isize
+    Ok(trap_ctx.x[10] as isize)
 }
 
 fn check_sigaction_error(signal: SignalFlags, action: usize, old_action: usize) -> bool {
@@ -177,36 +350,22 @@ pub fn sys_sigaction(
     signum: i32,
     action: *const SignalAction,
     old_action: *mut SignalAction,
-) -> isize {
+) -> SysResult {
     let cur_proc = get_cur_proc();
     let mut inner = cur_proc.exclusive_access();
     if signum as usize > MAX_SIG {
-        return -1;
+        return Err(Errno::EINVAL);
     }
     if let Some(flag) = SignalFlags::from_bits(1 << signum) {
         if check_sigaction_error(flag, action as usize, old_action as usize) {
-            return -1;
+            return Err(Errno::EINVAL);
         }
         let prev_action = inner.signal_actions.table[signum as usize];
-
-        unsafe {
-            *(inner
-                .mm
-                .page_table
-                .find_pa(VirtAddr::from(old_action as usize))
-                .unwrap()
-                .0 as *mut SignalAction) = prev_action;
-        }
-        inner.signal_actions.table[signum as usize] = unsafe {
-            *(inner
-                .mm
-                .page_table
-                .find_pa(VirtAddr::from(action as usize))
-                .unwrap()
-                .0 as *const SignalAction)
-        };
-        0
+        let new_action = inner.mm.copy_in_val(action as usize)?;
+        inner.mm.copy_out_val(old_action as usize, &prev_action)?;
+        inner.signal_actions.table[signum as usize] = new_action;
+        Ok(0)
     } else {
-        -1
+        Err(Errno::EINVAL)
     }
 }
@@ -1,18 +1,19 @@
+use crate::errno::{Errno, SysResult};
 use crate::proc::{block_thread, get_cur_proc, get_cur_thread};
 use crate::sync::{BlockedMutex, Condvar, Mutex, SpinMutex};
 use crate::timer::{create_timer, get_time_ms};
 use alloc::sync::Arc;
 
-pub fn sys_sleep(ms: usize) -> isize {
+pub fn sys_sleep(ms: usize) -> SysResult {
     let expire_ms = get_time_ms() + ms;
     let thread = get_cur_thread().unwrap();
     create_timer(expire_ms, thread);
     block_thread();
-    0
+    Ok(0)
 }
 
 /// Return the mutex id (mid)
-pub fn sys_mutex_create(is_blocked: bool) -> isize {
+pub fn sys_mutex_create(is_blocked: bool) -> SysResult {
     let proc = get_cur_proc();
     let mutex: Option<Arc<dyn Mutex>> = if !is_blocked {
         Some(Arc::new(SpinMutex::new()))
@@ -27,34 +28,34 @@ pub fn sys_mutex_create(is_blocked: bool) -> isize {
         .find(|(_, item)| item.is_none())
     {
         proc_inner.mutex_list[mid] = mutex;
-        mid as isize
+        Ok(mid as isize)
     } else {
         proc_inner.mutex_list.push(mutex);
-        (proc_inner.mutex_list.len() - 1) as isize
+        Ok((proc_inner.mutex_list.len() - 1) as isize)
     }
 }
 
-pub fn sys_mutex_lock(mid: usize) -> isize {
+pub fn sys_mutex_lock(mid: usize) -> SysResult {
     let proc = get_cur_proc();
     let proc_inner = proc.exclusive_access();
-    let mutex = Arc::clone(proc_inner.mutex_list[mid].as_ref().unwrap());
+    let mutex = get_mutex(&proc_inner.mutex_list, mid)?;
     drop(proc_inner);
     drop(proc);
     mutex.lock();
-    0
+    Ok(0)
 }
 
-pub fn sys_mutex_unlock(mid: usize) -> isize {
+pub fn sys_mutex_unlock(mid: usize) -> SysResult {
     let proc = get_cur_proc();
     let proc_inner = proc.exclusive_access();
-    let mutex = Arc::clone(proc_inner.mutex_list[mid].as_ref().unwrap());
+    let mutex = get_mutex(&proc_inner.mutex_list, mid)?;
     drop(proc_inner);
     drop(proc);
     mutex.unlock();
-    0
+    Ok(0)
 }
 
-pub fn sys_condvar_create() -> isize {
+pub fn sys_condvar_create() -> SysResult {
     let proc = get_cur_proc();
     let mut proc_inner = proc.exclusive_access();
     let cid = if let Some(cid) = proc_inner
@@ -70,26 +71,42 @@ pub fn sys_condvar_create() -> isize {
         proc_inner.condvar_list.push(Some(Arc::new(Condvar::new())));
         proc_inner.condvar_list.len() - 1
     };
-    cid as isize
+    Ok(cid as isize)
 }
 
-pub fn sys_condvar_signal(condvar_id: usize) -> isize {
+pub fn sys_condvar_signal(condvar_id: usize) -> SysResult {
     let proc = get_cur_proc();
     let proc_inner = proc.exclusive_access();
-    let condvar = Arc::clone(proc_inner.condvar_list[condvar_id].as_ref().unwrap());
+    let condvar = get_condvar(&proc_inner.condvar_list, condvar_id)?;
     drop(proc_inner);
     drop(proc);
     condvar.signal();
-    0
+    Ok(0)
 }
 
-pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
+pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> SysResult {
     let proc = get_cur_proc();
     let proc_inner = proc.exclusive_access();
-    let condvar = Arc::clone(proc_inner.condvar_list[condvar_id].as_ref().unwrap());
-    let mutex = Arc::clone(proc_inner.mutex_list[mutex_id].as_ref().unwrap());
+    let condvar = get_condvar(&proc_inner.condvar_list, condvar_id)?;
+    let mutex = get_mutex(&proc_inner.mutex_list, mutex_id)?;
     drop(proc_inner);
     drop(proc);
     condvar.wait(mutex);
-    0
+    Ok(0)
+}
+
+/// Look up a mutex of the process, EINVAL for a bad id
+fn get_mutex(mutex_list: &[Option<Arc<dyn Mutex>>], mid: usize) -> Result<Arc<dyn Mutex>, Errno> {
+    mutex_list
+        .get(mid)
+        .and_then(|mutex| mutex.clone())
+        .ok_or(Errno::EINVAL)
+}
+
+/// Look up a condvar of the process, EINVAL for a bad id
+fn get_condvar(condvar_list: &[Option<Arc<Condvar>>], cid: usize) -> Result<Arc<Condvar>, Errno> {
+    condvar_list
+        .get(cid)
+        .and_then(|condvar| condvar.clone())
+        .ok_or(Errno::EINVAL)
 }
\ No newline at end of file
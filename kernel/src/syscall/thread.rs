@@ -1,3 +1,4 @@
+use crate::errno::{Errno, SysResult};
 use crate::mm::KERNEL_MM;
 use crate::proc::{get_cur_thread, push_thread, ThreadControlBlock};
 use crate::trap::TrapContext;
@@ -8,8 +9,9 @@ use alloc::sync::Arc;
 /// arg: the argument to pass to the thread function
 /// return the thread's TID
 /// A group of thread resources will be allocated: user stack, trap context and kernel stack, etc.
+/// The user stack grows on demand up to the stack limit of the process, see sys_setrlimit.
 /// No need to create new addr space, which is different from process creation.
-pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
+pub fn sys_thread_create(entry: usize, arg: usize) -> SysResult {
     let thread = get_cur_thread().unwrap();
     let proc = thread.pcb.upgrade().unwrap();
     // create a new thread
@@ -24,7 +26,8 @@ pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
         true,
     ));
 
-    // push new thread to the scheduler
+    // push new thread to the scheduler, with the priority of the creator
+    new_thr.sched().nice = thread.sched().nice;
     push_thread(new_thr.clone());
 
     let new_thr_inner = new_thr.exclusive_access();
@@ -48,40 +51,43 @@ pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
 
     // println!("sys_thread_create: new thread created with TID {}, entry: {}", new_thr_tid, entry);
 
-    new_thr_tid as isize
+    Ok(new_thr_tid as isize)
 }
-pub fn sys_gettid() -> isize {
-    get_cur_thread().unwrap().exclusive_access().res.as_ref().unwrap().tid as isize
+pub fn sys_gettid() -> SysResult {
+    Ok(get_cur_thread().unwrap().exclusive_access().res.as_ref().unwrap().tid as isize)
 }
 
 /// Wait for a thread to exit
-/// thread does not exist, return -1
-/// thread has not exited yet, return -2
+/// thread does not exist, return ESRCH
+/// thread has not exited yet, return EAGAIN
 /// otherwise, return thread's exit code
-pub fn sys_waittid(tid: usize) -> i32 {
+pub fn sys_waittid(tid: usize) -> SysResult {
     let thread = get_cur_thread().unwrap();
     let proc = thread.pcb.upgrade().unwrap();
-    let thr_inner = thread.exclusive_access();
-    let mut proc_inner = proc.exclusive_access();
-
+    let cur_tid = thread.exclusive_access().res.as_ref().unwrap().tid;
     // a thread cannot wait for itself
-    if tid == thr_inner.res.as_ref().unwrap().tid {
-        return -1;
+    if tid == cur_tid {
+        return Err(Errno::EDEADLK);
     }
 
+    let mut proc_inner = proc.exclusive_access();
+
     let mut exit_code: Option<i32> = None;
-    let target_thread = proc_inner.threads[tid].as_ref();
+    let target_thread = proc_inner.threads.get(tid).and_then(|t| t.as_ref());
     if let Some(target_thread) = target_thread {
         if let Some(target_exit_code) = target_thread.exclusive_access().exit_code {
             exit_code = Some(target_exit_code);
         }
     } else {
-        return -1;
+        return Err(Errno::ESRCH);
     }
     if let Some(exit_code) = exit_code {
-        proc_inner.threads[tid] = None;
-        exit_code
+        if let Some(target_thread) = proc_inner.threads[tid].take() {
+            let usage = target_thread.exclusive_access().clock.usage;
+            proc_inner.exited_usage.add(&usage);
+        }
+        Ok(exit_code as isize)
     } else {
-        -2
+        Err(Errno::EAGAIN)
     }
 }
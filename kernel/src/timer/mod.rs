@@ -1,9 +1,11 @@
 use crate::config::*;
-use crate::proc::{wakeup_thread, ThreadControlBlock};
-use crate::sync::UPSafeCell;
+use crate::proc::{hart_id, wakeup_thread, ThreadControlBlock};
+use crate::sync::SpinLock;
 use alloc::collections::BinaryHeap;
 use alloc::sync::Arc;
 use core::arch::global_asm;
+use core::mem::size_of;
+use core::ptr::addr_of_mut;
 use lazy_static::lazy_static;
 use riscv::register::{mie, mscratch, mstatus, mtvec};
 
@@ -17,9 +19,10 @@ const MILLI_PER_SEC: usize = 1_000;
 
 const TICKS_PER_SEC: usize = 100;
 
+/// Set the mtimecmp of the current hart
 pub fn set_time_cmp(time: usize) {
     unsafe {
-        (MTIMECMP as *mut usize).write_volatile(time);
+        (MTIMECMP as *mut usize).add(hart_id()).write_volatile(time);
     }
 }
 
@@ -37,29 +40,39 @@ pub fn get_time_ms() -> usize {
     get_time() / (CLOCK_FREQ / MILLI_PER_SEC)
 }
 
+/// Convert a duration read from mtime to microseconds
+pub fn time_to_us(time: usize) -> usize {
+    time / (CLOCK_FREQ / MICRO_PER_SEC)
+}
+
 pub fn set_next_trigger() {
     set_time_cmp(get_time() + 1 * CLOCK_FREQ / TICKS_PER_SEC);
 }
 
+/// The scratch space of `__mtime_trap` for each hart
 #[link_section = ".bss.stack"]
 #[no_mangle]
-pub static mut TIMER_SCRATCH: [usize; 5] = [0; 5];
+pub static mut TIMER_SCRATCH: [[usize; 6]; MAX_HARTS] = [[0; 6]; MAX_HARTS];
 
+/// Set up the machine timer and software interrupts of the current hart, in machine mode
 pub fn init() {
     extern "C" {
         fn __mtime_trap();
     }
+    let hart = hart_id();
     unsafe {
         mtvec::write(__mtime_trap as usize, mtvec::TrapMode::Direct);
 
-        // let scratch = &mut TIMER_SCRATCH;
-        TIMER_SCRATCH[3] = MTIMECMP;
-        TIMER_SCRATCH[4] = CLOCK_FREQ / TICKS_PER_SEC;
-        mscratch::write(TIMER_SCRATCH.as_mut_ptr() as usize);
+        let scratch = &mut *addr_of_mut!(TIMER_SCRATCH[hart]);
+        scratch[3] = MTIMECMP + hart * size_of::<usize>();
+        scratch[4] = CLOCK_FREQ / TICKS_PER_SEC;
+        scratch[5] = MSIP + hart * size_of::<u32>();
+        mscratch::write(scratch.as_mut_ptr() as usize);
 
         mstatus::set_mie();
 
         mie::set_mtimer();
+        mie::set_msoft();
     }
 }
 
@@ -87,8 +100,8 @@ impl Ord for TimerCondVar {
     }
 }
 lazy_static! {
-    static ref TIMERS: UPSafeCell<BinaryHeap<TimerCondVar>> =
-        unsafe { UPSafeCell::new(BinaryHeap::<TimerCondVar>::new()) };
+    static ref TIMERS: SpinLock<BinaryHeap<TimerCondVar>> =
+        SpinLock::new(BinaryHeap::<TimerCondVar>::new());
 }
 
 pub fn create_timer(expire_ms: usize, tcb: Arc<ThreadControlBlock>) {
@@ -0,0 +1,99 @@
+//! Backtraces of the kernel by the frame pointers, symbolized with the symbols embedded by build.rs
+
+use crate::config::MEMORY_END;
+use crate::mm::{PageTable, VirtAddr};
+use crate::println;
+use core::arch::asm;
+use core::fmt::{self, Display, Formatter};
+use riscv::register::satp;
+
+/// `addr name` per line, sorted by the address. Empty if the kernel is built by cargo alone.
+static KERNEL_SYMS: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/kernel.sym"));
+
+/// Stop at this depth, in case the frame pointers form a loop
+const MAX_DEPTH: usize = 32;
+
+extern "C" {
+    fn skernel();
+    fn stext();
+    fn etext();
+}
+
+/// Find the function containing `pc`, return its name and the offset of `pc` in it
+pub fn symbolize(pc: usize) -> Option<(&'static str, usize)> {
+    if !(stext as usize..etext as usize).contains(&pc) {
+        return None;
+    }
+    let syms = core::str::from_utf8(KERNEL_SYMS).ok()?;
+    let mut found = None;
+    for line in syms.lines() {
+        let Some((addr, name)) = line.split_once(' ') else {
+            continue;
+        };
+        let Ok(addr) = usize::from_str_radix(addr, 16) else {
+            continue;
+        };
+        if addr > pc {
+            break;
+        }
+        found = Some((name, pc - addr));
+    }
+    found
+}
+
+/// Displays a pc with the function containing it
+pub struct Symbolized(pub usize);
+
+impl Display for Symbolized {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match symbolize(self.0) {
+            Some((name, offset)) => write!(f, "{:#x} <{}+{:#x}>", self.0, name, offset),
+            None => write!(f, "{:#x}", self.0),
+        }
+    }
+}
+
+/// Whether the kernel can read the word at `va` without a trap
+fn readable(va: usize) -> bool {
+    // the upper bits of a Sv39 address are the same as bit 38
+    if ((va as isize) << 25 >> 25) as usize != va {
+        return false;
+    }
+    let satp = satp::read().bits();
+    if satp >> 60 == 0 {
+        // the paging is not enabled yet
+        return (skernel as usize..MEMORY_END).contains(&va);
+    }
+    PageTable::from_token(satp)
+        .find_pte(VirtAddr::from(va).floor())
+        .is_some_and(|pte| pte.is_valid() && pte.is_readable())
+}
+
+/// Print the return addresses in the frames from the one at `fp`, i.e. the s0 of a function.
+/// Each frame has the return address at fp - 8 and the fp of its caller at fp - 16.
+fn print_backtrace_from(mut fp: usize) {
+    println!("[kernel] backtrace:");
+    for depth in 0..MAX_DEPTH {
+        if fp % 8 != 0 || fp < 16 || !readable(fp - 16) || !readable(fp - 8) {
+            break;
+        }
+        let ra = unsafe { *((fp - 8) as *const usize) };
+        let caller_fp = unsafe { *((fp - 16) as *const usize) };
+        println!("  #{:<2} {}", depth, Symbolized(ra));
+        // the stack grows down, so the frames of the callers are above
+        if caller_fp <= fp {
+            break;
+        }
+        fp = caller_fp;
+    }
+}
+
+/// Print the backtrace of the caller
+#[inline(never)]
+pub fn print_backtrace() {
+    let fp: usize;
+    unsafe {
+        asm!("mv {}, s0", out(reg) fp);
+    }
+    print_backtrace_from(fp);
+}
@@ -18,6 +18,8 @@ pub struct TrapContext {
     pub kernel_sp: usize,
     /// Addr of trap_handler function
     pub trap_handler: usize,
+    /// The hart the thread runs on, loaded into tp when it traps
+    pub hart_id: usize,
 }
 
 impl TrapContext {
@@ -41,6 +43,7 @@ impl TrapContext {
             kernel_satp,
             kernel_sp,
             trap_handler: trap_handler as usize,
+            hart_id: 0,
         };
         ctx.set_sp(sp);
         ctx
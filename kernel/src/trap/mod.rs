@@ -1,9 +1,11 @@
+mod backtrace;
 mod context;
 
 use crate::syscall::syscall;
 
 use crate::config::*;
-use crate::proc::{check_signals_error_of_current, current_add_signal, exit_thread, get_cur_trap_ctx, get_cur_trap_ctx_user_va, get_cur_user_token, handle_signals, switch_thread, SignalFlags};
+use crate::mm::VirtAddr;
+use crate::proc::{check_signals_error_of_current, current_add_fault_signal, exit_thread, get_cur_proc, get_cur_thread, get_cur_trap_ctx, get_cur_trap_ctx_user_va, get_cur_user_token, handle_signals, hart_id, switch_thread, tick_thread, try_get_cur_thread, SignalFlags};
 use core::arch::{asm, global_asm};
 use riscv::register::{
     mtvec::TrapMode,
@@ -12,6 +14,17 @@ use riscv::register::{
 };
 
 global_asm!(include_str!("trampoline.S"));
+global_asm!(include_str!("kernel_trap.S"));
+
+/// The ABI names of the registers, for the report of a kernel trap
+const REG_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+    "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+    "t5", "t6",
+];
+
+/// scause of a misaligned load, which is `Exception::Unknown` in the riscv crate
+const LOAD_MISALIGNED: usize = 4;
 
 /// initialize CSR `stvec` as the entry of `__alltraps`
 pub fn init() {
@@ -27,8 +40,11 @@ pub fn init() {
     }
 }
 fn set_kernel_trap_entry() {
+    extern "C" {
+        fn __kernel_trap();
+    }
     unsafe {
-        stvec::write(trap_from_kernel as usize, TrapMode::Direct);
+        stvec::write(__kernel_trap as usize, TrapMode::Direct);
     }
 }
 fn set_user_trap_entry() {
@@ -41,6 +57,7 @@ fn set_user_trap_entry() {
 /// handle an interrupt, exception, or system call from user space
 pub fn trap_handler() -> ! {
     set_kernel_trap_entry();
+    get_cur_thread().unwrap().exclusive_access().clock.charge_user();
 
     let scause = scause::read();
     let stval = stval::read();
@@ -52,40 +69,78 @@ pub fn trap_handler() -> ! {
     // );
     match scause.cause() {
         Trap::Interrupt(Interrupt::SupervisorSoft) => {
-            // delegated by m mode, actually a machine timer interrupt
             // println!("[kernel] FUCK! TIME INTERRUPT! {}", stval);
-            let sip = sip::read().bits();
-            unsafe {
-                asm! {"csrw sip, {sip}", sip = in(reg) sip ^ 2};
+            handle_timer_interrupt();
+            if tick_thread() {
+                switch_thread();
             }
-            set_next_trigger();
-            check_timer();
-            switch_thread();
         }
         Trap::Exception(Exception::UserEnvCall) => {
             let mut ctx = get_cur_trap_ctx();
             ctx.sepc += 4;
-            let res = syscall(ctx.x[17], [ctx.x[10], ctx.x[11], ctx.x[12]]) as usize;
+            let args = [ctx.x[10], ctx.x[11], ctx.x[12], ctx.x[13], ctx.x[14], ctx.x[15]];
+            let res = syscall(ctx.x[17], args) as usize;
             // ctx is changed during sys_exec
             ctx = get_cur_trap_ctx();
             ctx.x[10] = res;
         }
+        Trap::Exception(Exception::StorePageFault) => {
+            // load the page if it's lazy or swapped out, grow the stack into it, and copy it if
+            // it's shared after fork. A fault in a guard page or beyond the stack limit is SIGSEGV.
+            let cur_proc = get_cur_proc();
+            let mut inner = cur_proc.exclusive_access();
+            let vpn = VirtAddr::from(stval).floor();
+            if inner.mm.populate(vpn) || inner.mm.copy_on_write(vpn) {
+                drop(inner);
+                count_page_fault();
+            } else {
+                drop(inner);
+                current_add_fault_signal(SignalFlags::SIGSEGV, stval);
+            }
+        }
+        Trap::Exception(Exception::LoadPageFault)
+        | Trap::Exception(Exception::InstructionPageFault) => {
+            // the first access to a page of a lazy area or a page swapped out, load it
+            let cur_proc = get_cur_proc();
+            let mut inner = cur_proc.exclusive_access();
+            if inner.mm.populate(VirtAddr::from(stval).floor()) {
+                drop(inner);
+                count_page_fault();
+            } else {
+                drop(inner);
+                current_add_fault_signal(SignalFlags::SIGSEGV, stval);
+            }
+        }
         Trap::Exception(Exception::StoreFault)
-        | Trap::Exception(Exception::StorePageFault)
         | Trap::Exception(Exception::LoadFault)
-        | Trap::Exception(Exception::LoadPageFault) => {
+        | Trap::Exception(Exception::InstructionFault) => {
             // error!(
             //     "[kernel] PageFault, bad addr = {:#x}, bad instruction = {:#x}, kernel killed it\
             //     .\n",
             //     stval, ctx.sepc
             // );
             // exit_proc(-2);
-            current_add_signal(SignalFlags::SIGSEGV);
+            current_add_fault_signal(SignalFlags::SIGSEGV, stval);
+        }
+        Trap::Exception(Exception::StoreMisaligned)
+        | Trap::Exception(Exception::InstructionMisaligned) => {
+            current_add_fault_signal(SignalFlags::SIGBUS, stval);
+        }
+        Trap::Exception(Exception::Unknown) if scause.bits() == LOAD_MISALIGNED => {
+            current_add_fault_signal(SignalFlags::SIGBUS, stval);
+        }
+        Trap::Exception(Exception::Breakpoint) => {
+            // stval may not hold the address of the ebreak
+            current_add_fault_signal(SignalFlags::SIGTRAP, get_cur_trap_ctx().sepc);
         }
         Trap::Exception(Exception::IllegalInstruction) => {
             // error!("IllegalInstruction in application\n");
             // exit_proc(-3);
-            current_add_signal(SignalFlags::SIGILL);
+            current_add_fault_signal(SignalFlags::SIGILL, get_cur_trap_ctx().sepc);
+        }
+        Trap::Exception(_) => {
+            // any other exception caused by the user, like an ecall it can't make
+            current_add_fault_signal(SignalFlags::SIGILL, get_cur_trap_ctx().sepc);
         }
         _ => {
             panic!(
@@ -99,16 +154,52 @@ pub fn trap_handler() -> ! {
     handle_signals();
 
     if let Some((errno, msg)) = check_signals_error_of_current() {
-        println!("[kernel] {}", msg);
+        let fault_addr = {
+            let cur_proc = get_cur_proc();
+            let mut inner = cur_proc.exclusive_access();
+            // wait4 reports the signal to the parent
+            inner.term_signal = Some(-errno as usize);
+            inner.fault_addr
+        };
+        match fault_addr {
+            Some(addr) => println!("[kernel] {}, fault addr = {:#x}", msg, addr),
+            None => println!("[kernel] {}", msg),
+        }
         exit_thread(errno);
     }
 
+    // the main thread is exiting the process, see `stop_other_threads`
+    if get_cur_proc().is_exiting() {
+        exit_thread(0);
+    }
+
     trap_return()
 }
 
+/// Handle the supervisor software interrupt, delegated by m mode, actually a machine timer
+/// interrupt: wake up the sleeping threads and the console readers.
+/// The idle loops call it too, as the kernel itself is never interrupted.
+pub fn handle_timer_interrupt() {
+    let sip = sip::read().bits();
+    unsafe {
+        asm! {"csrw sip, {sip}", sip = in(reg) sip & !2};
+    }
+    set_next_trigger();
+    check_timer();
+    check_console();
+}
+
+/// A page fault of the current thread is resolved, account it
+fn count_page_fault() {
+    get_cur_thread().unwrap().exclusive_access().clock.usage.page_faults += 1;
+}
+
 #[no_mangle]
 pub fn trap_return() -> ! {
     // println!("[kernel] trap_return: pid: {}", get_cur_proc().unwrap().pid.0);
+    get_cur_thread().unwrap().exclusive_access().clock.charge_system();
+    // the thread may run on another hart since the last trap
+    get_cur_trap_ctx().hart_id = hart_id();
     set_user_trap_entry();
     let trap_ctx_ptr = get_cur_trap_ctx_user_va();
     let user_satp = get_cur_user_token();
@@ -131,13 +222,40 @@ pub fn trap_return() -> ! {
 }
 
 #[no_mangle]
-pub fn trap_from_kernel() -> ! {
-    panic!(
-        "a trap {:?} happened in kernel mode!",
-        scause::read().cause()
+/// handle a trap from the kernel, which is a bug. `ctx` is saved by `__kernel_trap`.
+/// Report where it happened, then panic, and the panic handler prints the backtrace.
+pub extern "C" fn trap_from_kernel(ctx: &TrapContext) -> ! {
+    let scause = scause::read();
+    println!(
+        "[kernel] trap {:?} in kernel mode on hart {}",
+        scause.cause(),
+        hart_id()
     );
+    println!("[kernel] sepc = {}", Symbolized(ctx.sepc));
+    println!("[kernel] stval = {:#x}", stval::read());
+    // the thread manager may be in use when the trap happened
+    match try_get_cur_thread() {
+        Some(thread) => {
+            let pid = thread.pcb.upgrade().map(|pcb| pcb.getpid());
+            let tid = thread
+                .try_access()
+                .and_then(|inner| inner.res.as_ref().map(|res| res.tid));
+            println!("[kernel] current pid = {:?}, tid = {:?}", pid, tid);
+        }
+        None => println!("[kernel] no current thread"),
+    }
+    for (i, chunk) in ctx.x.chunks(4).enumerate() {
+        for (j, reg) in chunk.iter().enumerate() {
+            print!("{:>4} = {:#018x}  ", REG_NAMES[i * 4 + j], reg);
+        }
+        println!("");
+    }
+    panic!("a trap {:?} happened in kernel mode!", scause.cause());
 }
 
+use crate::console::stdin::check_console;
 use crate::timer::{check_timer, set_next_trigger};
+pub use backtrace::print_backtrace;
+use backtrace::Symbolized;
 pub use context::TrapContext;
-use crate::println;
+use crate::{print, println};
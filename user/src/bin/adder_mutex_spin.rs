@@ -27,9 +27,9 @@ unsafe fn critical_section(t: &mut usize) {
 unsafe fn f() -> ! {
     let mut t = 2usize;
     for _ in 0..PER_THREAD {
-        mutex_lock(0);
+        mutex_lock(0).unwrap();
         critical_section(&mut t);
-        mutex_unlock(0);
+        mutex_unlock(0).unwrap();
     }
     exit(t as i32)
 }
@@ -49,13 +49,13 @@ pub fn main(argc: usize, argv: &[&str]) -> i32 {
     }
 
     let start = get_time();
-    assert_eq!(spin_mutex_create(), 0);
+    assert_eq!(spin_mutex_create().unwrap(), 0);
     let mut v = Vec::new();
     for _ in 0..thread_count {
-        v.push(thread_create(f as usize, 0) as usize);
+        v.push(thread_create(f as usize, 0).unwrap());
     }
     for tid in v.into_iter() {
-        waittid(tid);
+        waittid(tid).unwrap();
     }
     println!("time cost is {}ms", get_time() - start);
     assert_eq!(unsafe { A }, unsafe { PER_THREAD } * thread_count);
@@ -84,11 +84,11 @@ pub fn main(argc: usize, argv: &[&str]) -> i32 {
         "Peterson works when there are only 2 threads."
     );
     for id in 0..thread_count {
-        v.push(thread_create(f as usize, id) as usize);
+        v.push(thread_create(f as usize, id).unwrap());
     }
     let mut time_cost = Vec::new();
     for tid in v.iter() {
-        time_cost.push(waittid(*tid));
+        time_cost.push(waittid(*tid).unwrap());
     }
     println!("time cost is {}ms", get_time() - start);
     assert_eq!(unsafe { A }, unsafe { PER_THREAD } * thread_count);
@@ -0,0 +1,75 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec;
+use core::arch::asm;
+use user_lib::{close, pipe, read, write, Errno};
+
+const SYSCALL_OPEN: usize = 56;
+const SYSCALL_READ: usize = 63;
+const SYSCALL_WRITE: usize = 64;
+const SYSCALL_EXEC: usize = 221;
+const SYSCALL_FSTAT: usize = 2000;
+
+/// A kernel address, which is mapped in the user page table but not accessible to the user
+const TRAP_CONTEXT: usize = usize::MAX - 2 * 0x1000 + 1;
+
+/// Pass raw pointers, which the wrappers of user_lib don't allow
+fn syscall(id: usize, args: [usize; 3]) -> isize {
+    let mut ret: isize;
+    unsafe {
+        asm!(
+            "ecall",
+            inlateout("x10") args[0] => ret,
+            in("x11") args[1],
+            in("x12") args[2],
+            in("x17") id,
+        );
+    }
+    ret
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let efault = -Errno::EFAULT.raw();
+    // unmapped, kernel-only and out of the user space
+    assert_eq!(syscall(SYSCALL_WRITE, [1, 0, 10]), efault);
+    assert_eq!(syscall(SYSCALL_WRITE, [1, TRAP_CONTEXT, 10]), efault);
+    assert_eq!(syscall(SYSCALL_WRITE, [1, 1 << 38, 10]), efault);
+    assert_eq!(syscall(SYSCALL_OPEN, [TRAP_CONTEXT, 0, 0]), efault);
+    assert_eq!(syscall(SYSCALL_FSTAT, [1, 0, 0]), efault);
+
+    // the code is not writable, and the data is kept on failure
+    let mut pipe_fd = [0usize; 2];
+    pipe(&mut pipe_fd).unwrap();
+    write(pipe_fd[1], b"x").unwrap();
+    assert_eq!(syscall(SYSCALL_READ, [pipe_fd[0], main as usize, 1]), efault);
+    let mut buf = [0u8; 1];
+    assert_eq!(read(pipe_fd[0], &mut buf), Ok(1));
+    assert_eq!(&buf, b"x");
+    close(pipe_fd[0]).unwrap();
+    close(pipe_fd[1]).unwrap();
+
+    // strings are bounded
+    let long_path = vec![b'a'; 0x2000];
+    assert_eq!(
+        syscall(SYSCALL_OPEN, [long_path.as_ptr() as usize, 0, 0]),
+        -Errno::ENAMETOOLONG.raw()
+    );
+
+    // a bad argv fails exec without replacing the process
+    let path = "/tests/bad_pointer\0";
+    assert_eq!(syscall(SYSCALL_EXEC, [path.as_ptr() as usize, 0, 0]), efault);
+    let args = [0x10usize, 0];
+    assert_eq!(
+        syscall(SYSCALL_EXEC, [path.as_ptr() as usize, args.as_ptr() as usize, 0]),
+        efault
+    );
+
+    println!("bad_pointer passed!");
+    0
+}
@@ -0,0 +1,27 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::arch::asm;
+
+const ENOSYS: isize = 38;
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("Try to make a syscall the kernel does not know");
+    let mut ret: isize;
+    unsafe {
+        asm!(
+            "ecall",
+            inlateout("x10") 0usize => ret,
+            in("x11") 0usize,
+            in("x12") 0usize,
+            in("x17") 0xdeadusize,
+        );
+    }
+    assert_eq!(ret, -ENOSYS);
+    println!("bad_syscall passed!");
+    0
+}
@@ -0,0 +1,49 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::{brk, fork, sbrk, waitpid, Errno};
+
+const PAGE_SIZE: usize = 0x1000;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // the new pages of the heap are zeroed and writable
+    let old_brk = sbrk(0).unwrap();
+    assert_eq!(sbrk(PAGE_SIZE as isize), Ok(old_brk));
+    let new_brk = sbrk(0).unwrap();
+    assert_eq!(new_brk, old_brk + PAGE_SIZE);
+    let page = unsafe { core::slice::from_raw_parts_mut(old_brk as *mut u8, PAGE_SIZE) };
+    assert!(page.iter().all(|b| *b == 0));
+    page.fill(0x5a);
+
+    // a forked child has a copy of the heap
+    let pid = fork().unwrap();
+    if pid == 0 {
+        assert!(page.iter().all(|b| *b == 0x5a));
+        return 0;
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), Ok(pid));
+    assert_eq!(exit_code, 0);
+
+    // shrink it back, and it can't go below the start of the heap or grow without bound
+    assert_eq!(brk(old_brk), Ok(old_brk));
+    assert_eq!(brk(0x1000), Err(Errno::ENOMEM));
+    assert_eq!(sbrk(isize::MAX / 2), Err(Errno::ENOMEM));
+    assert_eq!(sbrk(0), Ok(old_brk));
+
+    // the allocator grows the heap on demand, far beyond its initial size
+    let mut v: Vec<usize> = Vec::new();
+    for i in 0..0x20000 {
+        v.push(i);
+    }
+    assert!(v.iter().enumerate().all(|(i, x)| i == *x));
+    assert!(sbrk(0).unwrap() > old_brk);
+    println!("brk_test passed!");
+    0
+}
@@ -5,21 +5,22 @@ extern crate alloc;
 #[macro_use]
 extern crate user_lib;
 
-use user_lib::{close, get_abs_path, open, read, OpenFlags};
+use user_lib::{close, open, read, OpenFlags};
 use alloc::format;
 
 #[no_mangle]
 pub fn main(argc: usize, argv: &[&str]) -> i32 {
     assert_eq!(argc, 2);
-    let path = get_abs_path(argv[1]);
-    let fd = open(format!("{}\0", path).as_str(), OpenFlags::RDONLY);
-    if fd == -1 {
-        panic!("Error occured when opening file");
-    }
-    let fd = fd as usize;
+    let fd = match open(format!("{}\0", argv[1]).as_str(), OpenFlags::RDONLY) {
+        Ok(fd) => fd,
+        Err(errno) => {
+            println!("cat: {}: {}", argv[1], errno);
+            return -1;
+        }
+    };
     let mut buf = [0u8; 256];
     loop {
-        let size = read(fd, &mut buf) as usize;
+        let size = read(fd, &mut buf).expect("Error occured when reading file");
         if size == 0 {
             break;
         }
@@ -32,6 +33,6 @@ pub fn main(argc: usize, argv: &[&str]) -> i32 {
             }
         }
     }
-    close(fd);
+    close(fd).ok();
     0
 }
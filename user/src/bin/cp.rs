@@ -4,16 +4,16 @@
 extern crate alloc;
 extern crate user_lib;
 
-use user_lib::{cp, get_abs_path, println};
+use alloc::format;
+use user_lib::{cp, println};
 
 #[no_mangle]
 pub fn main(argc: usize, argv: &[&str]) -> i32 {
     assert_eq!(argc, 3);
-    let src = get_abs_path(argv[1]);
-    let dst = get_abs_path(argv[2]);
-    let ret = cp(src.as_str(), dst.as_str());
-    if ret < 0 {
-        println!("cp {} {} failed: {}", src, dst, ret);
+    let src = format!("{}\0", argv[1]);
+    let dst = format!("{}\0", argv[2]);
+    if let Err(errno) = cp(src.as_str(), dst.as_str()) {
+        println!("cp {} {} failed: {}", argv[1], argv[2], errno);
         return -1;
     }
     0
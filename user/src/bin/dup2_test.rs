@@ -0,0 +1,31 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, dup, dup2, pipe, read, write, Errno};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut pipe_fd = [0usize; 2];
+    pipe(&mut pipe_fd).unwrap();
+    // a high fd, with free ones below it
+    assert_eq!(dup2(pipe_fd[1], 7), Ok(7));
+    assert_eq!(write(7, b"dup2"), Ok(4));
+    let mut buf = [0u8; 4];
+    assert_eq!(read(pipe_fd[0], &mut buf), Ok(4));
+    assert_eq!(&buf, b"dup2");
+    // dup still takes the lowest free fd
+    let fd = dup(pipe_fd[0]).unwrap();
+    assert!(fd < 7);
+    // the file at the new fd is replaced
+    assert_eq!(dup2(pipe_fd[0], fd), Ok(fd));
+    assert_eq!(dup2(fd, fd), Ok(fd));
+    assert_eq!(dup2(99, 8), Err(Errno::EBADF));
+    for fd in [fd, 7, pipe_fd[0], pipe_fd[1]] {
+        close(fd).unwrap();
+    }
+    println!("dup2_test passed!");
+    0
+}
@@ -16,7 +16,7 @@ pub fn thread_a() -> ! {
 
 #[no_mangle]
 pub fn main() -> i32 {
-    thread_create(thread_a as usize, 0);
+    thread_create(thread_a as usize, 0).unwrap();
     println!("main thread exited.");
     exit(0)
 }
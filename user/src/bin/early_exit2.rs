@@ -17,7 +17,7 @@ pub fn thread_a() -> ! {
 
 #[no_mangle]
 pub fn main() -> i32 {
-    thread_create(thread_a as usize, 0);
+    thread_create(thread_a as usize, 0).unwrap();
     sleep(100);
     println!("main thread exited.");
     exit(0)
@@ -0,0 +1,98 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::arch::asm;
+use core::ptr::{addr_of, addr_of_mut, read_volatile};
+use user_lib::{exit, fork, sigaction, waitpid, SignalAction, SIGSEGV, SIGTRAP};
+
+const BAD_ADDR: usize = 0x10;
+
+/// The address of the ebreak, written by the child before running it
+static mut EBREAK_ADDR: usize = 0;
+/// Not executable
+static mut DATA: [u32; 4] = [0; 4];
+
+fn on_segv(_signum: i32, addr: usize) {
+    exit(if addr == BAD_ADDR { 42 } else { 1 })
+}
+
+fn on_trap(_signum: i32, addr: usize) {
+    let expected = unsafe { read_volatile(addr_of!(EBREAK_ADDR)) };
+    exit(if addr == expected { 43 } else { 1 })
+}
+
+fn ebreak() {
+    unsafe {
+        asm!(
+            ".option push",
+            ".option norvc",
+            "auipc {pc}, 0",
+            "addi {pc}, {pc}, 12",
+            "sd {pc}, 0({slot})",
+            "ebreak",
+            ".option pop",
+            pc = out(reg) _,
+            slot = in(reg) addr_of_mut!(EBREAK_ADDR),
+        );
+    }
+}
+
+fn handle(signum: i32, handler: fn(i32, usize)) {
+    let action = SignalAction {
+        handler: handler as usize,
+        ..Default::default()
+    };
+    assert!(sigaction(signum, Some(&action), None).is_ok());
+}
+
+/// Run `f` in a child, return its exit code
+fn run(f: fn()) -> i32 {
+    let pid = fork().unwrap();
+    if pid == 0 {
+        f();
+        exit(0);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), Ok(pid));
+    exit_code
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // the default action of the signals raised by faults is to kill the process
+    assert_eq!(run(ebreak), -5);
+    assert_eq!(
+        run(|| unsafe {
+            let f: fn() = core::mem::transmute(addr_of!(DATA));
+            f();
+        }),
+        -11
+    );
+    assert_eq!(
+        run(|| unsafe {
+            read_volatile(BAD_ADDR as *const u8);
+        }),
+        -11
+    );
+
+    // the handlers get the address of the fault
+    assert_eq!(
+        run(|| {
+            handle(SIGSEGV, on_segv);
+            unsafe { (BAD_ADDR as *mut u8).write_volatile(0) };
+        }),
+        42
+    );
+    assert_eq!(
+        run(|| {
+            handle(SIGTRAP, on_trap);
+            ebreak();
+        }),
+        43
+    );
+    println!("fault_signals passed!");
+    0
+}
@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec;
+use user_lib::{fork, get_time, waitpid, yield_};
+
+const FORKS: usize = 200;
+const DATA_SIZE: usize = 256 * 1024;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // the pages are shared after fork, so a large address space doesn't make fork slow
+    let mut data = vec![1u8; DATA_SIZE];
+    let start = get_time();
+    for i in 0..FORKS {
+        let pid = fork().unwrap();
+        if pid == 0 {
+            return if data[i] == 1 { 0 } else { -1 };
+        }
+        let mut exit_code = 0;
+        assert_eq!(waitpid(pid, &mut exit_code), Ok(pid));
+        assert_eq!(exit_code, 0);
+    }
+    println!(
+        "{} forks with {}KiB of heap in {}ms",
+        FORKS,
+        DATA_SIZE / 1024,
+        get_time() - start
+    );
+
+    // but the writes of each process are private
+    let pid = fork().unwrap();
+    if pid == 0 {
+        assert_eq!(data[1], 1);
+        data.fill(2);
+        yield_();
+        assert!(data.iter().all(|x| *x == 2));
+        return 0;
+    }
+    data[1] = 3;
+    yield_();
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), Ok(pid));
+    assert_eq!(exit_code, 0);
+    assert_eq!(data[1], 3);
+    assert!(data.iter().enumerate().all(|(i, x)| i == 1 || *x == 1));
+    println!("fork_bench passed!");
+    0
+}
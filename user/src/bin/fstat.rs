@@ -5,17 +5,23 @@ extern crate alloc;
 extern crate user_lib;
 
 use alloc::format;
-use user_lib::{fstat, get_abs_path, open, print, OpenFlags};
+use user_lib::{close, fstat, open, print, println, OpenFlags, Stat};
 
 #[no_mangle]
 pub fn main(argc: usize, argv: &[&str]) -> i32 {
     assert_eq!(argc, 2);
-    let path = get_abs_path(argv[1]);
-    let fd = open(format!("{}\0", path).as_str(), OpenFlags::RDONLY);
-    if fd == -1 {
-        panic!("Error occured when opening file");
+    let fd = match open(format!("{}\0", argv[1]).as_str(), OpenFlags::RDONLY) {
+        Ok(fd) => fd,
+        Err(errno) => {
+            println!("fstat: {}: {}", argv[1], errno);
+            return -1;
+        }
+    };
+    let mut stat = Stat::new();
+    if fstat(fd, &mut stat).is_err() {
+        panic!("Error occured when getting file status");
     }
-    fstat(fd as usize);
-    print!("\n");
+    print!("{}\n", stat);
+    close(fd).ok();
     0
 }
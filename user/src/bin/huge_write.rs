@@ -4,7 +4,7 @@
 #[macro_use]
 extern crate user_lib;
 
-use user_lib::{close, get_abs_path, get_time, open, write, OpenFlags};
+use user_lib::{close, get_time, open, write, OpenFlags};
 
 #[no_mangle]
 pub fn main() -> i32 {
@@ -13,19 +13,19 @@ pub fn main() -> i32 {
         *ch = i as u8;
     }
     let f = open(
-        get_abs_path("testf\0").as_str(),
-        OpenFlags::CREATE | OpenFlags::WRONLY,
+        "testf\0",
+        OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC,
     );
-    if f < 0 {
-        panic!("Open test file failed!");
-    }
-    let f = f as usize;
+    let f = match f {
+        Ok(f) => f,
+        Err(errno) => panic!("Open test file failed: {}", errno),
+    };
     let start = get_time();
     let size_mb = 1usize;
     for _ in 0..1024 * size_mb {
-        write(f, &buffer);
+        write(f, &buffer).unwrap();
     }
-    close(f);
+    close(f).unwrap();
     let time_ms = (get_time() - start) as usize;
     let speed_kbs = size_mb * 1000000 / time_ms;
     println!(
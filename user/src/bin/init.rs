@@ -9,13 +9,12 @@ use user_lib::{exec, fork, wait, yield_};
 #[no_mangle]
 fn main() -> i32 {
     println!("\n[init] This is init. Wish you a good day!");
-    if fork() == 0 {
+    if fork().unwrap() == 0 {
         exec("/bin/shell\0", &[core::ptr::null::<u8>()]);
     } else {
         loop {
             let mut exit_code: i32 = 0;
-            let pid = wait(&mut exit_code);
-            if pid == -1 {
+            if wait(&mut exit_code).is_err() {
                 yield_();
                 continue;
             }
@@ -0,0 +1,103 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::ptr::{addr_of, addr_of_mut, read_volatile};
+use user_lib::{
+    close, cp, exec, fork, lseek, open, read, rm, waitpid, write, Errno, OpenFlags, SEEK_END,
+    SEEK_SET,
+};
+
+const PAGE_SIZE: usize = 0x1000;
+const TABLE_LEN: usize = 16 * PAGE_SIZE / 4;
+
+const fn make_table() -> [u32; TABLE_LEN] {
+    let mut table = [0u32; TABLE_LEN];
+    let mut i = 0;
+    while i < TABLE_LEN {
+        table[i] = (i as u32).wrapping_mul(2654435761);
+        i += 1;
+    }
+    table
+}
+
+/// Loaded from the file page by page
+static TABLE: [u32; TABLE_LEN] = make_table();
+/// Zero-filled page by page
+static mut BSS: [u8; 256 * PAGE_SIZE] = [0; 256 * PAGE_SIZE];
+
+fn table(i: usize) -> u32 {
+    unsafe { read_volatile(addr_of!(TABLE[i])) }
+}
+
+fn bss() -> &'static mut [u8] {
+    unsafe { &mut *addr_of_mut!(BSS) }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // the pages read first by a forked child are loaded for it alone
+    let pid = fork().unwrap();
+    if pid == 0 {
+        assert_eq!(
+            table(TABLE_LEN - 1),
+            ((TABLE_LEN - 1) as u32).wrapping_mul(2654435761)
+        );
+        assert_eq!(bss()[100 * PAGE_SIZE], 0);
+        bss()[100 * PAGE_SIZE] = 1;
+        return 0;
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), Ok(pid));
+    assert_eq!(exit_code, 0);
+    assert_eq!(bss()[100 * PAGE_SIZE], 0);
+
+    for i in (0..TABLE_LEN).step_by(PAGE_SIZE / 4 + 1) {
+        assert_eq!(table(i), (i as u32).wrapping_mul(2654435761));
+    }
+    for i in (0..128 * PAGE_SIZE).step_by(PAGE_SIZE * 3 + 7) {
+        assert_eq!(bss()[i], 0);
+        bss()[i] = 0xa5;
+    }
+
+    // the kernel loads the pages it copies from or to, before the user touches them
+    let path = "/tmp/lazy_load\0";
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::RDWR | OpenFlags::TRUNC).unwrap();
+    let src = unsafe { &*(addr_of!(TABLE[7 * PAGE_SIZE / 4]) as *const [u8; PAGE_SIZE]) };
+    assert_eq!(write(fd, src), Ok(PAGE_SIZE));
+    assert_eq!(lseek(fd, 0, SEEK_SET), Ok(0));
+    let dst = &mut bss()[200 * PAGE_SIZE + 1..201 * PAGE_SIZE + 1];
+    assert_eq!(read(fd, dst), Ok(PAGE_SIZE));
+    close(fd).unwrap();
+    rm(path).unwrap();
+    for i in 0..PAGE_SIZE / 4 {
+        let bytes = [dst[i * 4], dst[i * 4 + 1], dst[i * 4 + 2], dst[i * 4 + 3]];
+        assert_eq!(u32::from_le_bytes(bytes), table(7 * PAGE_SIZE / 4 + i));
+    }
+
+    // a file which is not an ELF fails exec, and the caller goes on
+    let flags = OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC;
+    let fd = open(path, flags).unwrap();
+    write(fd, b"#!/bin/sh\necho hello\n").unwrap();
+    close(fd).unwrap();
+    assert_eq!(exec(path, &[core::ptr::null::<u8>()]), Errno::ENOEXEC);
+    rm(path).unwrap();
+
+    // a running program can't be written, and a program opened for writing can't be run
+    let this = "/tests/lazy_load\0";
+    assert_eq!(open(this, OpenFlags::WRONLY), Err(Errno::ETXTBSY));
+    // TRUNC only applies to an open for writing
+    let fd = open(this, OpenFlags::RDONLY | OpenFlags::TRUNC).unwrap();
+    assert!(lseek(fd, 0, SEEK_END).unwrap() > 0);
+    close(fd).unwrap();
+    cp(this, path).unwrap();
+    assert_eq!(cp(path, this), Err(Errno::ETXTBSY));
+    let fd = open(path, OpenFlags::WRONLY).unwrap();
+    assert_eq!(exec(path, &[core::ptr::null::<u8>()]), Errno::ETXTBSY);
+    close(fd).unwrap();
+    rm(path).unwrap();
+    println!("lazy_load passed!");
+    0
+}
@@ -6,66 +6,43 @@ extern crate alloc;
 extern crate user_lib;
 
 use alloc::format;
-use alloc::vec::Vec;
-use user_lib::{close, fstat, get_abs_path, getcwd, open, read, DirEntry, OpenFlags};
+use alloc::string::String;
+use user_lib::{close, fstat, getcwd, open, read_dir, OpenFlags, Stat};
 
 #[no_mangle]
 pub fn main(argc: usize, argv: &[&str]) -> i32 {
     assert!(argc <= 2, "Usage: ll [path]");
     let path = if argc == 1 {
-        getcwd()
+        getcwd().unwrap()
     } else {
-        let mut s = get_abs_path(argv[1]);
+        let mut s = String::from(argv[1]);
         if !s.ends_with('/') {
             s.push('/');
         }
         s
     };
-    let dir_fd = open(format!("{}\0", &path).as_str(), OpenFlags::RDONLY);
-    if dir_fd == -1 {
-        panic!("Error occured when opening file");
-    }
-    let dir_fd = dir_fd as usize;
-    let mut buf = [0u8; 256];
-    let mut dir_data = Vec::new();
-    loop {
-        let size = read(dir_fd, &mut buf) as usize;
-        if size == 0 {
-            break;
+    let dir_fd = match open(format!("{}\0", &path).as_str(), OpenFlags::RDONLY) {
+        Ok(fd) => fd,
+        Err(errno) => {
+            println!("ll: {}: {}", path, errno);
+            return -1;
         }
-        dir_data.extend_from_slice(&buf[..size]);
-    }
-    assert_eq!(
-        dir_data.len() % core::mem::size_of::<DirEntry>(),
-        0,
-        "Directory data size is not a multiple of DirEntry size"
-    );
-    let dir_entries: Vec<DirEntry> = unsafe {
-        core::slice::from_raw_parts(
-            dir_data.as_ptr() as *const DirEntry,
-            dir_data.len() / core::mem::size_of::<DirEntry>(),
-        )
-        .iter()
-        .filter_map(|&entry| if entry.is_empty() { None } else { Some(entry) })
-        .collect()
     };
+    let dir_entries = read_dir(dir_fd).expect("Error occured when reading directory");
     println!("total {} entries:", dir_entries.len());
     for entry in dir_entries {
-        let name = unsafe {
-            let raw_name = &entry.name[..];
-            let len = raw_name
-                .iter()
-                .position(|&c| c == 0)
-                .unwrap_or(raw_name.len());
-            core::str::from_utf8_unchecked(&raw_name[..len])
-        };
+        let name = entry.name.as_str();
         print!("{}", format!("Name: {:<30}", name));
-        let fd = open(format!("{}{}\0", &path, name).as_str(), OpenFlags::RDONLY);
-        fstat(fd as usize);
-        close(fd as usize);
+        if let Ok(fd) = open(format!("{}{}\0", &path, name).as_str(), OpenFlags::RDONLY) {
+            let mut stat = Stat::new();
+            if fstat(fd, &mut stat).is_ok() {
+                print!("{}", stat);
+            }
+            close(fd).ok();
+        }
         print!("\n");
     }
     // print!("\n");
-    close(dir_fd);
+    close(dir_fd).ok();
     0
 }
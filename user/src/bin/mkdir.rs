@@ -4,19 +4,21 @@
 extern crate alloc;
 extern crate user_lib;
 
-use user_lib::{close, get_abs_path, open, OpenFlags};
+use alloc::format;
+use user_lib::{close, open, println, OpenFlags};
 
 #[no_mangle]
 pub fn main(argc: usize, argv: &[&str]) -> i32 {
     assert!(argc == 2);
-    let mut path = get_abs_path(argv[1]);
-    if !path.ends_with("/") {
-        path.push('/');
+    let path = format!("{}\0", argv[1]);
+    match open(path.as_str(), OpenFlags::CREATE | OpenFlags::DIRECTORY) {
+        Ok(fd) => {
+            close(fd).ok();
+        }
+        Err(errno) => {
+            println!("mkdir: {}: {}", argv[1], errno);
+            return -1;
+        }
     }
-    let fd = open(path.as_str(), OpenFlags::CREATE);
-    if fd <= 0 {
-        panic!("Error occured when opening file");
-    }
-    close(fd as usize);
     0
 }
@@ -0,0 +1,95 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, fork, mmap, mprotect, munmap, open, pipe, read, rm, waitpid, write, Errno, MmapFlags,
+    MmapProt, OpenFlags,
+};
+
+const PAGE_SIZE: usize = 0x1000;
+
+fn anonymous(len: usize, prot: MmapProt) -> Result<usize, Errno> {
+    mmap(0, len, prot, MmapFlags::PRIVATE | MmapFlags::ANONYMOUS, 0, 0)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // anonymous mappings are zeroed
+    let rw = MmapProt::READ | MmapProt::WRITE;
+    let start = anonymous(3 * PAGE_SIZE, rw).unwrap();
+    let buf = unsafe { core::slice::from_raw_parts_mut(start as *mut u8, 3 * PAGE_SIZE) };
+    assert!(buf.iter().all(|b| *b == 0));
+    buf.fill(0x5a);
+
+    // a read-only page can't be written, even by the kernel on our behalf
+    let mid = start + PAGE_SIZE;
+    mprotect(mid, PAGE_SIZE, MmapProt::READ).unwrap();
+    assert_eq!(buf[PAGE_SIZE], 0x5a);
+    let mut pipe_fd = [0usize; 2];
+    pipe(&mut pipe_fd).unwrap();
+    write(pipe_fd[1], b"x").unwrap();
+    let mid_page = unsafe { core::slice::from_raw_parts_mut(mid as *mut u8, PAGE_SIZE) };
+    assert_eq!(read(pipe_fd[0], mid_page), Err(Errno::EFAULT));
+
+    // unmapping the middle page keeps the others
+    munmap(mid, PAGE_SIZE).unwrap();
+    assert_eq!(write(pipe_fd[1], mid_page), Err(Errno::EFAULT));
+    assert_eq!(buf[0], 0x5a);
+    assert_eq!(buf[3 * PAGE_SIZE - 1], 0x5a);
+
+    // the mappings are copied by fork
+    let pid = fork().unwrap();
+    if pid == 0 {
+        assert_eq!(buf[0], 0x5a);
+        munmap(start, 3 * PAGE_SIZE).unwrap();
+        return 0;
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), Ok(pid));
+    assert_eq!(exit_code, 0);
+    munmap(start, 3 * PAGE_SIZE).unwrap();
+
+    // a file mapping holds its content, and zeros beyond the end of the file
+    let path = "/tmp/mmap_test\0";
+    let content = b"Hello, mmap!\n";
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC).unwrap();
+    write(fd, content).unwrap();
+    close(fd).unwrap();
+    let fd = open(path, OpenFlags::RDONLY).unwrap();
+    let flags = MmapFlags::PRIVATE;
+    let start = mmap(0, PAGE_SIZE, MmapProt::READ, flags, fd, 0).unwrap();
+    let mapped = unsafe { core::slice::from_raw_parts(start as *const u8, PAGE_SIZE) };
+    assert_eq!(&mapped[..content.len()], content);
+    assert!(mapped[content.len()..].iter().all(|b| *b == 0));
+    munmap(start, PAGE_SIZE).unwrap();
+    assert_eq!(mmap(0, PAGE_SIZE, MmapProt::READ, flags, fd, 1), Err(Errno::EINVAL));
+    close(fd).unwrap();
+    rm(path).unwrap();
+
+    // bad arguments
+    assert_eq!(anonymous(0, rw), Err(Errno::EINVAL));
+    assert_eq!(anonymous(PAGE_SIZE, MmapProt::empty()), Err(Errno::EINVAL));
+    assert_eq!(mmap(0, PAGE_SIZE, MmapProt::READ, flags, pipe_fd[0], 0), Err(Errno::ENODEV));
+    assert_eq!(mmap(0, PAGE_SIZE, MmapProt::READ, flags, 100, 0), Err(Errno::EBADF));
+    assert_eq!(munmap(start + 1, PAGE_SIZE), Err(Errno::EINVAL));
+    // only the mappings made by mmap can be removed
+    assert_eq!(munmap(main as usize & !(PAGE_SIZE - 1), PAGE_SIZE), Err(Errno::EINVAL));
+    close(pipe_fd[0]).unwrap();
+    close(pipe_fd[1]).unwrap();
+
+    // a large buffer
+    let len = 4 * 1024 * 1024;
+    let start = anonymous(len, rw).unwrap();
+    let buf = unsafe { core::slice::from_raw_parts_mut(start as *mut usize, len / 8) };
+    for (i, x) in buf.iter_mut().enumerate() {
+        *x = i;
+    }
+    assert!(buf.iter().enumerate().all(|(i, x)| i == *x));
+    munmap(start, len).unwrap();
+
+    println!("mmap_test passed!");
+    0
+}
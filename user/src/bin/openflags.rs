@@ -0,0 +1,55 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, lseek, open, read, rm, write, Errno, OpenFlags, SEEK_SET};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let path = "/tmp/openflags\0";
+    let dir = "/tmp/openflags_dir\0";
+
+    // EXCL: the first create succeeds, the second one fails
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::EXCL | OpenFlags::WRONLY).unwrap();
+    write(fd, "Hello, ".as_bytes()).unwrap();
+    close(fd).unwrap();
+    assert_eq!(
+        open(path, OpenFlags::CREATE | OpenFlags::EXCL | OpenFlags::WRONLY),
+        Err(Errno::EEXIST)
+    );
+
+    // APPEND: writes land at the end even after seeking back
+    let fd = open(path, OpenFlags::APPEND | OpenFlags::RDWR).unwrap();
+    lseek(fd, 0, SEEK_SET).unwrap();
+    write(fd, "world!\n".as_bytes()).unwrap();
+    lseek(fd, 0, SEEK_SET).unwrap();
+    let mut buffer = [0u8; 100];
+    let read_len = read(fd, &mut buffer).unwrap();
+    assert_eq!(
+        core::str::from_utf8(&buffer[..read_len]).unwrap(),
+        "Hello, world!\n"
+    );
+    close(fd).unwrap();
+
+    // CREATE alone keeps the content, TRUNC clears it
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::RDWR).unwrap();
+    assert_eq!(read(fd, &mut buffer), Ok(14));
+    close(fd).unwrap();
+    let fd = open(path, OpenFlags::TRUNC | OpenFlags::RDWR).unwrap();
+    assert_eq!(read(fd, &mut buffer), Ok(0));
+    close(fd).unwrap();
+
+    // DIRECTORY: only directories can be opened, and CREATE makes one
+    assert_eq!(open(path, OpenFlags::DIRECTORY), Err(Errno::ENOTDIR));
+    let fd = open(dir, OpenFlags::CREATE | OpenFlags::DIRECTORY).unwrap();
+    close(fd).unwrap();
+    let fd = open(dir, OpenFlags::DIRECTORY).unwrap();
+    close(fd).unwrap();
+
+    assert_eq!(rm(path), Ok(0));
+    assert_eq!(rm(dir), Ok(0));
+    println!("openflags passed!");
+    0
+}
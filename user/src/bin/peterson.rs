@@ -67,10 +67,10 @@ pub unsafe fn thread_fn(id: usize) -> ! {
 #[no_mangle]
 pub fn main() -> i32 {
     let mut v = Vec::new();
-    v.push(thread_create(thread_fn as usize, 0));
-    v.push(thread_create(thread_fn as usize, 1));
+    v.push(thread_create(thread_fn as usize, 0).unwrap());
+    v.push(thread_create(thread_fn as usize, 1).unwrap());
     for tid in v.iter() {
-        let exit_code = waittid(*tid as usize);
+        let exit_code = waittid(*tid).unwrap();
         assert_eq!(exit_code, 0, "thread conflict happened!");
         println!("thread#{} exited with code {}", tid, exit_code);
     }
@@ -0,0 +1,44 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fork, getrusage, pipe, read, sleep, wait, write, RUSAGE_SELF};
+
+static STR: &str = "Hello, world!";
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut pipe_fd = [0usize; 2];
+    pipe(&mut pipe_fd).unwrap();
+    if fork().unwrap() == 0 {
+        close(pipe_fd[1]).unwrap();
+        let mut buffer = [0u8; 32];
+        // the reader sleeps until the parent writes, instead of spinning
+        let len_read = read(pipe_fd[0], &mut buffer[..STR.len()]).unwrap();
+        assert_eq!(core::str::from_utf8(&buffer[..len_read]).unwrap(), STR);
+        let usage = getrusage(RUSAGE_SELF).unwrap();
+        println!(
+            "blocked reader: utime = {} us, stime = {} us",
+            usage.utime_us, usage.stime_us
+        );
+        assert!(usage.utime_us + usage.stime_us < 100_000);
+        assert!(usage.nvcsw >= 1);
+        // and wakes up when the write end is closed
+        assert_eq!(read(pipe_fd[0], &mut buffer), Ok(0));
+        close(pipe_fd[0]).unwrap();
+        0
+    } else {
+        close(pipe_fd[0]).unwrap();
+        sleep(200);
+        assert_eq!(write(pipe_fd[1], STR.as_bytes()), Ok(STR.len()));
+        sleep(100);
+        close(pipe_fd[1]).unwrap();
+        let mut child_exit_code: i32 = 0;
+        wait(&mut child_exit_code).unwrap();
+        assert_eq!(child_exit_code, 0);
+        println!("pipe_block_test passed!");
+        0
+    }
+}
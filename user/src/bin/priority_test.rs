@@ -0,0 +1,74 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::ptr::{read_volatile, write_volatile};
+use user_lib::{
+    exit, fork, get_time, getpriority, nice, setpriority, waitpid, Errno, PRIO_PROCESS,
+};
+
+/// How long the children compete for the CPU, in ms
+const DURATION: isize = 2000;
+const NICES: [i32; 3] = [0, 5, 10];
+/// Children at each nice value, so they outnumber the harts and still compete
+const COPIES: usize = 4;
+
+/// Spin until `deadline`, return the number of rounds done
+fn spin(deadline: isize) -> usize {
+    let mut rounds = 0;
+    let mut x = 0usize;
+    while get_time() < deadline {
+        for i in 0..1000 {
+            unsafe { write_volatile(&mut x, read_volatile(&x) + i) };
+        }
+        rounds += 1;
+    }
+    rounds
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(setpriority(PRIO_PROCESS, 0, 5), Ok(0));
+    assert_eq!(getpriority(PRIO_PROCESS, 0), Ok(5));
+    assert_eq!(nice(-100), Ok(-20));
+    assert_eq!(nice(100), Ok(19));
+    assert_eq!(setpriority(1, 0, 0), Err(Errno::EINVAL));
+    assert_eq!(getpriority(PRIO_PROCESS, 99999), Err(Errno::ESRCH));
+
+    // the children run at the same time, the CPU time of each is in proportion to its weight
+    let deadline = get_time() + DURATION;
+    let mut pids = [[0; COPIES]; 3];
+    for copy in 0..COPIES {
+        for (i, &n) in NICES.iter().enumerate() {
+            let pid = fork().unwrap();
+            if pid == 0 {
+                setpriority(PRIO_PROCESS, 0, n).unwrap();
+                exit(spin(deadline) as i32);
+            }
+            pids[i][copy] = pid;
+        }
+    }
+    let mut rounds = [0; 3];
+    for (i, copies) in pids.iter().enumerate() {
+        for &pid in copies {
+            let mut exit_code = 0;
+            assert_eq!(waitpid(pid, &mut exit_code), Ok(pid));
+            rounds[i] += exit_code;
+        }
+        println!("nice {}: {} rounds", NICES[i], rounds[i]);
+    }
+    // the weights of nice 0, 5 and 10 are 1024, 335 and 110, about 3 times each
+    assert!(
+        rounds[0] >= 2 * rounds[1],
+        "nice 0 should run 3x as long as nice 5"
+    );
+    assert!(
+        rounds[1] >= 2 * rounds[2],
+        "nice 5 should run 3x as long as nice 10"
+    );
+    assert!(rounds[2] > 0, "nice 10 should still run");
+    println!("priority_test passed!");
+    0
+}
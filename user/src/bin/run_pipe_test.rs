@@ -9,11 +9,11 @@ use user_lib::{exec, fork, wait};
 #[no_mangle]
 pub fn main() -> i32 {
     for i in 0..5 {
-        if fork() == 0 {
+        if fork().unwrap() == 0 {
             exec("/tests/pipe_large_test\0", &[core::ptr::null::<u8>()]);
         } else {
             let mut _unused: i32 = 0;
-            wait(&mut _unused);
+            wait(&mut _unused).unwrap();
             println!("Iter {} OK.", i);
         }
     }
@@ -0,0 +1,95 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::hint::black_box;
+use core::ptr::{read_volatile, write_volatile};
+use user_lib::{
+    exit, fork, get_time, getpid, getrusage, sleep, waitpid, Errno, RUSAGE_CHILDREN, RUSAGE_SELF,
+    RUSAGE_THREAD,
+};
+
+/// Spin in user mode for `ms` milliseconds
+fn spin(ms: isize) {
+    let deadline = get_time() + ms;
+    let mut x = 0usize;
+    while get_time() < deadline {
+        for i in 0..10000 {
+            unsafe { write_volatile(&mut x, read_volatile(&x) + i) };
+        }
+    }
+}
+
+/// Each frame takes more than 1 KiB of the stack, which grows by page faults
+fn recurse(depth: usize) -> usize {
+    let mut buf = [0u8; 1024];
+    buf[depth % 1024] = depth as u8;
+    let buf = black_box(&mut buf);
+    if depth == 0 {
+        return 0;
+    }
+    recurse(depth - 1) + buf[depth % 1024] as usize
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // the CPU is shared with the processes waiting for us, so only a part of the time is ours
+    let before = getrusage(RUSAGE_SELF).unwrap();
+    spin(200);
+    let after = getrusage(RUSAGE_SELF).unwrap();
+    let utime = after.utime_us - before.utime_us;
+    println!("spin 200 ms: utime = {} us", utime);
+    assert!(utime >= 20_000 && utime <= 250_000);
+
+    let before = after;
+    for _ in 0..2000 {
+        getpid();
+    }
+    let after = getrusage(RUSAGE_SELF).unwrap();
+    println!(
+        "2000 syscalls: stime = {} us",
+        after.stime_us - before.stime_us
+    );
+    assert!(after.stime_us > before.stime_us);
+
+    let before = after;
+    sleep(20);
+    let after = getrusage(RUSAGE_SELF).unwrap();
+    assert!(after.nvcsw > before.nvcsw);
+
+    let before = after;
+    black_box(recurse(64));
+    let after = getrusage(RUSAGE_SELF).unwrap();
+    println!(
+        "64 KiB of stack: {} page faults",
+        after.page_faults - before.page_faults
+    );
+    assert!(after.page_faults - before.page_faults >= 8);
+
+    // only the children reaped count
+    let before = getrusage(RUSAGE_CHILDREN).unwrap();
+    let pid = fork().unwrap();
+    if pid == 0 {
+        spin(100);
+        exit(0);
+    }
+    spin(10);
+    assert_eq!(
+        getrusage(RUSAGE_CHILDREN).unwrap().utime_us,
+        before.utime_us
+    );
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), Ok(pid));
+    let after = getrusage(RUSAGE_CHILDREN).unwrap();
+    println!("child: utime = {} us", after.utime_us - before.utime_us);
+    assert!(after.utime_us - before.utime_us >= 10_000);
+
+    let thread = getrusage(RUSAGE_THREAD).unwrap();
+    let proc = getrusage(RUSAGE_SELF).unwrap();
+    assert!(thread.utime_us <= proc.utime_us);
+    assert_eq!(getrusage(2).unwrap_err(), Errno::EINVAL);
+    println!("rusage_test passed!");
+    0
+}
@@ -0,0 +1,58 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, lseek, open, pipe, read, rm, write, Errno, OpenFlags, SEEK_CUR, SEEK_END, SEEK_SET,
+};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let test_str = "Hello, world!\n";
+    let path = "/tmp/seektest\0";
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::RDWR | OpenFlags::TRUNC).unwrap();
+    write(fd, test_str.as_bytes()).unwrap();
+    assert_eq!(lseek(fd, 0, SEEK_CUR), Ok(test_str.len()));
+
+    // re-read from the beginning
+    let mut buffer = [0u8; 100];
+    assert_eq!(lseek(fd, 0, SEEK_SET), Ok(0));
+    let read_len = read(fd, &mut buffer[..5]).unwrap();
+    assert_eq!(core::str::from_utf8(&buffer[..read_len]).unwrap(), "Hello");
+
+    // relative to the end
+    assert_eq!(lseek(fd, -7, SEEK_END), Ok(7));
+    let read_len = read(fd, &mut buffer).unwrap();
+    assert_eq!(core::str::from_utf8(&buffer[..read_len]).unwrap(), "world!\n");
+
+    // a negative offset is rejected and the offset is kept
+    assert_eq!(lseek(fd, -100, SEEK_CUR), Err(Errno::EINVAL));
+    assert_eq!(lseek(fd, 0, SEEK_CUR), Ok(test_str.len()));
+
+    // append at the end
+    lseek(fd, 0, SEEK_END).unwrap();
+    write(fd, "Bye!\n".as_bytes()).unwrap();
+    assert_eq!(lseek(fd, 0, SEEK_SET), Ok(0));
+    let read_len = read(fd, &mut buffer).unwrap();
+    assert_eq!(
+        core::str::from_utf8(&buffer[..read_len]).unwrap(),
+        "Hello, world!\nBye!\n"
+    );
+    close(fd).unwrap();
+    assert_eq!(rm(path), Ok(0));
+
+    // pipes and stdio cannot seek
+    let mut pipe_fd = [0usize; 2];
+    pipe(&mut pipe_fd).unwrap();
+    assert_eq!(lseek(pipe_fd[0], 0, SEEK_SET), Err(Errno::ESPIPE));
+    assert_eq!(lseek(pipe_fd[1], 0, SEEK_CUR), Err(Errno::ESPIPE));
+    close(pipe_fd[0]).unwrap();
+    close(pipe_fd[1]).unwrap();
+    assert_eq!(lseek(0, 0, SEEK_SET), Err(Errno::ESPIPE));
+    assert_eq!(lseek(1, 0, SEEK_END), Err(Errno::ESPIPE));
+
+    println!("seektest passed!");
+    0
+}
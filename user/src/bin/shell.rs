@@ -11,13 +11,20 @@ const LF: u8 = 0x0au8;
 const CR: u8 = 0x0du8;
 const DL: u8 = 0x7fu8;
 const BS: u8 = 0x08u8;
+const CTRL_C: u8 = 0x03u8;
+const CTRL_Z: u8 = 0x1au8;
 
+use alloc::format;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 use core::fmt::Display;
 use log::{error, info};
 use user_lib::console::getchar;
-use user_lib::{close, dup, exec, fork, get_abs_path, get_env_var_path, get_exe_path, getcwd, open, pipe, waitpid, OpenFlags};
+use user_lib::{
+    cd, close, dup2, exec, fork, get_env_var_path, get_exe_path, get_time, getcwd, kill, open,
+    pipe, setpgid, tcsetpgrp, wait4, waitpid, wexitstatus, wifsignaled, wifstopped, wstopsig,
+    wtermsig, Errno, OpenFlags, RUsage, SIGCONT, WNOHANG, WUNTRACED,
+};
 
 enum State {
     Good,
@@ -44,51 +51,684 @@ fn print_cwd(state: &State, cwd: &str) {
     }
 }
 
+enum Token {
+    Word(String),
+    /// `|`
+    Pipe,
+    /// `;`
+    Semi,
+    /// `&&`
+    And,
+    /// `||`
+    Or,
+    /// `&`
+    Background,
+    /// `<`
+    In,
+    /// `>` or `>>`, fd is the number written before it, 1 by default
+    Out { fd: usize, append: bool },
+    /// `N>&M`
+    DupOut { fd: usize, target: usize },
+}
+
+/// Push the pending word, if any
+fn push_word(tokens: &mut Vec<Token>, word: &mut Option<String>) {
+    if let Some(word) = word.take() {
+        tokens.push(Token::Word(word));
+    }
+}
+
+/// Split the line into words and operators.
+/// Single quotes keep everything literally, double quotes still take `\"` and `\\`,
+/// and a backslash outside quotes escapes the next character.
+fn tokenize(line: &str) -> Result<Vec<Token>, &'static str> {
+    let mut tokens = Vec::new();
+    let mut word: Option<String> = None;
+    // the pending word has quoted or escaped characters, so it is never a fd number
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                quoted = true;
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("unmatched '"),
+                    }
+                }
+            }
+            '"' => {
+                quoted = true;
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err("unmatched \""),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("unmatched \""),
+                    }
+                }
+            }
+            '\\' => {
+                quoted = true;
+                match chars.next() {
+                    Some(c) => word.get_or_insert_with(String::new).push(c),
+                    None => return Err("nothing to escape after \\"),
+                }
+            }
+            ' ' | '\t' => {
+                push_word(&mut tokens, &mut word);
+                quoted = false;
+            }
+            '|' | ';' | '&' | '<' | '>' => {
+                // `2>`, `2>>` and `2>&1`: a single unquoted digit right before `>` is the fd
+                let mut fd = 1;
+                if c == '>' && !quoted {
+                    if let Some(w) = word.as_ref() {
+                        if w.len() == 1 && w.as_bytes()[0].is_ascii_digit() {
+                            fd = (w.as_bytes()[0] - b'0') as usize;
+                            word = None;
+                        }
+                    }
+                }
+                push_word(&mut tokens, &mut word);
+                quoted = false;
+                let token = match c {
+                    '|' if chars.peek() == Some(&'|') => {
+                        chars.next();
+                        Token::Or
+                    }
+                    '|' => Token::Pipe,
+                    '&' if chars.peek() == Some(&'&') => {
+                        chars.next();
+                        Token::And
+                    }
+                    '&' => Token::Background,
+                    ';' => Token::Semi,
+                    '<' => Token::In,
+                    _ if chars.peek() == Some(&'>') => {
+                        chars.next();
+                        Token::Out { fd, append: true }
+                    }
+                    _ if chars.peek() == Some(&'&') => {
+                        chars.next();
+                        let mut target = String::new();
+                        while let Some(&c) = chars.peek() {
+                            if !c.is_ascii_digit() {
+                                break;
+                            }
+                            target.push(c);
+                            chars.next();
+                        }
+                        match target.parse::<usize>() {
+                            Ok(target) => Token::DupOut { fd, target },
+                            Err(_) => return Err("expected a fd number after >&"),
+                        }
+                    }
+                    _ => Token::Out { fd, append: false },
+                };
+                tokens.push(token);
+            }
+            _ => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    push_word(&mut tokens, &mut word);
+    Ok(tokens)
+}
+
+#[derive(Clone)]
+enum Redirect {
+    /// open `path` with `flags` as `fd`
+    File {
+        fd: usize,
+        path: String,
+        flags: OpenFlags,
+    },
+    /// make `fd` a copy of `target`
+    Dup { fd: usize, target: usize },
+}
+
+#[derive(Clone)]
 struct Command {
-    input: String,
-    output: String,
+    /// each arg ends with '\0'
     args: Vec<String>,
-    args_addr: Vec<*const u8>,
+    redirects: Vec<Redirect>,
 }
 
 impl Command {
-    pub fn new(cmd: &str) -> Self {
-        let cmd = cmd.trim();
-        let mut args: Vec<String> = cmd
-            .split_whitespace()
-            .map(|arg| {
-                let mut s = String::from(arg);
-                s.push('\0');
-                s
+    pub fn new() -> Self {
+        Self {
+            args: Vec::new(),
+            redirects: Vec::new(),
+        }
+    }
+}
+
+/// Commands connected by pipes
+#[derive(Clone)]
+struct Pipeline {
+    commands: Vec<Command>,
+    /// ends with `&`
+    background: bool,
+}
+
+impl Pipeline {
+    /// The command line shown in the jobs table
+    pub fn describe(&self) -> String {
+        let commands: Vec<String> = self
+            .commands
+            .iter()
+            .map(|command| {
+                let args: Vec<&str> = command
+                    .args
+                    .iter()
+                    .map(|arg| arg.trim_end_matches('\0'))
+                    .collect();
+                args.join(" ")
             })
             .collect();
-        let mut input = String::new();
-        if let Some((idx, _)) = args
-            .iter()
-            .enumerate()
-            .find(|(_, arg)| arg.as_str() == "<\0")
-        {
-            input = args[idx + 1].clone();
-            args.drain(idx..=idx + 1);
+        commands.join(" | ")
+    }
+}
+
+/// How a pipeline is chained to the one before it
+#[derive(Clone, Copy)]
+enum Connector {
+    /// `;`, or the first pipeline of the line
+    Seq,
+    /// `&&`, run only if the previous pipeline succeeded
+    And,
+    /// `||`, run only if the previous pipeline failed
+    Or,
+}
+
+/// Group the tokens into pipelines chained by connectors
+fn parse(tokens: Vec<Token>) -> Result<Vec<(Connector, Pipeline)>, &'static str> {
+    let mut list = Vec::new();
+    let mut connector = Connector::Seq;
+    let mut pipeline: Vec<Command> = Vec::new();
+    let mut command = Command::new();
+    let mut tokens = tokens.into_iter();
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Word(mut word) => {
+                word.push('\0');
+                command.args.push(word);
+            }
+            Token::In | Token::Out { .. } => {
+                let mut path = match tokens.next() {
+                    Some(Token::Word(path)) => path,
+                    _ => return Err("expected a file name after redirection"),
+                };
+                path.push('\0');
+                let (fd, flags) = match token {
+                    Token::Out { fd, append: true } => {
+                        (fd, OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::APPEND)
+                    }
+                    Token::Out { fd, append: false } => {
+                        (fd, OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC)
+                    }
+                    _ => (0, OpenFlags::RDONLY),
+                };
+                command.redirects.push(Redirect::File { fd, path, flags });
+            }
+            Token::DupOut { fd, target } => command.redirects.push(Redirect::Dup { fd, target }),
+            Token::Pipe | Token::Semi | Token::And | Token::Or | Token::Background => {
+                if command.args.is_empty() {
+                    return Err("missing command");
+                }
+                pipeline.push(core::mem::replace(&mut command, Command::new()));
+                if let Token::Pipe = token {
+                    continue;
+                }
+                let pipeline = Pipeline {
+                    commands: core::mem::take(&mut pipeline),
+                    background: matches!(token, Token::Background),
+                };
+                list.push((connector, pipeline));
+                connector = match token {
+                    Token::And => Connector::And,
+                    Token::Or => Connector::Or,
+                    _ => Connector::Seq,
+                };
+            }
         }
-        let mut output = String::new();
-        if let Some((idx, _)) = args
-            .iter()
-            .enumerate()
-            .find(|(_, arg)| arg.as_str() == ">\0")
-        {
-            output = args[idx + 1].clone();
-            args.drain(idx..=idx + 1);
+    }
+    if !command.args.is_empty() {
+        pipeline.push(command);
+        let pipeline = Pipeline {
+            commands: pipeline,
+            background: false,
+        };
+        list.push((connector, pipeline));
+    } else if !pipeline.is_empty()
+        || !command.redirects.is_empty()
+        || !matches!(connector, Connector::Seq)
+    {
+        // a trailing `;` or `&` is fine, but `|`, `&&` and `||` need a command after them
+        return Err("missing command");
+    }
+    Ok(list)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum JobState {
+    Running,
+    Stopped,
+}
+
+impl Display for JobState {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            JobState::Running => write!(f, "Running"),
+            JobState::Stopped => write!(f, "Stopped"),
         }
-        let mut args_addr: Vec<*const u8> = args.iter().map(|arg| arg.as_ptr()).collect();
-        args_addr.push(0 as *const u8); // null-terminate the args
+    }
+}
+
+/// The times of a pipeline run by `time`, reported when it is done
+struct Timing {
+    /// in milliseconds
+    start: isize,
+    /// the CPU time of the processes reaped so far, in microseconds
+    utime_us: usize,
+    stime_us: usize,
+}
+
+impl Timing {
+    pub fn new() -> Self {
         Self {
-            input,
-            output,
-            args,
-            args_addr,
+            start: get_time(),
+            utime_us: 0,
+            stime_us: 0,
         }
     }
+    pub fn report(&self) {
+        let real_ms = (get_time() - self.start) as usize;
+        println!("real {}", format_us(real_ms * 1000));
+        println!("user {}", format_us(self.utime_us));
+        println!("sys  {}", format_us(self.stime_us));
+    }
+}
+
+/// A pipeline started by the shell, in the foreground or the background
+struct Job {
+    /// 0 until the job is put into the jobs table
+    id: usize,
+    /// processes not reaped yet
+    pids: Vec<usize>,
+    /// the process group of the job, which gets the signals of Ctrl-C and Ctrl-Z in the foreground
+    pgid: usize,
+    /// the exit code of a pipeline is the one of its last command
+    last_pid: usize,
+    exit_code: i32,
+    state: JobState,
+    cmd: String,
+    /// started by `time`
+    timing: Option<Timing>,
+}
+
+impl Job {
+    pub fn signal(&self, signum: i32) {
+        for pid in self.pids.iter() {
+            kill(*pid, signum).ok();
+        }
+    }
+    /// Forget the process whose status `wait4` has reported, if it has exited
+    fn update(&mut self, pid: usize, status: i32, usage: RUsage) {
+        if wifstopped(status) {
+            self.state = JobState::Stopped;
+            return;
+        }
+        self.pids.retain(|p| *p != pid);
+        if let Some(timing) = self.timing.as_mut() {
+            timing.utime_us += usage.utime_us;
+            timing.stime_us += usage.stime_us;
+        }
+        if pid == self.last_pid {
+            self.exit_code = exit_code_of(status);
+        }
+    }
+    /// Reap the exited processes without blocking, return true if all of them are gone
+    pub fn reap(&mut self) -> bool {
+        while let Ok((pid, status, usage)) = wait4(-(self.pgid as isize), WNOHANG | WUNTRACED) {
+            self.update(pid, status, usage);
+        }
+        self.pids.is_empty()
+    }
+}
+
+/// The exit code of a process, or 128 + the signal which killed it, like other shells
+fn exit_code_of(status: i32) -> i32 {
+    if wifsignaled(status) {
+        128 + wtermsig(status)
+    } else {
+        wexitstatus(status)
+    }
+}
+
+/// Put the job into the table, which is sorted by id, and return its id
+fn add_job(jobs: &mut Vec<Job>, mut job: Job) -> usize {
+    if job.id == 0 {
+        job.id = jobs.last().map_or(0, |last| last.id) + 1;
+    }
+    let id = job.id;
+    let idx = jobs.iter().position(|j| j.id > id).unwrap_or(jobs.len());
+    jobs.insert(idx, job);
+    id
+}
+
+/// Find the job given as `%n` or `n` in args[1], or the latest one if not given
+fn find_job(args: &[String], jobs: &[Job]) -> Option<usize> {
+    match args.get(1) {
+        Some(arg) => {
+            let id = arg
+                .trim_end_matches('\0')
+                .trim_start_matches('%')
+                .parse::<usize>()
+                .ok()?;
+            jobs.iter().position(|job| job.id == id)
+        }
+        None => jobs.len().checked_sub(1),
+    }
+}
+
+/// Report and forget the background jobs that have finished
+fn reap_jobs(jobs: &mut Vec<Job>) {
+    jobs.retain_mut(|job| {
+        if !job.reap() {
+            return true;
+        }
+        if job.exit_code == 0 {
+            println!("[{}] Done       {}", job.id, job.cmd);
+        } else {
+            println!("[{}] Exit {:<5} {}", job.id, job.exit_code, job.cmd);
+        }
+        if let Some(timing) = job.timing.as_ref() {
+            timing.report();
+        }
+        false
+    });
+}
+
+/// Wait for the job in the foreground, which gets SIGINT and SIGTSTP from the console for
+/// Ctrl-C and Ctrl-Z. Return its exit code, or 128 + the signal if it is stopped and put into
+/// the jobs table.
+fn wait_foreground(mut job: Job, jobs: &mut Vec<Job>) -> i32 {
+    tcsetpgrp(Some(job.pgid)).unwrap();
+    while !job.pids.is_empty() {
+        match wait4(-(job.pgid as isize), WUNTRACED) {
+            Ok((pid, status, usage)) => {
+                job.update(pid, status, usage);
+                if wifstopped(status) {
+                    tcsetpgrp(None).unwrap();
+                    let cmd = job.cmd.clone();
+                    println!("[{}] Stopped    {}", add_job(jobs, job), cmd);
+                    return 128 + wstopsig(status);
+                }
+            }
+            Err(Errno::EINTR) => {}
+            Err(errno) => panic!("[shell] Error waiting for {}: {}", job.cmd, errno),
+        }
+    }
+    tcsetpgrp(None).unwrap();
+    if let Some(timing) = job.timing.as_ref() {
+        timing.report();
+    }
+    job.exit_code
+}
+
+fn builtin_jobs(jobs: &mut Vec<Job>) -> i32 {
+    reap_jobs(jobs);
+    for job in jobs.iter() {
+        println!("[{}] {:<10} {}", job.id, job.state, job.cmd);
+    }
+    0
+}
+
+/// Continue a job in the foreground
+fn builtin_fg(args: &[String], jobs: &mut Vec<Job>) -> i32 {
+    let idx = match find_job(args, jobs) {
+        Some(idx) => idx,
+        None => {
+            println!("[fg] No such job.");
+            return -1;
+        }
+    };
+    let mut job = jobs.remove(idx);
+    println!("{}", job.cmd);
+    if job.state == JobState::Stopped {
+        job.signal(SIGCONT);
+        job.state = JobState::Running;
+    }
+    wait_foreground(job, jobs)
+}
+
+/// Continue a stopped job in the background
+fn builtin_bg(args: &[String], jobs: &mut [Job]) -> i32 {
+    let idx = match find_job(args, jobs) {
+        Some(idx) => idx,
+        None => {
+            println!("[bg] No such job.");
+            return -1;
+        }
+    };
+    let job = &mut jobs[idx];
+    if job.state == JobState::Stopped {
+        job.signal(SIGCONT);
+        job.state = JobState::Running;
+    }
+    println!("[{}] {} &", job.id, job.cmd);
+    0
+}
+
+/// Run the command in the shell itself if it is a builtin one
+fn run_builtin(args: &[String], jobs: &mut Vec<Job>) -> Option<i32> {
+    match args[0].as_str() {
+        "cd\0" => Some(builtin_cd(args)),
+        "jobs\0" => Some(builtin_jobs(jobs)),
+        "fg\0" => Some(builtin_fg(args, jobs)),
+        "bg\0" => Some(builtin_bg(args, jobs)),
+        _ => None,
+    }
+}
+
+/// `cd` has to run in the shell itself, since every process has its own cwd
+fn builtin_cd(args: &[String]) -> i32 {
+    if args.len() != 2 {
+        println!("[cd] Usage: cd <dir>");
+        return -1;
+    }
+    let dir = args[1].trim_end_matches('\0');
+    match cd(args[1].as_str()) {
+        Ok(_) => 0,
+        Err(errno) => {
+            println!("[cd] {}: {}", dir, errno);
+            -1
+        }
+    }
+}
+
+/// Format microseconds as seconds, to the millisecond
+fn format_us(us: usize) -> String {
+    format!("{}.{:03}s", us / 1_000_000, us / 1000 % 1000)
+}
+
+/// Run the pipeline after `time`. When it is done, even after being stopped and continued,
+/// report the time it took and the CPU time of its own processes.
+fn builtin_time(pipeline: &Pipeline, jobs: &mut Vec<Job>) -> Result<i32, i32> {
+    let mut timed = pipeline.clone();
+    timed.commands[0].args.remove(0);
+    if timed.commands[0].args.is_empty() {
+        println!("[time] Usage: time <command>");
+        return Ok(-1);
+    }
+    if timed.background {
+        println!("[time] Can't time a job in the background");
+        return Ok(-1);
+    }
+    let timing = Timing::new();
+    if timed.commands.len() == 1 {
+        if let Some(exit_code) = run_builtin(&timed.commands[0].args, jobs) {
+            timing.report();
+            return Ok(exit_code);
+        }
+    }
+    match start_job(&timed)? {
+        Some(mut job) => {
+            job.timing = Some(timing);
+            Ok(wait_foreground(job, jobs))
+        }
+        None => Ok(-1),
+    }
+}
+
+/// Set up the fds of the forked child running the i-th command of a pipeline, then exec it.
+/// Only returns if something went wrong.
+fn exec_command(command: &Command, i: usize, pipes_fd: &[[usize; 2]]) -> i32 {
+    // receive input from the previous command
+    if i > 0 {
+        dup2(pipes_fd[i - 1][0], 0).unwrap();
+    }
+    // send output to the next command
+    if i < pipes_fd.len() {
+        dup2(pipes_fd[i][1], 1).unwrap();
+    }
+    // close all pipe ends inherited from the parent process
+    for pipe_fd in pipes_fd.iter() {
+        close(pipe_fd[0]).ok();
+        close(pipe_fd[1]).ok();
+    }
+    // redirections override the pipes and apply from left to right
+    for redirect in command.redirects.iter() {
+        match redirect {
+            Redirect::File { fd, path, flags } => {
+                let file_fd = match open(path.as_str(), *flags) {
+                    Ok(file_fd) => file_fd,
+                    Err(errno) => {
+                        println!(
+                            "[shell] Error opening file: '{}': {}",
+                            path.trim_end_matches('\0'),
+                            errno
+                        );
+                        return -4;
+                    }
+                };
+                if file_fd != *fd {
+                    dup2(file_fd, *fd).unwrap();
+                    close(file_fd).ok();
+                }
+            }
+            Redirect::Dup { fd, target } => {
+                if dup2(*target, *fd).is_err() {
+                    println!("[shell] Bad file descriptor: {}", target);
+                    return -4;
+                }
+            }
+        }
+    }
+
+    // execute the new command
+    let args = &command.args;
+    let mut args_addr: Vec<*const u8> = args.iter().map(|arg| arg.as_ptr()).collect();
+    args_addr.push(core::ptr::null::<u8>()); // null-terminate the args
+    if let Some(path) = get_exe_path(args[0].as_str()) {
+        let errno = exec(&path, args_addr.as_slice());
+        println!("[shell] Error when executing: {}", errno);
+        -4
+    } else {
+        println!(
+            "[shell] Command '{}' not found. Neither in cwd nor in env var PATHs: {:?}",
+            args[0].trim_end_matches('\0'),
+            get_env_var_path()
+        );
+        -5
+    }
+}
+
+/// Run a pipeline and return the exit code of its last command, or 0 if it runs in the background.
+/// `Err` is only returned in a forked child whose exec failed, with the code it should exit with.
+fn run_pipeline(pipeline: &Pipeline, jobs: &mut Vec<Job>) -> Result<i32, i32> {
+    let commands = &pipeline.commands;
+    if commands[0].args[0] == "time\0" {
+        return builtin_time(pipeline, jobs);
+    }
+    if commands.len() == 1 {
+        if let Some(exit_code) = run_builtin(&commands[0].args, jobs) {
+            return Ok(exit_code);
+        }
+    }
+    let job = match start_job(pipeline)? {
+        Some(job) => job,
+        None => return Ok(-1),
+    };
+    if pipeline.background {
+        let last_pid = job.last_pid;
+        println!("[{}] {}", add_job(jobs, job), last_pid);
+        Ok(0)
+    } else {
+        Ok(wait_foreground(job, jobs))
+    }
+}
+
+/// Fork the processes of a pipeline into a new job, None if none of them is started.
+/// `Err` is only returned in a forked child whose exec failed, like `run_pipeline`.
+fn start_job(pipeline: &Pipeline) -> Result<Option<Job>, i32> {
+    let commands = &pipeline.commands;
+    // create pipes
+    let mut pipes_fd: Vec<[usize; 2]> = Vec::new();
+    for _ in 1..commands.len() {
+        let mut pipe_fd = [0usize; 2];
+        assert!(pipe(&mut pipe_fd).is_ok(), "[shell] Error creating pipe");
+        pipes_fd.push(pipe_fd);
+    }
+    let mut children: Vec<_> = Vec::new();
+    for (i, command) in commands.iter().enumerate() {
+        // the job is a group led by its first process, both sides set it so neither races
+        let pgid = children.first().copied().unwrap_or(0);
+        match fork() {
+            Ok(0) => {
+                setpgid(0, pgid).unwrap();
+                return Err(exec_command(command, i, &pipes_fd));
+            }
+            Ok(pid) => {
+                setpgid(pid, if pgid == 0 { pid } else { pgid }).unwrap();
+                children.push(pid);
+            }
+            Err(errno) => {
+                // the commands started see the pipes closed
+                println!("[shell] Error forking: {}", errno);
+                break;
+            }
+        }
+    }
+    for pipe_fd in pipes_fd.iter() {
+        close(pipe_fd[0]).ok();
+        close(pipe_fd[1]).ok();
+    }
+    if children.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(Job {
+        id: 0,
+        pids: children.clone(),
+        pgid: children[0],
+        last_pid: *children.last().unwrap(),
+        exit_code: 0,
+        state: JobState::Running,
+        cmd: pipeline.describe(),
+        timing: None,
+    }))
 }
 
 #[no_mangle]
@@ -96,16 +736,20 @@ pub fn main(_argc: usize, _argv: &[&str]) -> i32 {
     println!("[shell] This is CrazyDave shell.");
 
     // print the logo, run the logo exe file
-    let pid = fork();
-    if pid == 0 {
-        exec("/bin/logo\0", &[core::ptr::null::<u8>()]);
-    } else {
-        waitpid(pid as usize, &mut 0);
+    match fork() {
+        Ok(0) => {
+            exec("/bin/logo\0", &[core::ptr::null::<u8>()]);
+        }
+        Ok(pid) => {
+            waitpid(pid, &mut 0).ok();
+        }
+        Err(errno) => println!("[shell] Error forking: {}", errno),
     }
 
     let mut line: String = String::new();
-    let mut cwd: String = getcwd();
+    let mut cwd: String = getcwd().unwrap();
     let mut state = State::Good;
+    let mut jobs: Vec<Job> = Vec::new();
 
     print_cwd(&state, &cwd);
     loop {
@@ -116,136 +760,44 @@ pub fn main(_argc: usize, _argv: &[&str]) -> i32 {
                 line = line.trim().to_string();
 
                 if !line.is_empty() {
-                    let splited: Vec<&str> = line.split('|').collect();
-                    let commands: Vec<Command> = splited
-                        .iter()
-                        .map(|&cmd| Command::new(cmd))
-                        .collect();
-                    let mut valid = true;
-                    for (i, process_args) in commands.iter().enumerate() {
-                        if i == 0 {
-                            if !process_args.output.is_empty() {
-                                valid = false;
-                            }
-                        } else if i == commands.len() - 1 {
-                            if !process_args.input.is_empty() {
-                                valid = false;
-                            }
-                        } else if !process_args.output.is_empty() || !process_args.input.is_empty()
-                        {
-                            valid = false;
-                        }
-                    }
-                    if commands.len() == 1 {
-                        valid = true;
-                    }
-                    if !valid {
-                        println!("[shell] Invalid command: Inputs/Outputs cannot be correctly \
-                        binded!");
-                    } else {
-                        // create pipes
-                        let mut pipes_fd: Vec<[usize; 2]> = Vec::new();
-                        if !commands.is_empty() {
-                            for _ in 0..commands.len() - 1 {
-                                let mut pipe_fd = [0usize; 2];
-                                assert!(pipe(&mut pipe_fd) >= 0, "[shell] Error creating pipe");
-                                pipes_fd.push(pipe_fd);
-                            }
-                        }
-                        let mut children: Vec<_> = Vec::new();
-                        for (i, command) in commands.iter().enumerate(){
-                            let pid = fork();
-                            if pid == 0 {
-                                let input = &command.input;
-                                let output = &command.output;
-                                let args = &command.args;
-                                let args_addr = &command.args_addr;
-
-                                if !input.is_empty() {
-                                    // redirect input
-                                    let input_fd = open(get_abs_path(&input).as_str(), OpenFlags::RDONLY);
-                                    if input_fd < 0 {
-                                        println!("[shell] Error opening input file: '{}'", input);
-                                        return -4;
-                                    }
-                                    close(0);
-                                    assert_eq!(dup(input_fd as usize), 0);
-                                    close(input_fd as usize);
+                    match tokenize(line.as_str()).and_then(parse) {
+                        Ok(list) => {
+                            let mut status = 0;
+                            for (connector, pipeline) in list.iter() {
+                                let run = match connector {
+                                    Connector::Seq => true,
+                                    Connector::And => status == 0,
+                                    Connector::Or => status != 0,
+                                };
+                                if !run {
+                                    continue;
                                 }
-
-                                if !output.is_empty() {
-                                    // redirect output
-                                    let output_fd = open(
-                                        get_abs_path(&output).as_str(),
-                                        OpenFlags::CREATE | OpenFlags::WRONLY,
-                                    );
-                                    if output_fd < 0 {
-                                        println!("[shell] Error opening output file: '{}'", output);
-                                        return -4;
-                                    }
-                                    close(1);
-                                    assert_eq!(dup(output_fd as usize), 1);
-                                    close(output_fd as usize);
+                                match run_pipeline(pipeline, &mut jobs) {
+                                    Ok(exit_code) => status = exit_code,
+                                    // we are the forked child and exec failed
+                                    Err(exit_code) => return exit_code,
                                 }
-
-                                // receive input from the previous command
-                                if i > 0{
-                                    close(0);
-                                    let read_end = pipes_fd.get(i - 1).unwrap()[0];
-                                    assert_eq!(dup(read_end), 0);
-                                }
-                                // send output to the next command
-                                if i < commands.len() - 1 {
-                                    close(1);
-                                    let write_end = pipes_fd.get(i).unwrap()[1];
-                                    assert_eq!(dup(write_end), 1);
-                                }
-                                // close all pipe ends inherited from the parent process
-                                for pipe_fd in pipes_fd.iter() {
-                                    close(pipe_fd[0]);
-                                    close(pipe_fd[1]);
-                                }
-                                // execute the new command
-                                if let Some(path) = get_exe_path(args[0].as_str()) {
-                                    if exec(&path, args_addr.as_slice()) == -1 {
-                                        println!("[shell] Error when executing!");
-                                        return -4;
-                                    }
-                                } else {
-                                    println!(
-                                        "[shell] Command '{}' not found. Neither in cwd nor in env var \
-                                PATHs: {:?}",
-                                        args[0],
-                                        get_env_var_path()
-                                    );
-                                    return -5;
-                                }
-
-                                unreachable!();
-                            } else {
-                                children.push(pid);
                             }
+                            state = if status == 0 { State::Good } else { State::Bad };
                         }
-                        for pipe_fd in pipes_fd.iter() {
-                            close(pipe_fd[0]);
-                            close(pipe_fd[1]);
-                        }
-                        let mut exit_code: i32 = 0;
-                        state = State::Good;
-                        for pid in children.into_iter() {
-                            let exit_pid = waitpid(pid as usize, &mut exit_code);
-                            assert_eq!(pid, exit_pid);
-                            if exit_code < 0 {
-                                state = State::Bad;
-                            }
-                            // println!("[shell] Process {} exited with code {}", pid, exit_code);
+                        Err(msg) => {
+                            println!("[shell] Syntax error: {}", msg);
+                            state = State::Bad;
                         }
                     }
                 }
                 line.clear();
-                cwd = getcwd();
+                reap_jobs(&mut jobs);
+                cwd = getcwd().unwrap();
+                print_cwd(&state, &cwd);
+            }
+            CTRL_C => {
+                // drop the line being typed
+                println!("^C");
+                line.clear();
                 print_cwd(&state, &cwd);
             }
+            CTRL_Z => {}
             BS | DL => {
                 if !line.is_empty() {
                     print!("{}", BS as char);
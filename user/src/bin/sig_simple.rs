@@ -7,7 +7,7 @@ use user_lib::*;
 
 fn func() {
     println!("user_sig_test passed");
-    sigreturn();
+    sigreturn().unwrap();
 }
 
 #[no_mangle]
@@ -17,11 +17,11 @@ pub fn main() -> i32 {
     new.handler = func as usize;
 
     println!("signal_simple: sigaction");
-    if sigaction(SIGUSR1, Some(&new), Some(&mut old)) < 0 {
+    if sigaction(SIGUSR1, Some(&new), Some(&mut old)).is_err() {
         panic!("Sigaction failed!");
     }
     println!("signal_simple: kill");
-    if kill(getpid() as usize, SIGUSR1) < 0 {
+    if kill(getpid() as usize, SIGUSR1).is_err() {
         println!("Kill failed!");
         exit(1);
     }
@@ -7,14 +7,14 @@ use user_lib::*;
 
 fn func() {
     println!("func triggered");
-    sigreturn();
+    sigreturn().unwrap();
 }
 
 fn user_sig_test_failsignum() {
     let mut new = SignalAction::default();
     let mut old = SignalAction::default();
     new.handler = func as usize;
-    if sigaction(50, Some(&new), Some(&mut old)) >= 0 {
+    if sigaction(50, Some(&new), Some(&mut old)).is_ok() {
         panic!("Wrong sigaction but successed!");
     }
 }
@@ -24,31 +24,31 @@ fn user_sig_test_kill() {
     let mut old = SignalAction::default();
     new.handler = func as usize;
 
-    if sigaction(SIGUSR1, Some(&new), Some(&mut old)) < 0 {
+    if sigaction(SIGUSR1, Some(&new), Some(&mut old)).is_err() {
         panic!("Sigaction failed!");
     }
-    if kill(getpid() as usize, SIGUSR1) < 0 {
+    if kill(getpid() as usize, SIGUSR1).is_err() {
         println!("Kill failed!");
         exit(1);
     }
 }
 
 fn user_sig_test_multiprocsignals() {
-    let pid = fork();
+    let pid = fork().unwrap();
     if pid == 0 {
         let mut new = SignalAction::default();
         let mut old = SignalAction::default();
         new.handler = func as usize;
-        if sigaction(SIGUSR1, Some(&new), Some(&mut old)) < 0 {
+        if sigaction(SIGUSR1, Some(&new), Some(&mut old)).is_err() {
             panic!("Sigaction failed!");
         }
     } else {
-        if kill(pid as usize, SIGUSR1) < 0 {
+        if kill(pid, SIGUSR1).is_err() {
             println!("Kill failed!");
             exit(1);
         }
         let mut exit_code = 0;
-        wait(&mut exit_code);
+        wait(&mut exit_code).unwrap();
     }
 }
 
@@ -58,11 +58,11 @@ fn user_sig_test_restore() {
     let mut old2 = SignalAction::default();
     new.handler = func as usize;
 
-    if sigaction(SIGUSR1, Some(&new), Some(&mut old)) < 0 {
+    if sigaction(SIGUSR1, Some(&new), Some(&mut old)).is_err() {
         panic!("Sigaction failed!");
     }
 
-    if sigaction(SIGUSR1, Some(&old), Some(&mut old2)) < 0 {
+    if sigaction(SIGUSR1, Some(&old), Some(&mut old2)).is_err() {
         panic!("Sigaction failed!");
     }
 
@@ -73,24 +73,24 @@ fn user_sig_test_restore() {
 }
 
 fn kernel_sig_test_ignore() {
-    sigprocmask(SignalFlags::SIGSTOP.bits() as u32);
-    if kill(getpid() as usize, SignalFlags::SIGSTOP.bits()) < 0 {
+    sigprocmask(SignalFlags::SIGSTOP.bits() as u32).unwrap();
+    if kill(getpid() as usize, SIGSTOP).is_err() {
         println!("kill faild\n");
         exit(-1);
     }
 }
 
 fn kernel_sig_test_stop_cont() {
-    let pid = fork();
+    let pid = fork().unwrap();
     if pid == 0 {
-        kill(getpid() as usize, SIGSTOP);
+        kill(getpid() as usize, SIGSTOP).unwrap();
         sleep(500);
         exit(-1);
     } else {
         sleep(1000);
-        kill(pid as usize, SIGCONT);
+        kill(pid, SIGCONT).unwrap();
         let mut exit_code = 0;
-        wait(&mut exit_code);
+        wait(&mut exit_code).unwrap();
     }
 }
 
@@ -99,15 +99,15 @@ fn kernel_sig_test_failignorekill() {
     let mut old = SignalAction::default();
     new.handler = func as usize;
 
-    if sigaction(9, Some(&new), Some(&mut old)) >= 0 {
+    if sigaction(9, Some(&new), Some(&mut old)).is_ok() {
         panic!("Should not set sigaction to kill!");
     }
 
-    if sigaction(9, Some(&new), None) >= 0 {
+    if sigaction(9, Some(&new), None).is_ok() {
         panic!("Should not set sigaction to kill!");
     }
 
-    if sigaction(9, None, Some(&mut old)) >= 0 {
+    if sigaction(9, None, Some(&mut old)).is_ok() {
         panic!("Should not set sigaction to kill!");
     }
 }
@@ -118,39 +118,39 @@ fn final_sig_test() {
     new.handler = func as usize;
 
     let mut pipe_fd = [0usize; 2];
-    pipe(&mut pipe_fd);
+    pipe(&mut pipe_fd).unwrap();
 
-    let pid = fork();
+    let pid = fork().unwrap();
     if pid == 0 {
-        close(pipe_fd[0]);
-        if sigaction(SIGUSR1, Some(&new), Some(&mut old)) < 0 {
+        close(pipe_fd[0]).unwrap();
+        if sigaction(SIGUSR1, Some(&new), Some(&mut old)).is_err() {
             panic!("Sigaction failed!");
         }
-        write(pipe_fd[1], &[0u8]);
-        close(pipe_fd[1]);
+        write(pipe_fd[1], &[0u8]).unwrap();
+        close(pipe_fd[1]).unwrap();
         loop {}
     } else {
-        close(pipe_fd[1]);
+        close(pipe_fd[1]).unwrap();
         let mut buf = [0u8; 1];
-        assert_eq!(read(pipe_fd[0], &mut buf), 1);
-        close(pipe_fd[0]);
-        if kill(pid as usize, SIGUSR1) < 0 {
+        assert_eq!(read(pipe_fd[0], &mut buf), Ok(1));
+        close(pipe_fd[0]).unwrap();
+        if kill(pid, SIGUSR1).is_err() {
             println!("Kill failed!");
             exit(-1);
         }
         sleep(100);
-        kill(pid as usize, SIGKILL);
+        kill(pid, SIGKILL).unwrap();
     }
 }
 
 fn run(f: fn()) -> bool {
-    let pid = fork();
+    let pid = fork().unwrap();
     if pid == 0 {
         f();
         exit(0);
     } else {
         let mut exit_code: i32 = 0;
-        wait(&mut exit_code);
+        wait(&mut exit_code).unwrap();
         if exit_code != 0 {
             println!("FAILED!");
         } else {
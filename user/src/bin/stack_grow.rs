@@ -0,0 +1,85 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::hint::black_box;
+use user_lib::{
+    exit, fork, getrlimit, setrlimit, thread_create, waitpid, waittid, Errno, RLimit, RLIMIT_STACK,
+};
+
+const DEPTH: usize = 1024;
+
+/// Each frame takes more than 1 KiB of the stack
+fn recurse(depth: usize) -> usize {
+    let mut buf = [0u8; 1024];
+    buf[depth % 1024] = depth as u8;
+    let buf = black_box(&mut buf);
+    if depth == 0 {
+        return 0;
+    }
+    recurse(depth - 1) + buf[depth % 1024] as usize
+}
+
+fn expected(depth: usize) -> usize {
+    (1..=depth).map(|d| d as u8 as usize).sum()
+}
+
+fn thread_main(depth: usize) -> ! {
+    assert_eq!(recurse(depth), expected(depth));
+    exit(0)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let limit = getrlimit(RLIMIT_STACK).unwrap();
+    assert_eq!(limit.cur, limit.max);
+    assert!(limit.cur >= 2 * DEPTH * 1024);
+
+    // the stacks of the main thread and a new one grow to more than 1 MiB
+    assert_eq!(recurse(DEPTH), expected(DEPTH));
+    let tid = thread_create(thread_main as usize, DEPTH).unwrap();
+    assert_eq!(waittid(tid).unwrap(), 0);
+
+    let too_high = RLimit {
+        cur: limit.max + 1,
+        max: limit.max,
+    };
+    assert_eq!(setrlimit(RLIMIT_STACK, &too_high), Err(Errno::EINVAL));
+    let raised = RLimit {
+        cur: limit.max,
+        max: limit.max + 1,
+    };
+    assert_eq!(setrlimit(RLIMIT_STACK, &raised), Err(Errno::EPERM));
+    assert_eq!(getrlimit(0), Err(Errno::EINVAL));
+
+    // a child overflowing the lowered limit is killed by SIGSEGV, the limit is inherited
+    let low = RLimit {
+        cur: 64 * 1024,
+        max: limit.max,
+    };
+    let pid = fork().unwrap();
+    if pid == 0 {
+        setrlimit(RLIMIT_STACK, &low).unwrap();
+        let pid = fork().unwrap();
+        if pid == 0 {
+            assert_eq!(getrlimit(RLIMIT_STACK), Ok(low));
+            recurse(DEPTH);
+            return 0;
+        }
+        let mut exit_code = 0;
+        assert_eq!(waitpid(pid, &mut exit_code), Ok(pid));
+        return exit_code;
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), Ok(pid));
+    assert_eq!(exit_code, -11);
+
+    // the soft limit can be raised back up to the hard one
+    setrlimit(RLIMIT_STACK, &low).unwrap();
+    setrlimit(RLIMIT_STACK, &limit).unwrap();
+    assert_eq!(recurse(DEPTH), expected(DEPTH));
+    println!("stack_grow passed!");
+    0
+}
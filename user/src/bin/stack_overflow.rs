@@ -4,6 +4,8 @@
 #[macro_use]
 extern crate user_lib;
 
+use user_lib::{getrlimit, setrlimit, RLimit, RLIMIT_STACK};
+
 #[allow(unconditional_recursion)]
 fn f(depth: usize) {
     if depth % 10 == 0 {
@@ -14,6 +16,16 @@ fn f(depth: usize) {
 
 #[no_mangle]
 pub fn main() -> i32 {
+    // the stack can't grow beyond 64 KiB, so it overflows soon
+    let limit = getrlimit(RLIMIT_STACK).unwrap();
+    setrlimit(
+        RLIMIT_STACK,
+        &RLimit {
+            cur: 64 * 1024,
+            max: limit.max,
+        },
+    )
+    .unwrap();
     println!("It should trigger segmentation fault!");
     f(0);
     0
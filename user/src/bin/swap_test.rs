@@ -0,0 +1,45 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{brk, sbrk};
+
+const PAGE_SIZE: usize = 0x1000;
+/// More than the whole physical memory
+const TOTAL_SIZE: usize = 128 * 1024 * 1024;
+const STEP: usize = 1024 * 1024;
+
+fn pattern(i: usize) -> usize {
+    i.wrapping_mul(0x9e37_79b9_7f4a_7c15)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let base = sbrk(0).unwrap();
+    // grow the heap step by step, so the pages written before can be swapped out
+    for step in 0..TOTAL_SIZE / STEP {
+        let start = sbrk(STEP as isize).unwrap();
+        for i in (0..STEP).step_by(PAGE_SIZE) {
+            unsafe { *((start + i) as *mut usize) = pattern((step * STEP + i) / PAGE_SIZE) };
+        }
+    }
+    println!("swap_test: {} MiB written", TOTAL_SIZE / 1024 / 1024);
+    // every page is read back, from the disk if it's swapped out
+    for i in (0..TOTAL_SIZE).step_by(PAGE_SIZE) {
+        let val = unsafe { *((base + i) as *const usize) };
+        assert_eq!(val, pattern(i / PAGE_SIZE));
+    }
+    // and can be changed again
+    for i in (0..TOTAL_SIZE).step_by(PAGE_SIZE * 7) {
+        unsafe { *((base + i + 8) as *mut usize) = !pattern(i / PAGE_SIZE) };
+    }
+    for i in (0..TOTAL_SIZE).step_by(PAGE_SIZE * 7) {
+        let val = unsafe { *((base + i + 8) as *const usize) };
+        assert_eq!(val, !pattern(i / PAGE_SIZE));
+    }
+    assert_eq!(brk(base), Ok(base));
+    println!("swap_test passed!");
+    0
+}
@@ -18,10 +18,25 @@ static SUCC_TESTS: &[(&str, &str, &str, &str, i32)] = &[
     ("forktest2\0", "\0", "\0", "\0", 0),
     ("forktree\0", "\0", "\0", "\0", 0),
     ("huge_write\0", "\0", "\0", "\0", 0),
+    ("seektest\0", "\0", "\0", "\0", 0),
+    ("openflags\0", "\0", "\0", "\0", 0),
+    ("bad_syscall\0", "\0", "\0", "\0", 0),
+    ("bad_pointer\0", "\0", "\0", "\0", 0),
+    ("fault_signals\0", "\0", "\0", "\0", 0),
+    ("brk_test\0", "\0", "\0", "\0", 0),
+    ("mmap_test\0", "\0", "\0", "\0", 0),
+    ("fork_bench\0", "\0", "\0", "\0", 0),
+    ("lazy_load\0", "\0", "\0", "\0", 0),
+    ("swap_test\0", "\0", "\0", "\0", 0),
+    ("stack_grow\0", "\0", "\0", "\0", 0),
+    ("priority_test\0", "\0", "\0", "\0", 0),
+    ("rusage_test\0", "\0", "\0", "\0", 0),
     ("matrix\0", "\0", "\0", "\0", 0),
     ("phil_din_mutex\0", "\0", "\0", "\0", 0),
     ("pipe_large_test\0", "\0", "\0", "\0", 0),
     ("pipetest\0", "\0", "\0", "\0", 0),
+    ("pipe_block_test\0", "\0", "\0", "\0", 0),
+    ("dup2_test\0", "\0", "\0", "\0", 0),
     ("adder_peterson_spin\0", "\0", "\0", "\0", 0),
     ("adder_peterson_yield\0", "\0", "\0", "\0", 0),
     ("adder_mutex_blocking\0", "\0", "\0", "\0", 0),
@@ -86,13 +101,13 @@ fn run_tests(tests: &[(&str, &str, &str, &str, i32)]) -> i32 {
             arr[3] = core::ptr::null::<u8>();
         }
 
-        let pid = fork();
+        let pid = fork().unwrap();
         if pid == 0 {
             exec(format!("/tests/{}",test.0).as_str(), &arr[..]);
             panic!("unreachable!");
         } else {
             let mut exit_code: i32 = Default::default();
-            let wait_pid = waitpid(pid as usize, &mut exit_code);
+            let wait_pid = waitpid(pid, &mut exit_code).unwrap();
             assert_eq!(pid, wait_pid);
             if exit_code == test.4 {
                 // summary apps with  exit_code
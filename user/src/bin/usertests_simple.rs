@@ -25,7 +25,7 @@ use user_lib::{exec, fork, waitpid};
 pub fn main() -> i32 {
     for test in TESTS {
         println!("Usertests: Running {}", test);
-        let pid = fork();
+        let pid = fork().unwrap();
         if pid == 0 {
             exec(
                 format!("/tests/{}", *test).as_str(),
@@ -34,7 +34,7 @@ pub fn main() -> i32 {
             panic!("unreachable!");
         } else {
             let mut exit_code: i32 = Default::default();
-            let wait_pid = waitpid(pid as usize, &mut exit_code);
+            let wait_pid = waitpid(pid, &mut exit_code).unwrap();
             assert_eq!(pid, wait_pid);
             println!(
                 "\x1b[32mUsertests: Test {} in Process {} exited with code {}\x1b[0m",
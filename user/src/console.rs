@@ -9,7 +9,7 @@ use crate::{read, write};
 
 impl Write for Stdout {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        write(STDOUT, s.as_bytes());
+        write(STDOUT, s.as_bytes()).ok();
         Ok(())
     }
 }
@@ -34,6 +34,6 @@ macro_rules! println {
 
 pub fn getchar() -> u8 {
     let mut buf = [0u8; 1];
-    read(STDIN, &mut buf);
+    read(STDIN, &mut buf).ok();
     buf[0]
 }
@@ -0,0 +1,132 @@
+//! Error numbers returned by the kernel, the same values as Linux
+
+use core::fmt::Display;
+
+/// A failed syscall returns `-errno`
+#[repr(isize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Errno {
+    EPERM = 1,
+    ENOENT = 2,
+    ESRCH = 3,
+    EINTR = 4,
+    EIO = 5,
+    ENOEXEC = 8,
+    EBADF = 9,
+    ECHILD = 10,
+    EAGAIN = 11,
+    ENOMEM = 12,
+    EACCES = 13,
+    EFAULT = 14,
+    EBUSY = 16,
+    EEXIST = 17,
+    ENODEV = 19,
+    ENOTDIR = 20,
+    EISDIR = 21,
+    EINVAL = 22,
+    ETXTBSY = 26,
+    ENOSPC = 28,
+    ESPIPE = 29,
+    ERANGE = 34,
+    EDEADLK = 35,
+    ENAMETOOLONG = 36,
+    ENOSYS = 38,
+    ENOTEMPTY = 39,
+    /// A number not known here, e.g. from a newer kernel
+    Unknown(isize),
+}
+
+impl Errno {
+    /// The errno of a failed syscall, `Unknown` if it is none of the above
+    pub(crate) fn from_raw(errno: isize) -> Self {
+        match errno {
+            1 => Errno::EPERM,
+            2 => Errno::ENOENT,
+            3 => Errno::ESRCH,
+            4 => Errno::EINTR,
+            5 => Errno::EIO,
+            8 => Errno::ENOEXEC,
+            9 => Errno::EBADF,
+            10 => Errno::ECHILD,
+            11 => Errno::EAGAIN,
+            12 => Errno::ENOMEM,
+            13 => Errno::EACCES,
+            14 => Errno::EFAULT,
+            16 => Errno::EBUSY,
+            17 => Errno::EEXIST,
+            19 => Errno::ENODEV,
+            20 => Errno::ENOTDIR,
+            21 => Errno::EISDIR,
+            22 => Errno::EINVAL,
+            26 => Errno::ETXTBSY,
+            28 => Errno::ENOSPC,
+            29 => Errno::ESPIPE,
+            34 => Errno::ERANGE,
+            35 => Errno::EDEADLK,
+            36 => Errno::ENAMETOOLONG,
+            38 => Errno::ENOSYS,
+            39 => Errno::ENOTEMPTY,
+            _ => Errno::Unknown(errno),
+        }
+    }
+
+    /// The number of the error, the syscall returns its negative
+    pub fn raw(self) -> isize {
+        match self {
+            Errno::Unknown(errno) => errno,
+            // the enum is `repr(isize)`, so its discriminant comes first, which is the number
+            _ => unsafe { *(&self as *const Self as *const isize) },
+        }
+    }
+}
+
+/// Describe the error, like strerror in C
+pub fn strerror(errno: Errno) -> &'static str {
+    match errno {
+        Errno::EPERM => "Operation not permitted",
+        Errno::ENOENT => "No such file or directory",
+        Errno::ESRCH => "No such process",
+        Errno::EINTR => "Interrupted system call",
+        Errno::EIO => "I/O error",
+        Errno::ENOEXEC => "Exec format error",
+        Errno::EBADF => "Bad file descriptor",
+        Errno::ECHILD => "No child processes",
+        Errno::EAGAIN => "Try again",
+        Errno::ENOMEM => "Out of memory",
+        Errno::EACCES => "Permission denied",
+        Errno::EFAULT => "Bad address",
+        Errno::EBUSY => "Device or resource busy",
+        Errno::EEXIST => "File exists",
+        Errno::ENODEV => "No such device",
+        Errno::ENOTDIR => "Not a directory",
+        Errno::EISDIR => "Is a directory",
+        Errno::EINVAL => "Invalid argument",
+        Errno::ETXTBSY => "Text file busy",
+        Errno::ENOSPC => "No space left on device",
+        Errno::ESPIPE => "Illegal seek",
+        Errno::ERANGE => "Result out of range",
+        Errno::EDEADLK => "Resource deadlock would occur",
+        Errno::ENAMETOOLONG => "File name too long",
+        Errno::ENOSYS => "Function not implemented",
+        Errno::ENOTEMPTY => "Directory not empty",
+        Errno::Unknown(_) => "Unknown error",
+    }
+}
+
+impl Display for Errno {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Errno::Unknown(errno) => write!(f, "Unknown error {}", errno),
+            _ => write!(f, "{}", strerror(*self)),
+        }
+    }
+}
+
+/// Turn the return value of a syscall into a `Result`
+pub fn check(ret: isize) -> Result<usize, Errno> {
+    if ret < 0 {
+        Err(Errno::from_raw(-ret))
+    } else {
+        Ok(ret as usize)
+    }
+}
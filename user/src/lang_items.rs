@@ -1,4 +1,4 @@
-use crate::{getpid, kill, SignalFlags};
+use crate::{exit, getpid, kill, SIGABRT};
 
 #[panic_handler]
 fn panic_handler(panic_info: &core::panic::PanicInfo) -> ! {
@@ -13,6 +13,9 @@ fn panic_handler(panic_info: &core::panic::PanicInfo) -> ! {
     } else {
         println!("Panicked: {:?}", err);
     }
-    kill(getpid() as usize, SignalFlags::SIGABRT.bits());
-    unreachable!()
+    // the signal kills us on the way back to the user, unless it's blocked or caught
+    if let Err(errno) = kill(getpid() as usize, SIGABRT) {
+        println!("Failed to send SIGABRT: {}", errno);
+    }
+    exit(-6)
 }
@@ -6,6 +6,7 @@ extern crate alloc;
 
 #[macro_use]
 pub mod console;
+pub mod errno;
 mod lang_items;
 mod logging;
 pub mod syscall;
@@ -13,17 +14,41 @@ pub mod syscall;
 use alloc::format;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::fmt::Display;
 
 use bitflags::bitflags;
+use errno::check;
+pub use errno::{strerror, Errno};
 // use buddy_system_allocator::LockedHeap;
-use buddy::Heap;
+use buddy::{Heap, HeapInner};
+use core::alloc::Layout;
+use core::cmp::max;
 
 const USER_HEAP_SIZE: usize = 16 * 1024;
 static mut USER_HEAP_SPACE: [u8; USER_HEAP_SIZE] = [0; USER_HEAP_SIZE];
 #[global_allocator]
-static USER_HEAP: Heap = Heap::new();
+static USER_HEAP: Heap = Heap::with_rescue(grow_heap);
 // static HEAP: LockedHeap = LockedHeap::empty();
 
+/// Extend the heap by sbrk when it runs out, by at least `USER_HEAP_SIZE`
+fn grow_heap(heap: &mut HeapInner, layout: &Layout) {
+    let size = max(
+        max(layout.size(), layout.align()).next_power_of_two(),
+        USER_HEAP_SIZE,
+    );
+    let Ok(old_brk) = sbrk(0) else {
+        return;
+    };
+    // a buddy block is aligned to its size
+    let start = (old_brk + size - 1) & !(size - 1);
+    if brk(start + size).is_ok() {
+        unsafe {
+            heap.add_to_heap(old_brk, start + size - old_brk);
+        }
+    }
+}
+
 #[alloc_error_handler]
 pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
     panic!("Heap allocation error, layout = {:?}", layout);
@@ -63,7 +88,7 @@ pub fn main(_argc: usize, _argv: &[&str]) -> i32 {
 fn init() {
     unsafe {
         USER_HEAP
-            .borrow_mut()
+            .lock()
             .init(USER_HEAP_SPACE.as_ptr() as usize, USER_HEAP_SIZE);
         // HEAP.lock().init(USER_HEAP_SPACE.as_ptr() as usize, USER_HEAP_SIZE);
     }
@@ -76,25 +101,39 @@ bitflags! {
         const RDONLY = 0;
         const WRONLY = 1;
         const RDWR = 1 << 1;
+        const EXCL = 1 << 7;
         const CREATE = 1 << 9;
         const TRUNC = 1 << 10;
+        const APPEND = 1 << 11;
+        const DIRECTORY = 1 << 16;
     }
 }
-pub fn dup(fd: usize) -> isize {
-    sys_dup(fd)
+pub fn dup(fd: usize) -> Result<usize, Errno> {
+    check(sys_dup(fd))
+}
+/// Make `new_fd` refer to the file of `old_fd`, closing the file at `new_fd` first
+pub fn dup2(old_fd: usize, new_fd: usize) -> Result<usize, Errno> {
+    check(sys_dup2(old_fd, new_fd))
 }
-pub fn open(path: &str, flags: OpenFlags) -> isize {
-    sys_open(path, flags.bits)
+pub fn open(path: &str, flags: OpenFlags) -> Result<usize, Errno> {
+    check(sys_open(path, flags.bits))
 }
-pub fn close(fd: usize) -> isize {
-    sys_close(fd)
+pub fn close(fd: usize) -> Result<usize, Errno> {
+    check(sys_close(fd))
 }
-pub fn read(fd: usize, buf: &mut [u8]) -> isize {
-    sys_read(fd, buf)
+pub fn read(fd: usize, buf: &mut [u8]) -> Result<usize, Errno> {
+    check(sys_read(fd, buf))
 }
 
-pub fn write(fd: usize, buf: &[u8]) -> isize {
-    sys_write(fd, buf)
+pub fn write(fd: usize, buf: &[u8]) -> Result<usize, Errno> {
+    check(sys_write(fd, buf))
+}
+pub const SEEK_SET: usize = 0;
+pub const SEEK_CUR: usize = 1;
+pub const SEEK_END: usize = 2;
+/// Move the offset of `fd` relative to `whence`, return the new offset
+pub fn lseek(fd: usize, offset: isize, whence: usize) -> Result<usize, Errno> {
+    check(sys_lseek(fd, offset, whence))
 }
 pub fn exit(exit_code: i32) -> ! {
     sys_exit(exit_code)
@@ -108,51 +147,205 @@ pub fn get_time() -> isize {
 pub fn getpid() -> isize {
     sys_getpid()
 }
-pub fn fork() -> isize {
-    sys_fork()
+
+/// `which` of `setpriority` and `getpriority`, `who` is a pid or 0 for the current process
+pub const PRIO_PROCESS: usize = 0;
+
+/// Set the nice value, from -20 (the most CPU time) to 19 (the least)
+pub fn setpriority(which: usize, who: usize, nice: i32) -> Result<usize, Errno> {
+    check(sys_setpriority(which, who, nice))
+}
+pub fn getpriority(which: usize, who: usize) -> Result<i32, Errno> {
+    // the kernel returns 20 - nice
+    check(sys_getpriority(which, who)).map(|prio| 20 - prio as i32)
+}
+/// Add `inc` to the nice value of the current process, return the new one
+pub fn nice(inc: i32) -> Result<i32, Errno> {
+    let nice = getpriority(PRIO_PROCESS, 0)? + inc;
+    setpriority(PRIO_PROCESS, 0, nice)?;
+    getpriority(PRIO_PROCESS, 0)
+}
+/// Set the program break, i.e. the end of the heap, return the new one
+pub fn brk(addr: usize) -> Result<usize, Errno> {
+    check(sys_brk(addr))
+}
+/// Move the program break by `increment`, return the old one
+pub fn sbrk(increment: isize) -> Result<usize, Errno> {
+    let old_brk = check(sys_brk(0))?;
+    if increment != 0 {
+        brk((old_brk as isize + increment) as usize)?;
+    }
+    Ok(old_brk)
 }
-pub fn exec(path: &str, args: &[*const u8]) -> isize {
-    sys_exec(path, args)
+
+bitflags! {
+    /// Protection of the pages mapped by `mmap`
+    pub struct MmapProt: u32 {
+        const READ = 1 << 0;
+        const WRITE = 1 << 1;
+        const EXEC = 1 << 2;
+    }
 }
-pub fn wait(exit_code: &mut i32) -> isize {
-    loop {
-        match sys_waitpid(-1, exit_code as *mut _) {
-            -2 => {
-                yield_();
-            }
-            exit_pid => {
-                return exit_pid;
-            }
-        }
+
+bitflags! {
+    /// Only private mappings are supported
+    pub struct MmapFlags: u32 {
+        const PRIVATE = 1 << 1;
+        const ANONYMOUS = 1 << 5;
     }
 }
-pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
+
+/// Map `len` bytes of zeros, or of the file `fd` from `offset`, return the start address.
+/// `addr` is only a hint.
+pub fn mmap(
+    addr: usize,
+    len: usize,
+    prot: MmapProt,
+    flags: MmapFlags,
+    fd: usize,
+    offset: usize,
+) -> Result<usize, Errno> {
+    check(sys_mmap(addr, len, prot.bits, flags.bits, fd, offset))
+}
+pub fn munmap(addr: usize, len: usize) -> Result<usize, Errno> {
+    check(sys_munmap(addr, len))
+}
+pub fn mprotect(addr: usize, len: usize, prot: MmapProt) -> Result<usize, Errno> {
+    check(sys_mprotect(addr, len, prot.bits))
+}
+
+/// The size of the user stacks, which grow on page faults up to the soft limit `cur`
+pub const RLIMIT_STACK: usize = 3;
+
+/// A resource limit, `cur` can be raised up to `max` and `max` can only be lowered
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RLimit {
+    pub cur: usize,
+    pub max: usize,
+}
+
+pub fn getrlimit(resource: usize) -> Result<RLimit, Errno> {
+    let mut rlim = RLimit::default();
+    check(sys_getrlimit(resource, &mut rlim))?;
+    Ok(rlim)
+}
+pub fn setrlimit(resource: usize, rlim: &RLimit) -> Result<usize, Errno> {
+    check(sys_setrlimit(resource, rlim))
+}
+
+/// `who` of `getrusage`: the current process, its children reaped by `waitpid`, or the current thread
+pub const RUSAGE_SELF: isize = 0;
+pub const RUSAGE_CHILDREN: isize = -1;
+pub const RUSAGE_THREAD: isize = 1;
+
+/// The CPU time used and what happened meanwhile
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RUsage {
+    /// Time in user mode, in microseconds
+    pub utime_us: usize,
+    /// Time in the kernel, in microseconds
+    pub stime_us: usize,
+    /// Voluntary context switches, e.g. sleeping or waiting for a lock
+    pub nvcsw: usize,
+    /// Involuntary context switches, by preemption or yield
+    pub nivcsw: usize,
+    pub page_faults: usize,
+}
+
+pub fn getrusage(who: isize) -> Result<RUsage, Errno> {
+    let mut usage = RUsage::default();
+    check(sys_getrusage(who, &mut usage))?;
+    Ok(usage)
+}
+/// Return the pid of the child in the parent, and 0 in the child
+pub fn fork() -> Result<usize, Errno> {
+    check(sys_fork())
+}
+/// Only returns if the exec failed
+pub fn exec(path: &str, args: &[*const u8]) -> Errno {
+    Errno::from_raw(-sys_exec(path, args))
+}
+/// Wait for any child to exit, return its pid
+pub fn wait(exit_code: &mut i32) -> Result<usize, Errno> {
+    waitpid_with(-1, exit_code, 0)
+}
+pub fn waitpid(pid: usize, exit_code: &mut i32) -> Result<usize, Errno> {
+    waitpid_with(pid as isize, exit_code, 0)
+}
+/// Like `waitpid`, but EAGAIN if the child is still running
+pub fn waitpid_nb(pid: usize, exit_code: &mut i32) -> Result<usize, Errno> {
+    waitpid_with(pid as isize, exit_code, WNOHANG)
+}
+/// Wait again after a signal is handled
+fn waitpid_with(pid: isize, exit_code: &mut i32, options: usize) -> Result<usize, Errno> {
     loop {
-        match sys_waitpid(pid as isize, exit_code as *mut _) {
-            -2 => {
-                yield_();
-            }
-            exit_pid => {
-                return exit_pid;
-            }
+        match check(sys_waitpid(pid, exit_code as *mut _, options)) {
+            Err(Errno::EINTR) => {}
+            ret => return ret,
         }
     }
 }
-pub fn waitpid_nb(pid: usize, exit_code: &mut i32) -> isize {
-    sys_waitpid(pid as isize, exit_code as *mut _)
+
+/// Don't wait if no child has changed its state, fail with EAGAIN
+pub const WNOHANG: usize = 1;
+/// Report the stopped children too
+pub const WUNTRACED: usize = 2;
+
+/// Wait for a child chosen by `pid`, -1 for any child and `-pgid` for one in the group.
+/// Return its pid, its status and its usage if it has exited. The status is decoded by
+/// `wifexited` and the others, like Linux. A signal interrupts the wait with EINTR.
+pub fn wait4(pid: isize, options: usize) -> Result<(usize, i32, RUsage), Errno> {
+    let mut status = 0;
+    let mut usage = RUsage::default();
+    let pid = check(sys_wait4(pid, &mut status, options, &mut usage))?;
+    Ok((pid, status, usage))
+}
+pub fn wifexited(status: i32) -> bool {
+    status & 0x7f == 0
+}
+pub fn wexitstatus(status: i32) -> i32 {
+    (status >> 8) & 0xff
+}
+pub fn wifsignaled(status: i32) -> bool {
+    status & 0x7f != 0 && status & 0x7f != 0x7f
+}
+pub fn wtermsig(status: i32) -> i32 {
+    status & 0x7f
+}
+pub fn wifstopped(status: i32) -> bool {
+    status & 0xff == 0x7f
+}
+pub fn wstopsig(status: i32) -> i32 {
+    (status >> 8) & 0xff
+}
+
+/// Move process `pid` to group `pgid`, 0 for the caller and a group of its own
+pub fn setpgid(pid: usize, pgid: usize) -> Result<usize, Errno> {
+    check(sys_setpgid(pid, pgid))
+}
+pub fn getpgid(pid: usize) -> Result<usize, Errno> {
+    check(sys_getpgid(pid))
+}
+/// Make group `pgid` the foreground of the console, which gets SIGINT and SIGTSTP for
+/// Ctrl-C and Ctrl-Z. None takes the keys back.
+pub fn tcsetpgrp(pgid: Option<usize>) -> Result<usize, Errno> {
+    check(sys_tcsetpgrp(pgid.map_or(-1, |pgid| pgid as isize)))
 }
 pub fn sleep(time_ms: usize) {
     sys_sleep(time_ms);
 }
 
-pub fn pipe(pipe_fd: &mut [usize]) -> isize {
-    sys_pipe(pipe_fd)
+pub fn pipe(pipe_fd: &mut [usize]) -> Result<usize, Errno> {
+    check(sys_pipe(pipe_fd))
 }
 
 /// Action for a signal
 #[repr(C, align(16))]
 #[derive(Debug, Clone, Copy)]
 pub struct SignalAction {
+    /// Called with the signal number, and the fault address for SIGILL, SIGTRAP, SIGBUS and SIGSEGV
     pub handler: usize,
     pub mask: SignalFlags,
 }
@@ -237,116 +430,191 @@ bitflags! {
 }
 
 /// Send a signal to some process from current process.
-pub fn kill(pid: usize, signum: i32) -> isize {
-    sys_kill(pid, signum)
+pub fn kill(pid: usize, signum: i32) -> Result<usize, Errno> {
+    check(sys_kill(pid, signum))
 }
 
 pub fn sigaction(
     signum: i32,
     action: Option<&SignalAction>,
     old_action: Option<&mut SignalAction>,
-) -> isize {
-    sys_sigaction(
+) -> Result<usize, Errno> {
+    check(sys_sigaction(
         signum,
         action.map_or(core::ptr::null(), |a| a),
         old_action.map_or(core::ptr::null_mut(), |a| a),
-    )
+    ))
 }
 
-pub fn sigprocmask(mask: u32) -> isize {
-    sys_sigprocmask(mask)
+/// Set the blocked signals, return the old mask
+pub fn sigprocmask(mask: u32) -> Result<u32, Errno> {
+    check(sys_sigprocmask(mask)).map(|old_mask| old_mask as u32)
 }
 
-pub fn sigreturn() -> isize {
-    sys_sigreturn()
+pub fn sigreturn() -> Result<usize, Errno> {
+    check(sys_sigreturn())
 }
 
-pub fn thread_create(entry: usize, arg: usize) -> isize {
-    sys_thread_create(entry, arg)
+/// Return the tid of the new thread
+pub fn thread_create(entry: usize, arg: usize) -> Result<usize, Errno> {
+    check(sys_thread_create(entry, arg))
 }
 
-pub fn waittid(tid: usize) -> isize {
+/// Wait for the thread to exit, return its exit code
+pub fn waittid(tid: usize) -> Result<i32, Errno> {
     loop {
-        match sys_waittid(tid) {
-            -2 => {
+        match check(sys_waittid(tid)) {
+            Err(Errno::EAGAIN) => {
                 yield_();
             }
-            exit_code => return exit_code,
+            ret => return ret.map(|exit_code| exit_code as i32),
         }
     }
 }
-pub fn spin_mutex_create() -> isize {
-    sys_mutex_create(false)
+pub fn spin_mutex_create() -> Result<usize, Errno> {
+    check(sys_mutex_create(false))
+}
+pub fn blocked_mutex_create() -> Result<usize, Errno> {
+    check(sys_mutex_create(true))
 }
-pub fn blocked_mutex_create() -> isize {
-    sys_mutex_create(true)
+pub fn mutex_lock(mutex_id: usize) -> Result<usize, Errno> {
+    check(sys_mutex_lock(mutex_id))
 }
-pub fn mutex_lock(mutex_id: usize) {
-    sys_mutex_lock(mutex_id);
+pub fn mutex_unlock(mutex_id: usize) -> Result<usize, Errno> {
+    check(sys_mutex_unlock(mutex_id))
 }
-pub fn mutex_unlock(mutex_id: usize) {
-    sys_mutex_unlock(mutex_id);
+pub fn condvar_create() -> Result<usize, Errno> {
+    check(sys_condvar_create())
 }
-pub fn condvar_create() -> isize {
-    sys_condvar_create()
+pub fn condvar_signal(condvar_id: usize) -> Result<usize, Errno> {
+    check(sys_condvar_signal(condvar_id))
+}
+pub fn condvar_wait(condvar_id: usize, mutex_id: usize) -> Result<usize, Errno> {
+    check(sys_condvar_wait(condvar_id, mutex_id))
+}
+
+
+/// d_type of a `Dirent`
+pub const DT_DIR: u8 = 4;
+pub const DT_REG: u8 = 8;
+
+/// A directory entry parsed from the records filled by `getdents`
+pub struct Dirent {
+    pub ino: u64,
+    pub type_: u8,
+    pub name: String,
 }
-pub fn condvar_signal(condvar_id: usize) {
-    sys_condvar_signal(condvar_id);
+
+pub fn getdents(fd: usize, buf: &mut [u8]) -> Result<usize, Errno> {
+    check(sys_getdents(fd, buf))
 }
-pub fn condvar_wait(condvar_id: usize, mutex_id: usize) {
-    sys_condvar_wait(condvar_id, mutex_id);
+
+/// Read all entries of the directory `fd`
+pub fn read_dir(fd: usize) -> Result<Vec<Dirent>, Errno> {
+    let mut buf = [0u8; 256];
+    let mut entries = Vec::new();
+    loop {
+        let len = getdents(fd, &mut buf)?;
+        if len == 0 {
+            break;
+        }
+        // ino: u64, off: u64, reclen: u16, type: u8, then the name
+        let mut pos = 0;
+        while pos < len {
+            let record = &buf[pos..];
+            let reclen = u16::from_le_bytes([record[16], record[17]]) as usize;
+            let name = &record[19..reclen];
+            let name_len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+            entries.push(Dirent {
+                ino: u64::from_le_bytes(record[0..8].try_into().unwrap()),
+                type_: record[18],
+                name: String::from_utf8_lossy(&name[..name_len]).to_string(),
+            });
+            pos += reclen;
+        }
+    }
+    Ok(entries)
 }
 
+bitflags! {
+    /// File type in `Stat`, the same values as st_mode in Linux
+    pub struct StatMode: u32 {
+        const NULL = 0;
+        const FIFO = 0o010000;
+        const CHR = 0o020000;
+        const DIR = 0o040000;
+        const FILE = 0o100000;
+    }
+}
 
-pub const MAX_NAME_LENGTH: usize = 27;
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
-pub struct DirEntry {
-    pub name: [u8; MAX_NAME_LENGTH + 1],
-    pub inode_id: u32,
+pub struct Stat {
+    pub ino: u64,
+    pub mode: StatMode,
+    pad: u32,
+    pub size: u64,
+    pub blocks: u64,
+}
+
+impl Stat {
+    pub fn new() -> Self {
+        Self {
+            ino: 0,
+            mode: StatMode::NULL,
+            pad: 0,
+            size: 0,
+            blocks: 0,
+        }
+    }
 }
-impl DirEntry {
-    pub fn is_empty(&self) -> bool {
-        self.inode_id == 0 && self.name.iter().all(|&c| c == 0)
+
+impl Display for Stat {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let type_ = if self.mode.contains(StatMode::DIR) {
+            "Directory"
+        } else if self.mode.contains(StatMode::FILE) {
+            "File"
+        } else if self.mode.contains(StatMode::FIFO) {
+            "Pipe"
+        } else if self.mode.contains(StatMode::CHR) {
+            "CharDevice"
+        } else {
+            "Unknown"
+        };
+        write!(
+            f,
+            "Type: {:<15} Size: {:<15} Inode: {:<15} Blocks: {:<15}",
+            type_, self.size, self.ino, self.blocks
+        )
     }
 }
 
-pub fn fstat(fd: usize) -> isize {
-    sys_fstat(fd)
+pub fn fstat(fd: usize, st: &mut Stat) -> Result<usize, Errno> {
+    check(sys_fstat(fd, st as *mut _))
 }
-pub fn cd(path: &str) -> isize {
+pub fn cd(path: &str) -> Result<usize, Errno> {
     // println!("cd {}", path);
-    sys_cd(path)
+    check(sys_cd(path))
 }
-pub fn cp(src: &str, dst: &str) -> isize {
+pub fn cp(src: &str, dst: &str) -> Result<usize, Errno> {
     // println!("cp {} {}", src, dst);
-    sys_cp(src, dst)
+    check(sys_cp(src, dst))
 }
-pub fn mv(src: &str, dst: &str) -> isize {
+pub fn mv(src: &str, dst: &str) -> Result<usize, Errno> {
     // println!("mv {} {}", src, dst);
-    sys_mv(src, dst)
+    check(sys_mv(src, dst))
 }
-pub fn rm(path: &str) -> isize {
+pub fn rm(path: &str) -> Result<usize, Errno> {
     // println!("rm {}", path);
-    sys_rm(path)
+    check(sys_rm(path))
 }
 
-pub fn getcwd() -> String {
+pub fn getcwd() -> Result<String, Errno> {
     let mut buf = [0u8; 1024];
-    let len = sys_getcwd(&mut buf);
-    assert!(len >= 0, "getcwd failed with error code: {}", len);
-    let len = len as usize;
-    let str = String::from_utf8_lossy(&buf[..len]).parse().unwrap();
-    str
+    let len = check(sys_getcwd(&mut buf))?;
+    Ok(String::from_utf8_lossy(&buf[..len]).into_owned())
 }
-pub fn get_abs_path(path: &str) -> String {
-    if path.starts_with('/') {
-        path.to_string()
-    } else {
-        format!("{}{}", getcwd(), path)
-    }
-}
-
 pub fn get_env_var_path() -> Vec<String> {
     Vec::from(["/bin/", "/tests/"])
         .into_iter()
@@ -359,18 +627,15 @@ pub fn get_exe_path(path: &str) -> Option<String> {
     if path.starts_with('/') {
         Some(path.to_string())
     } else {
-        // try in cwd
-        let abs_path = get_abs_path(path);
-        let fd = open(&abs_path, OpenFlags::RDONLY);
-        if fd >= 0 {
-            close(fd as usize);
-            return Some(abs_path);
+        // try in cwd, the kernel resolves relative paths
+        if let Ok(fd) = open(path, OpenFlags::RDONLY) {
+            close(fd).ok();
+            return Some(path.to_string());
         }
         // try in environment variable PATH
         for env_path in get_env_var_path() {
-            let fd = open(&format!("{}{}", env_path, path), OpenFlags::RDONLY);
-            if fd >= 0 {
-                close(fd as usize);
+            if let Ok(fd) = open(&format!("{}{}", env_path, path), OpenFlags::RDONLY) {
+                close(fd).ok();
                 return Some(format!("{}{}", env_path, path));
             }
         }